@@ -31,7 +31,7 @@ pub fn new_form_data() -> FormDataBody {
 /// Hyphens are dropped — callers want a plain alphanumeric token — leaving 32 hex
 /// chars per uuid, then the result is cut to `len`. A multipart boundary only has
 /// to be absent from the body of that one request, so this is ample.
-fn rand_string(len: usize) -> String {
+pub(crate) fn rand_string(len: usize) -> String {
     let mut result = String::with_capacity(len);
 
     // One uuid covers len <= 32; the loop only matters if a caller wants more.
@@ -0,0 +1,1086 @@
+//! HTTP content codings — the `Content-Encoding` / `Accept-Encoding` tokens FlUrl
+//! understands and the codecs behind them. Every codec here is pure Rust
+//! (flate2's `rust_backend`, `brotli`, `ruzstd`), so the module is shared by both
+//! backends: both compress request bodies, while only the native one decodes
+//! responses itself — under wasm the browser does that.
+
+use crate::FlUrlError;
+
+/// A content coding from the HTTP registry, as used in `Accept-Encoding` and
+/// `Content-Encoding`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    Gzip,
+    /// zlib-wrapped DEFLATE, which is what `deflate` means in HTTP. Raw DEFLATE
+    /// streams, which some servers send under the same name, are decoded too.
+    Deflate,
+    Brotli,
+    Zstd,
+}
+
+impl Encoding {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+            Self::Brotli => "br",
+            Self::Zstd => "zstd",
+        }
+    }
+
+    /// Parses one coding token, case-insensitively. `x-gzip` is the legacy
+    /// alias of `gzip` (RFC 9110, 8.4.1.3).
+    pub fn from_token(token: &str) -> Option<Self> {
+        let token = token.trim();
+
+        if token.eq_ignore_ascii_case("gzip") || token.eq_ignore_ascii_case("x-gzip") {
+            return Some(Self::Gzip);
+        }
+
+        if token.eq_ignore_ascii_case("deflate") {
+            return Some(Self::Deflate);
+        }
+
+        if token.eq_ignore_ascii_case("br") {
+            return Some(Self::Brotli);
+        }
+
+        if token.eq_ignore_ascii_case("zstd") {
+            return Some(Self::Zstd);
+        }
+
+        None
+    }
+
+    /// The `Accept-Encoding` value advertising `encodings`, in the given order.
+    pub fn to_accept_encoding(encodings: &[Encoding]) -> String {
+        let mut result = String::new();
+
+        for encoding in encodings {
+            if result.split(", ").any(|token| token == encoding.as_str()) {
+                continue;
+            }
+
+            if !result.is_empty() {
+                result.push_str(", ");
+            }
+            result.push_str(encoding.as_str());
+        }
+
+        result
+    }
+}
+
+impl std::fmt::Display for Encoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// How `FlUrl::compress_with` compresses a request body.
+///
+/// `level` is the codec's own scale, `None` picking FlUrl's default for it:
+///
+/// | `algorithm` | levels | default |
+/// | --- | --- | --- |
+/// | `Gzip`, `Deflate` | 0 (stored) ..= 9 | 6 |
+/// | `Brotli` | 0 ..= 11 | 5 |
+/// | `Zstd` | 1 | 1 |
+///
+/// zstd is encoded by `ruzstd`, which implements only its fastest level, so
+/// level 1 is the only one there is. A level outside the range fails the request
+/// with [`FlUrlError::CompressionError`].
+///
+/// A buffered body shorter than `min_size` bytes is sent as is, without
+/// `Content-Encoding`. A streamed body is compressed whatever its size, which is
+/// not known up front.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionOptions {
+    pub algorithm: Encoding,
+    pub level: Option<u32>,
+    pub min_size: usize,
+}
+
+impl CompressionOptions {
+    pub const DEFAULT_MIN_SIZE: usize = 64;
+
+    /// `algorithm` at its default level, with the default `min_size`.
+    pub fn new(algorithm: Encoding) -> Self {
+        Self {
+            algorithm,
+            level: None,
+            min_size: Self::DEFAULT_MIN_SIZE,
+        }
+    }
+
+    /// The level to compress at, checked against the range of the algorithm.
+    pub(crate) fn get_level(&self) -> Result<u32, FlUrlError> {
+        let (default, range) = match self.algorithm {
+            Encoding::Gzip | Encoding::Deflate => (6, 0..=9),
+            Encoding::Brotli => (5, 0..=11),
+            // ruzstd has nothing but zstd's fastest level: a higher one would be
+            // silently ignored, so it is refused instead.
+            Encoding::Zstd => (1, 1..=1),
+        };
+
+        let level = self.level.unwrap_or(default);
+
+        if !range.contains(&level) {
+            return Err(FlUrlError::CompressionError(format!(
+                "{} compression level {} is out of range {}..={}",
+                self.algorithm,
+                level,
+                range.start(),
+                range.end()
+            )));
+        }
+
+        Ok(level)
+    }
+
+    /// Compresses a whole body.
+    pub(crate) fn compress(&self, data: &[u8]) -> Result<Vec<u8>, FlUrlError> {
+        use std::io::Write;
+
+        let level = self.get_level()?;
+
+        let result = match self.algorithm {
+            Encoding::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::new(level));
+                encoder.write_all(data).and_then(|_| encoder.finish())
+            }
+            Encoding::Deflate => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::new(level));
+                encoder.write_all(data).and_then(|_| encoder.finish())
+            }
+            Encoding::Brotli => {
+                let mut encoder =
+                    brotli::CompressorWriter::new(Vec::new(), 4096, level, BROTLI_WINDOW_BITS);
+                encoder.write_all(data).map(|_| encoder.into_inner())
+            }
+            Encoding::Zstd => Ok(ruzstd::encoding::compress_to_vec(
+                data,
+                ruzstd::encoding::CompressionLevel::Fastest,
+            )),
+        };
+
+        result.map_err(|err| compression_error(self.algorithm, err))
+    }
+}
+
+impl Default for CompressionOptions {
+    /// gzip at level 6, for bodies of 64 bytes and up — what `FlUrl::compress`
+    /// does.
+    fn default() -> Self {
+        Self::new(Encoding::Gzip)
+    }
+}
+
+impl From<Encoding> for CompressionOptions {
+    fn from(algorithm: Encoding) -> Self {
+        Self::new(algorithm)
+    }
+}
+
+/// The brotli window (`lgwin`) FlUrl encodes with: 4 MiB, the format's default.
+const BROTLI_WINDOW_BITS: u32 = 22;
+
+fn compression_error(encoding: Encoding, err: std::io::Error) -> FlUrlError {
+    FlUrlError::CompressionError(format!("Failed to compress {} body: {}", encoding, err))
+}
+
+/// The codings of a `Content-Encoding` value in the order they were applied,
+/// `identity` left out. `None` when one of them is not an [`Encoding`]: a body
+/// that can not be decoded all the way is better left as it came.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn parse_content_encoding(value: &str) -> Option<Vec<Encoding>> {
+    let mut result = Vec::new();
+
+    for token in value.split(',') {
+        let token = token.trim();
+
+        if token.is_empty() || token.eq_ignore_ascii_case("identity") {
+            continue;
+        }
+
+        result.push(Encoding::from_token(token)?);
+    }
+
+    Some(result)
+}
+
+/// Decodes a whole body that was encoded with `encoding`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn decode(encoding: Encoding, data: &[u8]) -> Result<Vec<u8>, FlUrlError> {
+    use std::io::Read;
+
+    let mut result = Vec::new();
+
+    let read_result = match encoding {
+        // MultiGzDecoder (not GzDecoder) so concatenated gzip members — a valid,
+        // spec-allowed encoding some servers emit — are all decoded, not just
+        // the first.
+        Encoding::Gzip => flate2::read::MultiGzDecoder::new(data).read_to_end(&mut result),
+        Encoding::Deflate => {
+            if has_zlib_header(data) {
+                flate2::read::ZlibDecoder::new(data).read_to_end(&mut result)
+            } else {
+                flate2::read::DeflateDecoder::new(data).read_to_end(&mut result)
+            }
+        }
+        Encoding::Brotli => brotli::Decompressor::new(data, 4096).read_to_end(&mut result),
+        Encoding::Zstd => decode_zstd(data, &mut result),
+    };
+
+    read_result.map_err(|err| {
+        FlUrlError::ReadingHyperBodyError(format!(
+            "Failed to decompress {} body: {}",
+            encoding, err
+        ))
+    })?;
+
+    Ok(result)
+}
+
+/// A zlib stream starts with a CMF/FLG pair: method 8 (DEFLATE) in the low
+/// nibble, and the two bytes as a big-endian number divisible by 31.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn has_zlib_header(data: &[u8]) -> bool {
+    match data {
+        [cmf, flg, ..] => cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0,
+        _ => false,
+    }
+}
+
+/// A zstd body may hold several frames back to back; they decode to the
+/// concatenation of their contents.
+#[cfg(not(target_arch = "wasm32"))]
+fn decode_zstd(data: &[u8], result: &mut Vec<u8>) -> std::io::Result<usize> {
+    use std::io::Read;
+
+    let mut input = data;
+    let len_before = result.len();
+
+    while !input.is_empty() {
+        let mut decoder =
+            ruzstd::decoding::StreamingDecoder::new(&mut input).map_err(std::io::Error::other)?;
+        decoder.read_to_end(result)?;
+    }
+
+    Ok(result.len() - len_before)
+}
+
+/// Decodes a body in the codings of its `Content-Encoding` as it comes off the
+/// wire, chunk by chunk: each chunk yields whatever part of the decoded body it
+/// completes, so a download of any size is decoded in the memory of a chunk
+/// plus the codecs' own windows. [`decode`] is the whole-body counterpart.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct StreamDecoder {
+    // The last coding applied comes first: that is the order a body is undone in.
+    stages: Vec<StreamDecoderStage>,
+    received_any: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StreamDecoder {
+    /// `encodings` in `Content-Encoding` order, as [`parse_content_encoding`]
+    /// returns them.
+    pub(crate) fn new(encodings: &[Encoding]) -> Self {
+        Self {
+            stages: encodings
+                .iter()
+                .rev()
+                .map(|encoding| StreamDecoderStage::new(*encoding))
+                .collect(),
+            received_any: false,
+        }
+    }
+
+    /// Feeds the next chunk of the encoded body. The result is empty when the
+    /// chunk did not complete anything that can be handed out yet.
+    pub(crate) fn decode_chunk(&mut self, chunk: &[u8]) -> Result<Vec<u8>, FlUrlError> {
+        if chunk.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.received_any = true;
+
+        let mut data = chunk.to_vec();
+
+        for stage in self.stages.iter_mut() {
+            data = stage.push(&data)?;
+        }
+
+        Ok(data)
+    }
+
+    /// Called once the encoded body has ended: returns what the codecs still
+    /// held back, or an error if the body stopped in the middle of a stream.
+    /// An empty body is not an error — like [`decode`], there is nothing to undo.
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>, FlUrlError> {
+        if !self.received_any {
+            return Ok(Vec::new());
+        }
+
+        let mut data = Vec::new();
+
+        for stage in self.stages.iter_mut() {
+            let mut result = stage.push(&data)?;
+            result.extend_from_slice(&stage.finish()?);
+            data = result;
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct StreamDecoderStage {
+    encoding: Encoding,
+    codec: StreamCodec,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum StreamCodec {
+    Gzip(flate2::write::MultiGzDecoder<Vec<u8>>),
+    // Zlib and raw DEFLATE both go by `deflate`: the first two bytes tell them
+    // apart, so they are held back until both are in.
+    Deflate(Vec<u8>),
+    Inflate(InflateStream),
+    Brotli(Box<brotli::DecompressorWriter<Vec<u8>>>),
+    Zstd(Box<ZstdStreamDecoder>),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StreamDecoderStage {
+    fn new(encoding: Encoding) -> Self {
+        let codec = match encoding {
+            Encoding::Gzip => StreamCodec::Gzip(flate2::write::MultiGzDecoder::new(Vec::new())),
+            Encoding::Deflate => StreamCodec::Deflate(Vec::new()),
+            Encoding::Brotli => {
+                StreamCodec::Brotli(Box::new(brotli::DecompressorWriter::new(Vec::new(), 4096)))
+            }
+            Encoding::Zstd => StreamCodec::Zstd(Box::new(ZstdStreamDecoder::new())),
+        };
+
+        Self { encoding, codec }
+    }
+
+    fn push(&mut self, data: &[u8]) -> Result<Vec<u8>, FlUrlError> {
+        let encoding = self.encoding;
+        self.push_to_codec(data)
+            .map_err(|err| stream_decode_error(encoding, err))
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>, FlUrlError> {
+        let encoding = self.encoding;
+        self.finish_codec()
+            .map_err(|err| stream_decode_error(encoding, err))
+    }
+
+    fn push_to_codec(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        use std::io::Write;
+
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        if let StreamCodec::Deflate(head) = &mut self.codec {
+            head.extend_from_slice(data);
+
+            if head.len() < 2 {
+                return Ok(Vec::new());
+            }
+
+            let head = std::mem::take(head);
+            self.codec = deflate_codec_for(&head);
+            return self.push_to_codec(&head);
+        }
+
+        // Write decoders keep part of their output buffered until the next
+        // write; flushing hands out everything this chunk decoded.
+        match &mut self.codec {
+            StreamCodec::Gzip(decoder) => {
+                decoder.write_all(data)?;
+                decoder.flush()?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            StreamCodec::Inflate(decoder) => decoder.push(data),
+            StreamCodec::Brotli(decoder) => {
+                decoder.write_all(data)?;
+                decoder.flush()?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            StreamCodec::Zstd(decoder) => decoder.push(data),
+            StreamCodec::Deflate(_) => unreachable!("resolved above"),
+        }
+    }
+
+    fn finish_codec(&mut self) -> std::io::Result<Vec<u8>> {
+        if let StreamCodec::Deflate(head) = &mut self.codec {
+            if head.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            // A one-byte body: too short for a zlib header, so raw DEFLATE.
+            let head = std::mem::take(head);
+            self.codec = deflate_codec_for(&head);
+            let mut result = self.push_to_codec(&head)?;
+            result.extend_from_slice(&self.finish_codec()?);
+            return Ok(result);
+        }
+
+        match &mut self.codec {
+            StreamCodec::Gzip(decoder) => {
+                decoder.try_finish()?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            StreamCodec::Inflate(decoder) => decoder.finish(),
+            StreamCodec::Brotli(decoder) => {
+                decoder.close()?;
+                Ok(std::mem::take(decoder.get_mut()))
+            }
+            StreamCodec::Zstd(decoder) => decoder.finish(),
+            StreamCodec::Deflate(_) => unreachable!("resolved above"),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn deflate_codec_for(head: &[u8]) -> StreamCodec {
+    StreamCodec::Inflate(InflateStream {
+        inflate: flate2::Decompress::new(has_zlib_header(head)),
+        ended: false,
+    })
+}
+
+/// Zlib or raw DEFLATE, driven through `flate2::Decompress` directly: unlike
+/// flate2's write decoders it tells when the stream has ended, so a body cut
+/// short is an error here as it is for [`decode`].
+#[cfg(not(target_arch = "wasm32"))]
+struct InflateStream {
+    inflate: flate2::Decompress,
+    ended: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InflateStream {
+    fn push(&mut self, mut data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut result = Vec::new();
+
+        // Anything after the end of the stream is ignored, as the read decoders do.
+        while !self.ended {
+            result.reserve(data.len().max(4096) * 2);
+
+            let total_in = self.inflate.total_in();
+            let total_out = self.inflate.total_out();
+
+            let status = self
+                .inflate
+                .decompress_vec(data, &mut result, flate2::FlushDecompress::None)
+                .map_err(std::io::Error::other)?;
+
+            data = &data[(self.inflate.total_in() - total_in) as usize..];
+
+            if status == flate2::Status::StreamEnd {
+                self.ended = true;
+            }
+
+            // Done with this chunk once it is consumed and the output had room
+            // to spare — i.e. nothing is left inside the inflater either.
+            let made_progress =
+                self.inflate.total_in() != total_in || self.inflate.total_out() != total_out;
+
+            if !made_progress || (data.is_empty() && result.len() < result.capacity()) {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+
+    fn finish(&mut self) -> std::io::Result<Vec<u8>> {
+        if !self.ended {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "deflate stream ended before its final block",
+            ));
+        }
+
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn stream_decode_error(encoding: Encoding, err: std::io::Error) -> FlUrlError {
+    FlUrlError::ReadingHyperBodyError(format!("Failed to decompress {} body: {}", encoding, err))
+}
+
+/// ruzstd decodes from a reader that has the whole frame, so the stream is cut
+/// into what it can take in one go — a frame header, or a block (with the
+/// frame checksum after the last one) — and a part is only handed over once all
+/// of its bytes are in. Blocks are at most 128 KiB, which bounds what is held
+/// back besides the decoding window.
+#[cfg(not(target_arch = "wasm32"))]
+struct ZstdStreamDecoder {
+    decoder: ruzstd::decoding::FrameDecoder,
+    pending: Vec<u8>,
+    in_frame: bool,
+    has_checksum: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ZstdStreamDecoder {
+    fn new() -> Self {
+        Self {
+            decoder: ruzstd::decoding::FrameDecoder::new(),
+            pending: Vec::new(),
+            in_frame: false,
+            has_checksum: false,
+        }
+    }
+
+    fn push(&mut self, data: &[u8]) -> std::io::Result<Vec<u8>> {
+        self.pending.extend_from_slice(data);
+
+        let mut result = Vec::new();
+        let mut consumed = 0;
+
+        loop {
+            let input = &self.pending[consumed..];
+
+            if !self.in_frame {
+                let Some(header_len) = zstd_frame_header_len(input) else {
+                    break;
+                };
+
+                if input.len() < header_len {
+                    break;
+                }
+
+                let mut header = &input[..header_len];
+                self.decoder
+                    .reset(&mut header)
+                    .map_err(std::io::Error::other)?;
+
+                self.has_checksum = input[4] & 0b100 != 0;
+                self.in_frame = true;
+                consumed += header_len;
+                continue;
+            }
+
+            let Some((block_len, last_block)) = zstd_block_len(input, self.has_checksum) else {
+                break;
+            };
+
+            if input.len() < block_len {
+                break;
+            }
+
+            self.decoder
+                .decode_blocks(
+                    &input[..block_len],
+                    ruzstd::decoding::BlockDecodingStrategy::UptoBlocks(1),
+                )
+                .map_err(std::io::Error::other)?;
+
+            if let Some(decoded) = self.decoder.collect() {
+                result.extend_from_slice(&decoded);
+            }
+
+            if last_block {
+                self.in_frame = false;
+            }
+            consumed += block_len;
+        }
+
+        self.pending.drain(..consumed);
+
+        Ok(result)
+    }
+
+    fn finish(&mut self) -> std::io::Result<Vec<u8>> {
+        if self.in_frame || !self.pending.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "zstd stream ended in the middle of a frame",
+            ));
+        }
+
+        Ok(Vec::new())
+    }
+}
+
+/// Magic number, descriptor, then the optional window descriptor, dictionary id
+/// and content size fields the descriptor announces. `None` until the descriptor
+/// is in.
+#[cfg(not(target_arch = "wasm32"))]
+fn zstd_frame_header_len(input: &[u8]) -> Option<usize> {
+    let descriptor = *input.get(4)?;
+    let single_segment = descriptor & 0b10_0000 != 0;
+
+    let window_descriptor_len = if single_segment { 0 } else { 1 };
+    let dictionary_id_len = [0, 1, 2, 4][usize::from(descriptor & 0b11)];
+    let content_size_len = match descriptor >> 6 {
+        0 => usize::from(single_segment),
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+
+    Some(5 + window_descriptor_len + dictionary_id_len + content_size_len)
+}
+
+/// The wire length of the block starting `input` and whether it is the last of
+/// its frame. A 3-byte header: last-block bit, 2-bit type, 21-bit size, where an
+/// RLE block's size is the run length and its content a single byte.
+#[cfg(not(target_arch = "wasm32"))]
+fn zstd_block_len(input: &[u8], has_checksum: bool) -> Option<(usize, bool)> {
+    let [b0, b1, b2, ..] = *input else {
+        return None;
+    };
+
+    let header = u32::from_le_bytes([b0, b1, b2, 0]);
+    let last_block = header & 1 != 0;
+    let block_type = (header >> 1) & 0b11;
+    let size = (header >> 3) as usize;
+
+    let content_len = if block_type == 1 { 1 } else { size };
+    let checksum_len = if last_block && has_checksum { 4 } else { 0 };
+
+    Some((3 + content_len + checksum_len, last_block))
+}
+
+/// How much input a zstd frame of a streamed body covers — one block's worth,
+/// ruzstd's largest.
+#[cfg(not(target_arch = "wasm32"))]
+const ZSTD_STREAM_FRAME_INPUT: usize = 128 * 1024;
+
+/// Encodes a request body as it is produced, chunk by chunk — the counterpart of
+/// [`StreamDecoder`]. Each chunk yields the part of the encoded body the codec
+/// let go of; the total length is only known once [`Self::finish`] has run.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) struct StreamEncoder {
+    encoding: Encoding,
+    codec: StreamEncoderCodec,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+enum StreamEncoderCodec {
+    Gzip(flate2::write::GzEncoder<Vec<u8>>),
+    Deflate(flate2::write::ZlibEncoder<Vec<u8>>),
+    // `into_inner` is what ends a brotli stream, hence the Option.
+    Brotli(Option<Box<brotli::CompressorWriter<Vec<u8>>>>),
+    // ruzstd only compresses whole inputs, so the body goes out as a sequence of
+    // frames, each over up to `ZSTD_STREAM_FRAME_INPUT` bytes of it. A zstd
+    // body may hold any number of frames (RFC 8878, 3).
+    Zstd {
+        pending: Vec<u8>,
+        frame_written: bool,
+    },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StreamEncoder {
+    /// `options.min_size` does not apply: a stream is compressed whatever its size.
+    pub(crate) fn new(options: &CompressionOptions) -> Result<Self, FlUrlError> {
+        let level = options.get_level()?;
+
+        let codec = match options.algorithm {
+            Encoding::Gzip => StreamEncoderCodec::Gzip(flate2::write::GzEncoder::new(
+                Vec::new(),
+                flate2::Compression::new(level),
+            )),
+            Encoding::Deflate => StreamEncoderCodec::Deflate(flate2::write::ZlibEncoder::new(
+                Vec::new(),
+                flate2::Compression::new(level),
+            )),
+            Encoding::Brotli => StreamEncoderCodec::Brotli(Some(Box::new(
+                brotli::CompressorWriter::new(Vec::new(), 4096, level, BROTLI_WINDOW_BITS),
+            ))),
+            Encoding::Zstd => StreamEncoderCodec::Zstd {
+                pending: Vec::new(),
+                frame_written: false,
+            },
+        };
+
+        Ok(Self {
+            encoding: options.algorithm,
+            codec,
+        })
+    }
+
+    pub(crate) fn get_encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Feeds the next chunk of the body. The result is empty when the codec is
+    /// still holding on to everything it was given.
+    pub(crate) fn encode_chunk(&mut self, chunk: &[u8]) -> Result<Vec<u8>, FlUrlError> {
+        use std::io::Write;
+
+        let encoding = self.encoding;
+
+        let result = match &mut self.codec {
+            StreamEncoderCodec::Gzip(encoder) => encoder
+                .write_all(chunk)
+                .map(|_| std::mem::take(encoder.get_mut())),
+            StreamEncoderCodec::Deflate(encoder) => encoder
+                .write_all(chunk)
+                .map(|_| std::mem::take(encoder.get_mut())),
+            StreamEncoderCodec::Brotli(encoder) => match encoder.as_mut() {
+                Some(encoder) => encoder
+                    .write_all(chunk)
+                    .map(|_| std::mem::take(encoder.get_mut())),
+                None => Ok(Vec::new()),
+            },
+            StreamEncoderCodec::Zstd {
+                pending,
+                frame_written,
+            } => {
+                pending.extend_from_slice(chunk);
+
+                let mut result = Vec::new();
+                let mut consumed = 0;
+
+                while pending.len() - consumed >= ZSTD_STREAM_FRAME_INPUT {
+                    let frame_input = &pending[consumed..consumed + ZSTD_STREAM_FRAME_INPUT];
+                    result.extend_from_slice(&ruzstd::encoding::compress_to_vec(
+                        frame_input,
+                        ruzstd::encoding::CompressionLevel::Fastest,
+                    ));
+                    consumed += ZSTD_STREAM_FRAME_INPUT;
+                    *frame_written = true;
+                }
+
+                pending.drain(..consumed);
+                Ok(result)
+            }
+        };
+
+        result.map_err(|err| compression_error(encoding, err))
+    }
+
+    /// Called once the body has ended: the rest of the encoded body, trailer
+    /// included.
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>, FlUrlError> {
+        let encoding = self.encoding;
+
+        let result = match &mut self.codec {
+            StreamEncoderCodec::Gzip(encoder) => encoder
+                .try_finish()
+                .map(|_| std::mem::take(encoder.get_mut())),
+            StreamEncoderCodec::Deflate(encoder) => encoder
+                .try_finish()
+                .map(|_| std::mem::take(encoder.get_mut())),
+            StreamEncoderCodec::Brotli(encoder) => match encoder.take() {
+                Some(encoder) => Ok(encoder.into_inner()),
+                None => Ok(Vec::new()),
+            },
+            StreamEncoderCodec::Zstd {
+                pending,
+                frame_written,
+            } => {
+                // An empty body still has to be a valid zstd stream: one frame.
+                if pending.is_empty() && *frame_written {
+                    Ok(Vec::new())
+                } else {
+                    let result = ruzstd::encoding::compress_to_vec(
+                        pending.as_slice(),
+                        ruzstd::encoding::CompressionLevel::Fastest,
+                    );
+                    pending.clear();
+                    *frame_written = true;
+                    Ok(result)
+                }
+            }
+        };
+
+        result.map_err(|err| compression_error(encoding, err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    const TEXT: &[u8] = b"The quick brown fox jumps over the lazy dog. The quick brown fox jumps over the lazy dog.";
+
+    #[test]
+    fn tokens_round_trip() {
+        for encoding in [
+            Encoding::Gzip,
+            Encoding::Deflate,
+            Encoding::Brotli,
+            Encoding::Zstd,
+        ] {
+            assert_eq!(Encoding::from_token(encoding.as_str()), Some(encoding));
+        }
+
+        assert_eq!(Encoding::from_token(" X-GZIP "), Some(Encoding::Gzip));
+        assert_eq!(Encoding::from_token("compress"), None);
+
+        assert_eq!(
+            Encoding::to_accept_encoding(&[Encoding::Brotli, Encoding::Gzip, Encoding::Brotli]),
+            "br, gzip"
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn content_encoding_lists_are_parsed_in_order() {
+        assert_eq!(
+            parse_content_encoding("gzip, identity, br"),
+            Some(vec![Encoding::Gzip, Encoding::Brotli])
+        );
+        assert_eq!(parse_content_encoding("identity"), Some(vec![]));
+        assert_eq!(parse_content_encoding("gzip, compress"), None);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn every_encoding_is_decoded() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(TEXT).unwrap();
+        let mut gzip = gzip.finish().unwrap();
+        // A second member must be decoded too.
+        let second_member = gzip.clone();
+        gzip.extend_from_slice(&second_member);
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(TEXT).unwrap();
+        let zlib = zlib.finish().unwrap();
+
+        let mut raw_deflate =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        raw_deflate.write_all(TEXT).unwrap();
+        let raw_deflate = raw_deflate.finish().unwrap();
+
+        let mut brotli = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut brotli, 4096, 5, 22);
+            writer.write_all(TEXT).unwrap();
+        }
+
+        let mut zstd =
+            ruzstd::encoding::compress_to_vec(TEXT, ruzstd::encoding::CompressionLevel::Fastest);
+        let second_frame = zstd.clone();
+        zstd.extend_from_slice(&second_frame);
+
+        let twice = [TEXT, TEXT].concat();
+
+        assert_eq!(decode(Encoding::Gzip, &gzip).unwrap(), twice);
+        assert_eq!(decode(Encoding::Deflate, &zlib).unwrap(), TEXT);
+        assert_eq!(decode(Encoding::Deflate, &raw_deflate).unwrap(), TEXT);
+        assert_eq!(decode(Encoding::Brotli, &brotli).unwrap(), TEXT);
+        assert_eq!(decode(Encoding::Zstd, &zstd).unwrap(), twice);
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn decode_in_chunks(encodings: &[Encoding], data: &[u8], chunk_size: usize) -> Vec<u8> {
+        let mut decoder = StreamDecoder::new(encodings);
+        let mut result = Vec::new();
+
+        for chunk in data.chunks(chunk_size) {
+            result.extend_from_slice(&decoder.decode_chunk(chunk).unwrap());
+        }
+        result.extend_from_slice(&decoder.finish().unwrap());
+
+        result
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn every_encoding_is_decoded_chunk_by_chunk() {
+        // Past one zstd block (128 KiB), and not too repetitive.
+        let text: Vec<u8> = (0..40_000u32)
+            .flat_map(|i| format!("{} {} ", i, i * 7919 % 1000).into_bytes())
+            .collect();
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&text).unwrap();
+        let mut gzip = gzip.finish().unwrap();
+        let second_member = gzip.clone();
+        gzip.extend_from_slice(&second_member);
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(&text).unwrap();
+        let zlib = zlib.finish().unwrap();
+
+        let mut raw_deflate =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        raw_deflate.write_all(&text).unwrap();
+        let raw_deflate = raw_deflate.finish().unwrap();
+
+        let mut brotli = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut brotli, 4096, 5, 22);
+            writer.write_all(&text).unwrap();
+        }
+
+        let mut zstd = ruzstd::encoding::compress_to_vec(
+            text.as_slice(),
+            ruzstd::encoding::CompressionLevel::Fastest,
+        );
+        let second_frame = zstd.clone();
+        zstd.extend_from_slice(&second_frame);
+
+        let twice = [text.as_slice(), text.as_slice()].concat();
+
+        for chunk_size in [1, 7, 4096] {
+            assert_eq!(
+                decode_in_chunks(&[Encoding::Gzip], &gzip, chunk_size),
+                twice
+            );
+            assert_eq!(
+                decode_in_chunks(&[Encoding::Deflate], &zlib, chunk_size),
+                text
+            );
+            assert_eq!(
+                decode_in_chunks(&[Encoding::Deflate], &raw_deflate, chunk_size),
+                text
+            );
+            assert_eq!(
+                decode_in_chunks(&[Encoding::Brotli], &brotli, chunk_size),
+                text
+            );
+            assert_eq!(
+                decode_in_chunks(&[Encoding::Zstd], &zstd, chunk_size),
+                twice
+            );
+        }
+
+        // `Content-Encoding: gzip, br`: brotli over gzip.
+        let mut gzip_then_brotli = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut gzip_then_brotli, 4096, 5, 22);
+            writer.write_all(&gzip).unwrap();
+        }
+        assert_eq!(
+            decode_in_chunks(&[Encoding::Gzip, Encoding::Brotli], &gzip_then_brotli, 100),
+            twice
+        );
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn a_stream_cut_short_is_an_error() {
+        let mut brotli = Vec::new();
+        {
+            let mut writer = brotli::CompressorWriter::new(&mut brotli, 4096, 5, 22);
+            writer.write_all(TEXT).unwrap();
+        }
+
+        let zstd =
+            ruzstd::encoding::compress_to_vec(TEXT, ruzstd::encoding::CompressionLevel::Fastest);
+
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        zlib.write_all(TEXT).unwrap();
+        let zlib = zlib.finish().unwrap();
+
+        for (encoding, data) in [
+            (Encoding::Deflate, zlib),
+            (Encoding::Brotli, brotli),
+            (Encoding::Zstd, zstd),
+        ] {
+            let mut decoder = StreamDecoder::new(&[encoding]);
+            decoder.decode_chunk(&data[..data.len() - 2]).unwrap();
+            assert!(decoder.finish().is_err(), "{}", encoding);
+        }
+
+        // Nothing at all is just an empty body.
+        assert!(StreamDecoder::new(&[Encoding::Gzip])
+            .finish()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn streamed_bodies_are_encoded_chunk_by_chunk() {
+        let text: Vec<u8> = (0..40_000u32)
+            .flat_map(|i| format!("{} {} ", i, i * 7919 % 1000).into_bytes())
+            .collect();
+
+        for encoding in [
+            Encoding::Gzip,
+            Encoding::Deflate,
+            Encoding::Brotli,
+            Encoding::Zstd,
+        ] {
+            for body in [text.as_slice(), b"".as_slice()] {
+                let mut encoder = StreamEncoder::new(&encoding.into()).unwrap();
+                let mut encoded = Vec::new();
+
+                for chunk in body.chunks(1000) {
+                    encoded.extend_from_slice(&encoder.encode_chunk(chunk).unwrap());
+                }
+                encoded.extend_from_slice(&encoder.finish().unwrap());
+
+                assert!(!encoded.is_empty(), "{}", encoding);
+                assert_eq!(decode(encoding, &encoded).unwrap(), body, "{}", encoding);
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn whole_bodies_are_compressed_at_the_level_asked_for() {
+        let text = TEXT.repeat(20);
+
+        for algorithm in [
+            Encoding::Gzip,
+            Encoding::Deflate,
+            Encoding::Brotli,
+            Encoding::Zstd,
+        ] {
+            let compressed = CompressionOptions::new(algorithm).compress(&text).unwrap();
+            assert!(compressed.len() < text.len(), "{}", algorithm);
+            assert_eq!(
+                decode(algorithm, &compressed).unwrap(),
+                text,
+                "{}",
+                algorithm
+            );
+        }
+
+        let stored = CompressionOptions {
+            level: Some(0),
+            ..CompressionOptions::new(Encoding::Gzip)
+        }
+        .compress(&text)
+        .unwrap();
+        assert!(stored.len() > text.len());
+        assert_eq!(decode(Encoding::Gzip, &stored).unwrap(), text);
+    }
+
+    #[test]
+    fn a_level_out_of_range_is_an_error() {
+        for (algorithm, level) in [
+            (Encoding::Gzip, 10),
+            (Encoding::Brotli, 12),
+            (Encoding::Zstd, 0),
+            (Encoding::Zstd, 19),
+        ] {
+            let options = CompressionOptions {
+                level: Some(level),
+                ..CompressionOptions::new(algorithm)
+            };
+            assert!(matches!(
+                options.compress(TEXT),
+                Err(FlUrlError::CompressionError(_))
+            ));
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    fn corrupted_body_is_an_error() {
+        let err = decode(Encoding::Brotli, b"definitely not brotli").unwrap_err();
+        assert!(matches!(err, FlUrlError::ReadingHyperBodyError(_)));
+    }
+}
@@ -20,19 +20,56 @@ pub enum FlUrlError {
     /// A `my_http_utils` request model failed to build (e.g. a field validator
     /// rejected its value) inside `FlUrl::execute_request`.
     RequestBuild(String),
+    /// The request body could not be compressed as `compress_with` asked — a
+    /// level outside the algorithm's range, or the encoder failing.
+    CompressionError(String),
 
-    /// `.compress()` was combined with a streamed request body. Gzipping needs the
-    /// whole body in one buffer, which is exactly what streaming exists to avoid, so
-    /// the request is refused instead of quietly materializing the payload.
+    /// No longer returned: `.compress()` on a streamed request body now compresses
+    /// it chunk by chunk as it is sent. Kept so that matches naming it still
+    /// compile.
     #[cfg(not(target_arch = "wasm32"))]
     StreamedBodyCanNotBeCompressed,
 
+    /// `download_resumable` / `download_parallel` got a status it can not write to
+    /// the file — anything but `200`, or `206` for the range it asked for — or an
+    /// event stream got one it does not reconnect on. Carries the status code.
+    UnexpectedStatusCode(u16),
+
+    /// An event stream was answered with a body that is not `text/event-stream`,
+    /// or sent a line too long to be one.
+    InvalidEventStream(String),
+
+    /// `download_parallel` got other bytes than it asked for: a range that starts
+    /// elsewhere or comes up short, a file that changed on the server mid-way, a
+    /// total that differs from the length announced up front.
+    #[cfg(not(target_arch = "wasm32"))]
+    DownloadMismatch(String),
+
+    /// `into_multipart` was called on a response that is not `multipart/*` with
+    /// a boundary, or the body breaks the multipart format: a delimiter line
+    /// with other text on it, a header block that does not end, a body that ends
+    /// before the closing delimiter.
+    #[cfg(not(target_arch = "wasm32"))]
+    InvalidMultipart(String),
+
     #[cfg(not(target_arch = "wasm32"))]
     HyperError(hyper::Error),
     #[cfg(not(target_arch = "wasm32"))]
     HttpError(hyper::http::Error),
     #[cfg(all(not(target_arch = "wasm32"), feature = "_tls"))]
     RustTlsError(my_tls::tokio_rustls::rustls::Error),
+    /// TLS material handed to FlUrl could not be used — an unreadable PEM
+    /// bundle, a certificate rustls refuses as a trust anchor, … Reported when
+    /// the value is built where possible; what can only be checked against the
+    /// TLS provider — a `TlsPolicy` naming an unknown cipher suite — is reported
+    /// by the request that tries to connect, and never retried.
+    #[cfg(all(not(target_arch = "wasm32"), feature = "_tls"))]
+    InvalidTlsConfiguration(String),
+    /// The server certificate chain verified, but none of its certificates
+    /// matches the pins set with `with_certificate_pins` (or the cache's pins for
+    /// the host). Never retried: a mismatch will not go away on the next attempt.
+    #[cfg(all(not(target_arch = "wasm32"), feature = "_tls"))]
+    TlsCertificatePinMismatch(String),
     #[cfg(not(target_arch = "wasm32"))]
     MyHttpClientError(my_http_client::MyHttpClientError),
     #[cfg(all(unix, feature = "with-ssh", not(target_arch = "wasm32")))]
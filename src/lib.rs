@@ -19,19 +19,24 @@
 //! call sites need no `cfg` of their own.
 //!
 //! The shared, transport-agnostic pieces — [`enum@FlUrlError`], the request
-//! [`body`] types and the drop-connection scenario — live at the crate root and
-//! are used by both backends.
+//! [`body`] types, the content codings, the event-stream parser and the
+//! drop-connection scenario — live at the crate root and are used by both
+//! backends.
 
 // ---- Shared, target-agnostic modules ---------------------------------------
 
 pub mod body;
+mod compression;
 mod empty_request_model;
 mod errors;
 mod fl_drop_connection_scenario;
+mod sse;
 
+pub use compression::*;
 pub use empty_request_model::*;
 pub use errors::*;
 pub use fl_drop_connection_scenario::*;
+pub use sse::{EventStreamOptions, SseEvent};
 
 pub extern crate my_http_utils;
 
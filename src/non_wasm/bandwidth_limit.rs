@@ -0,0 +1,231 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+use tokio::time::{Instant, Sleep};
+
+/// A bytes-per-second limit, as set with `FlUrl::set_bandwidth_limit` or
+/// `FlUrlHttpConnectionsCache::set_bandwidth_limit`: one token bucket for what
+/// is sent, one for what is received. Every transfer holding the same
+/// `BandwidthLimit` shares its budget — the requests of a cache, the ranges of
+/// a `download_parallel`.
+pub(crate) struct BandwidthLimit {
+    upload: TokenBucket,
+    download: TokenBucket,
+}
+
+impl BandwidthLimit {
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            upload: TokenBucket::new(bytes_per_second),
+            download: TokenBucket::new(bytes_per_second),
+        }
+    }
+}
+
+/// A token bucket that lets a transfer run ahead of its budget by one chunk,
+/// then makes it wait out the debt: the bytes are accounted for once they have
+/// passed, and the wait comes before the next read.
+struct TokenBucket {
+    bytes_per_second: f64,
+    state: parking_lot::Mutex<TokenBucketState>,
+}
+
+struct TokenBucketState {
+    // Negative while the transfers sharing the bucket are in debt.
+    available: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(bytes_per_second: u64) -> Self {
+        let bytes_per_second = bytes_per_second.max(1) as f64;
+
+        Self {
+            bytes_per_second,
+            // A full bucket: one second's worth of bytes may go out at once.
+            state: parking_lot::Mutex::new(TokenBucketState {
+                available: bytes_per_second,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes `len` bytes out of the bucket and returns how long the transfer has
+    /// to wait before it reads on.
+    fn take(&self, len: usize) -> Duration {
+        let mut state = self.state.lock();
+
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.refilled_at).as_secs_f64();
+        state.refilled_at = now;
+
+        state.available =
+            (state.available + elapsed * self.bytes_per_second).min(self.bytes_per_second);
+        state.available -= len as f64;
+
+        if state.available >= 0.0 {
+            return Duration::ZERO;
+        }
+
+        Duration::from_secs_f64(-state.available / self.bytes_per_second)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum Direction {
+    Upload,
+    Download,
+}
+
+/// One transfer's use of a [`BandwidthLimit`]: the bytes that passed are taken
+/// out of the bucket of its direction, and the wait that buys is served before
+/// the next chunk is read.
+pub(crate) struct Throttle {
+    limit: Arc<BandwidthLimit>,
+    direction: Direction,
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl Throttle {
+    pub fn new(limit: Arc<BandwidthLimit>, direction: Direction) -> Self {
+        Self {
+            limit,
+            direction,
+            delay: None,
+        }
+    }
+
+    /// `Ready` once the bytes let through so far have been paid for.
+    pub fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let Some(delay) = self.delay.as_mut() else {
+            return Poll::Ready(());
+        };
+
+        match delay.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.delay = None;
+                Poll::Ready(())
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    pub fn consume(&mut self, len: usize) {
+        let bucket = match self.direction {
+            Direction::Upload => &self.limit.upload,
+            Direction::Download => &self.limit.download,
+        };
+
+        let wait = bucket.take(len);
+
+        if !wait.is_zero() {
+            self.delay = Some(Box::pin(tokio::time::sleep(wait)));
+        }
+    }
+}
+
+/// A body held to a [`Throttle`]: the streamed request body on its way to the
+/// socket, the response body on its way into memory. The frames pass through
+/// unchanged — only later.
+pub(crate) struct ThrottledBody<TBody> {
+    inner: Pin<Box<TBody>>,
+    throttle: Option<Throttle>,
+}
+
+impl<TBody> ThrottledBody<TBody> {
+    /// With no `throttle` the body passes through untouched.
+    pub fn new(inner: TBody, throttle: Option<Throttle>) -> Self {
+        Self {
+            inner: Box::pin(inner),
+            throttle,
+        }
+    }
+}
+
+impl<TBody> Body for ThrottledBody<TBody>
+where
+    TBody: Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = TBody::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        if let Some(throttle) = this.throttle.as_mut() {
+            if throttle.poll_ready(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        let frame = match this.inner.as_mut().poll_frame(cx) {
+            Poll::Ready(frame) => frame,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if let (Some(throttle), Some(Ok(frame))) = (this.throttle.as_mut(), frame.as_ref()) {
+            if let Some(data) = frame.data_ref() {
+                throttle.consume(data.len());
+            }
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::non_wasm::test_utils::stream_of;
+
+    #[tokio::test]
+    async fn a_body_over_budget_is_held_to_the_limit() {
+        use http_body_util::{BodyExt, StreamBody};
+
+        // 100 KB/s, and 150 KB to send: the first 100 KB go out on the full
+        // bucket, the other 50 KB take half a second.
+        let limit = Arc::new(BandwidthLimit::new(100_000));
+
+        let frames: Vec<Result<Frame<Bytes>, String>> = (0..15)
+            .map(|_| Ok(Frame::data(Bytes::from(vec![b'x'; 10_000]))))
+            .collect();
+
+        let body = ThrottledBody::new(
+            StreamBody::new(stream_of(frames)),
+            Some(Throttle::new(limit, Direction::Upload)),
+        );
+
+        let started = Instant::now();
+        let collected = body.collect().await.unwrap().to_bytes();
+        let elapsed = started.elapsed();
+
+        assert_eq!(collected.len(), 150_000);
+        assert!(elapsed >= Duration::from_millis(400), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn upload_and_download_draw_on_buckets_of_their_own() {
+        let limit = BandwidthLimit::new(1000);
+
+        assert_eq!(limit.upload.take(1000), Duration::ZERO);
+        assert_eq!(limit.download.take(1000), Duration::ZERO);
+        assert!(limit.upload.take(500) > Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,105 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame};
+
+use crate::compression::StreamDecoder;
+
+/// Decodes the body of a response handed out with `into_hyper_response` as it is
+/// polled, for `accept_compression`. Sits on top of the `EscapedBodyGuard`, so
+/// the connection is still settled by the raw body reaching its end.
+pub(crate) struct DecodingBody {
+    inner: http_body_util::combinators::BoxBody<Bytes, String>,
+    // None once the inner body has ended and the decoder was finished.
+    decoder: Option<StreamDecoder>,
+    // HTTP/2 trailers seen while the decoder still had data to hand out.
+    trailers: Option<Frame<Bytes>>,
+}
+
+impl DecodingBody {
+    pub fn new(
+        inner: http_body_util::combinators::BoxBody<Bytes, String>,
+        decoder: StreamDecoder,
+    ) -> Self {
+        Self {
+            inner,
+            decoder: Some(decoder),
+            trailers: None,
+        }
+    }
+
+    fn finish_decoder(&mut self) -> Result<Vec<u8>, String> {
+        match self.decoder.take() {
+            Some(mut decoder) => decoder.finish().map_err(|err| format!("{:?}", err)),
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Body for DecodingBody {
+    type Data = Bytes;
+    type Error = String;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        loop {
+            let Some(decoder) = this.decoder.as_mut() else {
+                return Poll::Ready(this.trailers.take().map(Ok));
+            };
+
+            let frame = match Pin::new(&mut this.inner).poll_frame(cx) {
+                Poll::Ready(frame) => frame,
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let mut inner_ended = false;
+
+            let mut decoded = match frame {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => match decoder.decode_chunk(&data) {
+                        Ok(decoded) => decoded,
+                        Err(err) => {
+                            this.decoder = None;
+                            return Poll::Ready(Some(Err(format!("{:?}", err))));
+                        }
+                    },
+                    Err(trailers) => {
+                        this.trailers = Some(trailers);
+                        Vec::new()
+                    }
+                },
+                Some(Err(err)) => {
+                    this.decoder = None;
+                    return Poll::Ready(Some(Err(err)));
+                }
+                None => {
+                    inner_ended = true;
+                    Vec::new()
+                }
+            };
+
+            // Finish as soon as the raw body is done, so `is_end_stream` turns
+            // true together with the last data frame — a consumer that stops
+            // there then drops a fully drained body.
+            if inner_ended || this.trailers.is_some() || this.inner.is_end_stream() {
+                match this.finish_decoder() {
+                    Ok(rest) => decoded.extend_from_slice(&rest),
+                    Err(err) => return Poll::Ready(Some(Err(err))),
+                }
+            }
+
+            if !decoded.is_empty() {
+                return Poll::Ready(Some(Ok(Frame::data(Bytes::from(decoded)))));
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.decoder.is_none() && self.trailers.is_none()
+    }
+}
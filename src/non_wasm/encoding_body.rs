@@ -0,0 +1,97 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame};
+
+use crate::compression::StreamEncoder;
+
+/// Compresses a streamed request body as hyper polls it, for `compress()` on
+/// `execute_streamed`. The encoded length is not known until the end, so the
+/// size hint stays open and the request goes out chunked.
+pub(crate) struct EncodingBody<TBody> {
+    inner: Pin<Box<TBody>>,
+    // None once the inner body has ended and the encoder was finished.
+    encoder: Option<StreamEncoder>,
+    // Trailers of the inner body, sent after the last encoded bytes.
+    trailers: Option<Frame<Bytes>>,
+}
+
+impl<TBody> EncodingBody<TBody> {
+    pub fn new(inner: TBody, encoder: StreamEncoder) -> Self {
+        Self {
+            inner: Box::pin(inner),
+            encoder: Some(encoder),
+            trailers: None,
+        }
+    }
+}
+
+impl<TBody> Body for EncodingBody<TBody>
+where
+    TBody: Body<Data = Bytes>,
+    TBody::Error: std::fmt::Display,
+{
+    type Data = Bytes;
+    type Error = String;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        loop {
+            let Some(encoder) = this.encoder.as_mut() else {
+                return Poll::Ready(this.trailers.take().map(Ok));
+            };
+
+            let frame = match this.inner.as_mut().poll_frame(cx) {
+                Poll::Ready(frame) => frame,
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let mut body_ended = true;
+
+            let encoded = match frame {
+                Some(Ok(frame)) => match frame.into_data() {
+                    Ok(data) => {
+                        body_ended = false;
+                        encoder.encode_chunk(&data)
+                    }
+                    Err(trailers) => {
+                        this.trailers = Some(trailers);
+                        encoder.finish()
+                    }
+                },
+                Some(Err(err)) => {
+                    // The producer failed: the request must fail with it rather
+                    // than go out as a well-formed, truncated compressed body.
+                    this.encoder = None;
+                    return Poll::Ready(Some(Err(err.to_string())));
+                }
+                None => encoder.finish(),
+            };
+
+            if body_ended {
+                this.encoder = None;
+            }
+
+            let encoded = match encoded {
+                Ok(encoded) => encoded,
+                Err(err) => {
+                    this.encoder = None;
+                    return Poll::Ready(Some(Err(format!("{:?}", err))));
+                }
+            };
+
+            if !encoded.is_empty() {
+                return Poll::Ready(Some(Ok(Frame::data(Bytes::from(encoded)))));
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.encoder.is_none() && self.trailers.is_none()
+    }
+}
@@ -0,0 +1,211 @@
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use hyper::body::{Body, Frame, SizeHint};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::FlUrlError;
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A file on disk as a streamed request body, for `put_file` / `post_file`. The
+/// length is taken from the metadata of the open file once, and the body sends
+/// exactly that many bytes: a file that grows while it is uploaded is cut at
+/// the length, one that shrinks fails the request rather than leaving the
+/// `Content-Length` short.
+pub(crate) struct FileBody {
+    file: tokio::fs::File,
+    remaining: u64,
+    buffer: BytesMut,
+}
+
+impl FileBody {
+    pub async fn open(path: &Path) -> Result<Self, FlUrlError> {
+        let file = tokio::fs::File::open(path).await?;
+        let metadata = file.metadata().await?;
+
+        if !metadata.is_file() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is not a file", path.display()),
+            )
+            .into());
+        }
+
+        Ok(Self {
+            file,
+            remaining: metadata.len(),
+            buffer: BytesMut::new(),
+        })
+    }
+
+    pub fn get_len(&self) -> u64 {
+        self.remaining
+    }
+}
+
+impl Body for FileBody {
+    type Data = Bytes;
+    type Error = String;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        if this.remaining == 0 {
+            return Poll::Ready(None);
+        }
+
+        let chunk = match poll_read_chunk(
+            Pin::new(&mut this.file),
+            &mut this.buffer,
+            cx,
+            this.remaining,
+        ) {
+            Poll::Ready(Ok(chunk)) => chunk,
+            Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err.to_string()))),
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if chunk.is_empty() {
+            return Poll::Ready(Some(Err(format!(
+                "The file ended {} bytes short of the length it had when the upload started",
+                this.remaining
+            ))));
+        }
+
+        this.remaining -= chunk.len() as u64;
+
+        Poll::Ready(Some(Ok(Frame::data(chunk))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
+
+/// Reads the next chunk of at most `max_len` (and at most [`READ_CHUNK_SIZE`])
+/// bytes from `reader` through `buffer`, which is reused between calls. An
+/// empty chunk is the end of the reader.
+pub(crate) fn poll_read_chunk<R: AsyncRead + ?Sized>(
+    reader: Pin<&mut R>,
+    buffer: &mut BytesMut,
+    cx: &mut Context<'_>,
+    max_len: u64,
+) -> Poll<std::io::Result<Bytes>> {
+    let chunk_size = max_len.min(READ_CHUNK_SIZE as u64) as usize;
+    buffer.resize(chunk_size, 0);
+
+    let mut read_buf = ReadBuf::new(buffer);
+
+    match reader.poll_read(cx, &mut read_buf) {
+        Poll::Ready(Ok(())) => {}
+        Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+        Poll::Pending => return Poll::Pending,
+    }
+
+    let read = read_buf.filled().len();
+
+    Poll::Ready(Ok(buffer.split_to(read).freeze()))
+}
+
+/// The `Content-Type` of a file going by its extension, for the common cases;
+/// `None` for anything else, which is then sent without one.
+pub(crate) fn guess_content_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+    let result = match extension.as_str() {
+        "txt" | "log" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "csv" => "text/csv",
+        "md" => "text/markdown",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "yaml" | "yml" => "application/yaml",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "zst" => "application/zstd",
+        "7z" => "application/x-7z-compressed",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "bin" | "iso" | "img" => "application/octet-stream",
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_content_type_is_guessed_from_the_extension() {
+        assert_eq!(
+            guess_content_type(Path::new("/tmp/report.JSON")),
+            Some("application/json")
+        );
+        assert_eq!(
+            guess_content_type(Path::new("backup.tar.gz")),
+            Some("application/gzip")
+        );
+        assert_eq!(guess_content_type(Path::new("Makefile")), None);
+        assert_eq!(guess_content_type(Path::new("notes.unknown")), None);
+    }
+
+    #[tokio::test]
+    async fn a_file_that_shrinks_mid_upload_fails_the_body() {
+        let path = std::env::temp_dir().join(format!(
+            "flurl-file-body-{}.bin",
+            crate::body::rand_string(8)
+        ));
+        std::fs::write(&path, vec![b'x'; 3 * READ_CHUNK_SIZE]).unwrap();
+
+        let mut body = FileBody::open(&path).await.unwrap();
+        assert_eq!(body.get_len(), 3 * READ_CHUNK_SIZE as u64);
+
+        let first = http_body_util::BodyExt::frame(&mut body)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.into_data().unwrap().len(), READ_CHUNK_SIZE);
+
+        std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(READ_CHUNK_SIZE as u64)
+            .unwrap();
+
+        let err = http_body_util::BodyExt::frame(&mut body)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert!(err.contains("short"), "{}", err);
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
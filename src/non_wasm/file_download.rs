@@ -0,0 +1,703 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{FlUrl, FlUrlError, FlUrlResponse};
+
+/// What `save_to_file_with` does besides writing the body.
+///
+/// `fsync` makes the download survive a power cut once `save_to_file_with` has
+/// returned: the file is synced before it is renamed into place, and the
+/// directory after (on unix). Off by default — it costs a disk flush per download.
+///
+/// `sha256` hashes the body as it is written, for checking a download against a
+/// published checksum without reading the file back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SaveToFileOptions {
+    pub(crate) fsync: bool,
+    pub(crate) sha256: bool,
+}
+
+impl SaveToFileOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_fsync(mut self, fsync: bool) -> Self {
+        self.fsync = fsync;
+        self
+    }
+
+    pub fn set_sha256(mut self, sha256: bool) -> Self {
+        self.sha256 = sha256;
+        self
+    }
+}
+
+/// A body saved by `save_to_file` / `save_to_file_with`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedFile {
+    pub path: PathBuf,
+    /// The size of the file — for `download_resumable`, resumed bytes included.
+    pub bytes_written: u64,
+    /// Set when [`SaveToFileOptions::set_sha256`] asked for it.
+    pub sha256: Option<[u8; 32]>,
+}
+
+impl SavedFile {
+    /// The SHA-256 as lowercase hex, the way `sha256sum` prints it.
+    pub fn get_sha256_hex(&self) -> Option<String> {
+        let sha256 = self.sha256.as_ref()?;
+        Some(sha256.iter().map(|byte| format!("{:02x}", byte)).collect())
+    }
+}
+
+/// How `download_resumable_with` retries, and what it does besides writing the
+/// body — `fsync` and `sha256` as in [`SaveToFileOptions`].
+///
+/// A transport failure is retried `max_retries` times in a row (5 by default),
+/// the wait before each retry doubling from `initial_backoff` (500ms) up to
+/// `max_backoff` (30s). An attempt that got further than the one before resets
+/// both: a long download over a flaky link keeps going as long as it progresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResumableDownloadOptions {
+    max_retries: usize,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+    save: SaveToFileOptions,
+}
+
+impl Default for ResumableDownloadOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            save: SaveToFileOptions::new(),
+        }
+    }
+}
+
+impl ResumableDownloadOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    pub fn set_backoff(mut self, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn set_fsync(mut self, fsync: bool) -> Self {
+        self.save = self.save.set_fsync(fsync);
+        self
+    }
+
+    pub fn set_sha256(mut self, sha256: bool) -> Self {
+        self.save = self.save.set_sha256(sha256);
+        self
+    }
+}
+
+/// Where a download is written until it is complete: a temp file next to the
+/// target, renamed over it by `commit`. Readers of the target never see a half
+/// written file, and a download that fails — or whose future is dropped — leaves
+/// the target as it was and removes the temp file.
+pub(crate) struct FileSink {
+    file: tokio::fs::File,
+    // None once `commit` has renamed it into place.
+    temp_path: Option<PathBuf>,
+    path: PathBuf,
+    hasher: Option<Sha256>,
+    bytes_written: u64,
+    fsync: bool,
+}
+
+impl FileSink {
+    pub async fn create(path: &Path, options: &SaveToFileOptions) -> Result<Self, FlUrlError> {
+        let Some(file_name) = path.file_name() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} does not name a file", path.display()),
+            )
+            .into());
+        };
+
+        // Same directory as the target, so the rename stays on one file system
+        // and is atomic. Hidden and random, so concurrent downloads of the same
+        // file do not write into each other.
+        let temp_name = format!(
+            ".{}.{}.tmp",
+            file_name.to_string_lossy(),
+            crate::body::rand_string(12)
+        );
+        let temp_path = path.with_file_name(temp_name);
+
+        let file = tokio::fs::File::create(&temp_path).await?;
+
+        Ok(Self {
+            file,
+            temp_path: Some(temp_path),
+            path: path.to_path_buf(),
+            hasher: if options.sha256 {
+                Some(Sha256::new())
+            } else {
+                None
+            },
+            bytes_written: 0,
+            fsync: options.fsync,
+        })
+    }
+
+    /// For writers that fill the temp file at offsets on their own handles
+    /// (`download_parallel`): sizes it to `len` and tells where it is. `commit`
+    /// then reports `len` as written. Those bytes are not hashed: create the sink
+    /// without `sha256` and hash the file once it is complete.
+    pub async fn preallocate(&mut self, len: u64) -> Result<PathBuf, FlUrlError> {
+        self.file.set_len(len).await?;
+        self.bytes_written = len;
+        Ok(self.temp_path.clone().unwrap())
+    }
+
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), FlUrlError> {
+        self.file.write_all(data).await?;
+
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(data);
+        }
+
+        self.bytes_written += data.len() as u64;
+        Ok(())
+    }
+
+    pub async fn commit(mut self) -> Result<SavedFile, FlUrlError> {
+        // tokio hands writes to a blocking thread: flush waits for the last one.
+        self.file.flush().await?;
+
+        if self.fsync {
+            self.file.sync_all().await?;
+        }
+
+        let temp_path = self.temp_path.as_ref().unwrap();
+        tokio::fs::rename(temp_path, &self.path).await?;
+        self.temp_path = None;
+
+        if self.fsync {
+            sync_parent_dir(&self.path).await?;
+        }
+
+        Ok(SavedFile {
+            path: self.path.clone(),
+            bytes_written: self.bytes_written,
+            sha256: self.hasher.take().map(|hasher| hasher.finalize().into()),
+        })
+    }
+}
+
+impl Drop for FileSink {
+    fn drop(&mut self) {
+        if let Some(temp_path) = self.temp_path.take() {
+            let _ = std::fs::remove_file(temp_path);
+        }
+    }
+}
+
+/// What is on disk of a resumable download: `<path>.part` with the bytes so far
+/// and `<path>.part.validator` with the `If-Range` value they belong to.
+struct PartialDownload {
+    path: PathBuf,
+    part_path: PathBuf,
+    validator_path: PathBuf,
+}
+
+/// How one attempt of a resumable download ended, short of completing it.
+enum AttemptError {
+    /// Worth another attempt: the connection, not the download, failed.
+    Transport(FlUrlError),
+    /// The `.part` did not fit the answer and was dropped; go again at once.
+    Restart,
+    Fatal(FlUrlError),
+}
+
+impl PartialDownload {
+    fn new(path: &Path) -> Result<Self, FlUrlError> {
+        let Some(file_name) = path.file_name() else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} does not name a file", path.display()),
+            )
+            .into());
+        };
+        let file_name = file_name.to_string_lossy();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            part_path: path.with_file_name(format!("{}.part", file_name)),
+            validator_path: path.with_file_name(format!("{}.part.validator", file_name)),
+        })
+    }
+
+    /// How many bytes the next attempt can skip, and the validator to send with
+    /// the range. A `.part` without a validator can not be trusted to match the
+    /// file on the server, so it counts as nothing.
+    async fn get_resume_point(&self) -> Result<(u64, Option<String>), FlUrlError> {
+        let validator = match tokio::fs::read_to_string(&self.validator_path).await {
+            Ok(validator) => validator.trim().to_string(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let len = match tokio::fs::metadata(&self.part_path).await {
+            Ok(metadata) => metadata.len(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err.into()),
+        };
+
+        if validator.is_empty() || len == 0 {
+            return Ok((0, None));
+        }
+
+        Ok((len, Some(validator)))
+    }
+
+    /// Starts the `.part` over for a full `200` body. The validator is written
+    /// first, so a `.part` on disk is never paired with an older one.
+    async fn start_over(&self, validator: Option<&str>) -> Result<tokio::fs::File, FlUrlError> {
+        match validator {
+            Some(validator) => tokio::fs::write(&self.validator_path, validator).await?,
+            None => remove_if_exists(&self.validator_path).await?,
+        }
+
+        Ok(tokio::fs::File::create(&self.part_path).await?)
+    }
+
+    async fn open_for_append(&self) -> Result<tokio::fs::File, FlUrlError> {
+        let file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .open(&self.part_path)
+            .await?;
+        Ok(file)
+    }
+
+    /// A hasher that has already seen the first `len` bytes of the `.part`.
+    async fn hash_existing(&self, len: u64) -> Result<Sha256, FlUrlError> {
+        hash_file(&self.part_path, len).await
+    }
+
+    async fn discard(&self) -> Result<(), FlUrlError> {
+        remove_if_exists(&self.part_path).await?;
+        remove_if_exists(&self.validator_path).await
+    }
+
+    async fn commit(
+        self,
+        bytes_written: u64,
+        hasher: Option<Sha256>,
+        options: &SaveToFileOptions,
+    ) -> Result<SavedFile, FlUrlError> {
+        if options.fsync {
+            tokio::fs::File::open(&self.part_path)
+                .await?
+                .sync_all()
+                .await?;
+        }
+
+        tokio::fs::rename(&self.part_path, &self.path).await?;
+        remove_if_exists(&self.validator_path).await?;
+
+        if options.fsync {
+            sync_parent_dir(&self.path).await?;
+        }
+
+        Ok(SavedFile {
+            path: self.path,
+            bytes_written,
+            sha256: hasher.map(|hasher| hasher.finalize().into()),
+        })
+    }
+}
+
+pub(crate) async fn download_resumable(
+    fl_url: FlUrl,
+    path: &Path,
+    options: ResumableDownloadOptions,
+) -> Result<SavedFile, FlUrlError> {
+    let partial = PartialDownload::new(path)?;
+
+    let mut failures = 0;
+    let mut backoff = options.initial_backoff;
+    let mut furthest = 0;
+
+    loop {
+        let err = match download_attempt(&fl_url, &partial, &options.save).await {
+            Ok((bytes_written, hasher)) => {
+                return partial.commit(bytes_written, hasher, &options.save).await;
+            }
+            Err(AttemptError::Fatal(err)) => return Err(err),
+            Err(AttemptError::Restart) => continue,
+            Err(AttemptError::Transport(err)) => err,
+        };
+
+        let (reached, _) = partial.get_resume_point().await?;
+        if reached > furthest {
+            furthest = reached;
+            failures = 0;
+            backoff = options.initial_backoff;
+        }
+
+        if failures >= options.max_retries {
+            return Err(err);
+        }
+
+        tokio::time::sleep(backoff).await;
+        failures += 1;
+        backoff = (backoff * 2).min(options.max_backoff);
+    }
+}
+
+/// One GET of the download: resumes the `.part` where the server allows it and
+/// writes the body into it. Ok with the size of the whole file once the body has
+/// been read to its end.
+async fn download_attempt(
+    fl_url: &FlUrl,
+    partial: &PartialDownload,
+    options: &SaveToFileOptions,
+) -> Result<(u64, Option<Sha256>), AttemptError> {
+    let (offset, validator) = partial
+        .get_resume_point()
+        .await
+        .map_err(AttemptError::Fatal)?;
+
+    let mut request = fl_url.fork_for_download();
+    if let Some(validator) = validator.as_deref() {
+        request = request
+            .with_header("Range", format!("bytes={}-", offset))
+            .with_header("If-Range", validator);
+    }
+
+    let response = request.get().await.map_err(classify_transport_error)?;
+
+    let (mut file, offset) = match response.get_status_code() {
+        206 if offset > 0 => {
+            let start = get_content_range(&response).and_then(|range| range.start);
+
+            if start != Some(offset) {
+                partial.discard().await.map_err(AttemptError::Fatal)?;
+                return Err(AttemptError::Restart);
+            }
+
+            let file = partial
+                .open_for_append()
+                .await
+                .map_err(AttemptError::Fatal)?;
+            (file, offset)
+        }
+        200 => {
+            let validator = get_validator(&response);
+            let file = partial
+                .start_over(validator.as_deref())
+                .await
+                .map_err(AttemptError::Fatal)?;
+            (file, 0)
+        }
+        // The `.part` already holds the whole file: an earlier attempt read the
+        // body to its end but did not get to the rename.
+        416 if offset > 0 => {
+            let complete = get_content_range(&response).and_then(|range| range.total);
+
+            if complete != Some(offset) {
+                partial.discard().await.map_err(AttemptError::Fatal)?;
+                return Err(AttemptError::Restart);
+            }
+
+            let hasher = if options.sha256 {
+                Some(
+                    partial
+                        .hash_existing(offset)
+                        .await
+                        .map_err(AttemptError::Fatal)?,
+                )
+            } else {
+                None
+            };
+
+            return Ok((offset, hasher));
+        }
+        status_code => {
+            return Err(AttemptError::Fatal(FlUrlError::UnexpectedStatusCode(
+                status_code,
+            )))
+        }
+    };
+
+    let mut hasher = if options.sha256 {
+        Some(
+            partial
+                .hash_existing(offset)
+                .await
+                .map_err(AttemptError::Fatal)?,
+        )
+    } else {
+        None
+    };
+
+    let mut stream = response.get_body_as_stream();
+    let mut bytes_written = offset;
+
+    let read_result = loop {
+        let chunk = match stream.get_next_bytes().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break Ok(()),
+            Err(err) => break Err(classify_transport_error(err)),
+        };
+
+        if let Err(err) = file.write_all(&chunk).await {
+            break Err(AttemptError::Fatal(err.into()));
+        }
+
+        if let Some(hasher) = hasher.as_mut() {
+            hasher.update(&chunk);
+        }
+
+        bytes_written += chunk.len() as u64;
+    };
+
+    // Also after a failure: the next attempt resumes from the length on disk, so
+    // every byte taken off the wire has to be there first.
+    file.flush()
+        .await
+        .map_err(|err| AttemptError::Fatal(err.into()))?;
+
+    read_result?;
+
+    Ok((bytes_written, hasher))
+}
+
+fn classify_transport_error(err: FlUrlError) -> AttemptError {
+    if is_transport_error(&err) {
+        AttemptError::Transport(err)
+    } else {
+        AttemptError::Fatal(err)
+    }
+}
+
+/// A failure of the connection rather than of the request: worth another
+/// attempt. Shared with the reconnects of `get_event_stream`.
+pub(crate) fn is_transport_error(err: &FlUrlError) -> bool {
+    match err {
+        FlUrlError::Timeout
+        | FlUrlError::ReadingHyperBodyError(_)
+        | FlUrlError::CanNotEstablishConnection(_)
+        | FlUrlError::InvalidHttp1HandShake(_)
+        | FlUrlError::HyperError(_)
+        | FlUrlError::MyHttpClientError(_) => true,
+        #[cfg(all(unix, feature = "with-ssh"))]
+        FlUrlError::SshSessionError(_) => true,
+        _ => false,
+    }
+}
+
+/// What `If-Range` can be sent with next time: a strong ETag, else
+/// Last-Modified. A weak ETag can not be used for a range.
+pub(crate) fn get_validator(response: &FlUrlResponse) -> Option<String> {
+    if let Ok(Some(etag)) = response.get_header_case_insensitive("etag") {
+        if !etag.starts_with("W/") {
+            return Some(etag.to_string());
+        }
+    }
+
+    match response.get_header_case_insensitive("last-modified") {
+        Ok(Some(last_modified)) => Some(last_modified.to_string()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ContentRange {
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    pub total: Option<u64>,
+}
+
+pub(crate) fn get_content_range(response: &FlUrlResponse) -> Option<ContentRange> {
+    let value = response
+        .get_header_case_insensitive("content-range")
+        .ok()??;
+    parse_content_range(value)
+}
+
+/// `bytes 100-199/1000`, `bytes 100-199/*` or, on a 416, `bytes */1000`.
+pub(crate) fn parse_content_range(value: &str) -> Option<ContentRange> {
+    let (unit, range) = value.trim().split_once(' ')?;
+    if !unit.eq_ignore_ascii_case("bytes") {
+        return None;
+    }
+
+    let (range, total) = range.split_once('/')?;
+
+    let (start, end) = match range {
+        "*" => (None, None),
+        range => {
+            let (start, end) = range.split_once('-')?;
+            (Some(start.parse().ok()?), Some(end.parse().ok()?))
+        }
+    };
+
+    let total = match total {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+
+    Some(ContentRange { start, end, total })
+}
+
+/// A hasher that has seen the first `len` bytes of the file at `path`.
+pub(crate) async fn hash_file(path: &Path, len: u64) -> Result<Sha256, FlUrlError> {
+    let mut hasher = Sha256::new();
+
+    if len == 0 {
+        return Ok(hasher);
+    }
+
+    let mut file = tokio::fs::File::open(path).await?.take(len);
+    let mut buffer = vec![0u8; 64 * 1024];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher)
+}
+
+async fn remove_if_exists(path: &Path) -> Result<(), FlUrlError> {
+    match tokio::fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Makes the rename itself durable: on unix it is an entry in the directory, which
+/// has to be synced on its own.
+#[cfg(unix)]
+async fn sync_parent_dir(path: &Path) -> Result<(), FlUrlError> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    tokio::fs::File::open(parent).await?.sync_all().await?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn sync_parent_dir(_path: &Path) -> Result<(), FlUrlError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("flurl-{}-{}", name, crate::body::rand_string(8)));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn dir_entries(dir: &Path) -> Vec<String> {
+        let mut entries: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[tokio::test]
+    async fn the_target_only_appears_on_commit() {
+        let dir = temp_dir("commit");
+        let path = dir.join("artifact.bin");
+        std::fs::write(&path, b"old").unwrap();
+
+        let options = SaveToFileOptions::new().set_fsync(true).set_sha256(true);
+        let mut sink = FileSink::create(&path, &options).await.unwrap();
+        sink.write(b"hello, ").await.unwrap();
+        sink.write(b"world").await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"old");
+
+        let saved = sink.commit().await.unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello, world");
+        assert_eq!(saved.bytes_written, 12);
+        assert_eq!(
+            saved.get_sha256_hex().unwrap(),
+            "09ca7e4eaa6e8ae9c7d261167129184883644d07dfba7cbfbc4c8a2e08360d5b"
+        );
+        assert_eq!(dir_entries(&dir), vec!["artifact.bin".to_string()]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn content_ranges_are_parsed() {
+        assert_eq!(
+            parse_content_range("bytes 100-199/1000"),
+            Some(ContentRange {
+                start: Some(100),
+                end: Some(199),
+                total: Some(1000)
+            })
+        );
+        assert_eq!(
+            parse_content_range("bytes 100-199/*"),
+            Some(ContentRange {
+                start: Some(100),
+                end: Some(199),
+                total: None
+            })
+        );
+        assert_eq!(
+            parse_content_range("bytes */1000"),
+            Some(ContentRange {
+                start: None,
+                end: None,
+                total: Some(1000)
+            })
+        );
+        assert_eq!(parse_content_range("items 0-1/2"), None);
+        assert_eq!(parse_content_range("bytes x-1/2"), None);
+    }
+
+    #[tokio::test]
+    async fn a_sink_dropped_before_commit_leaves_nothing_behind() {
+        let dir = temp_dir("drop");
+        let path = dir.join("artifact.bin");
+
+        let mut sink = FileSink::create(&path, &SaveToFileOptions::new())
+            .await
+            .unwrap();
+        sink.write(b"partial").await.unwrap();
+        drop(sink);
+
+        assert!(dir_entries(&dir).is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
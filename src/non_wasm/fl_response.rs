@@ -1,12 +1,17 @@
-use std::{collections::HashMap, fmt::Debug, time::Duration};
+use std::{collections::HashMap, fmt::Debug, path::Path, sync::Arc, time::Duration};
 
 use hyper::{header::CONNECTION, StatusCode};
 use serde::de::DeserializeOwned;
 use my_http_utils::UrlBuilder;
 
 use crate::{
-    non_wasm::fl_response_as_stream::FlResponseAsStream, ConnectionReturner, FlUrlError,
-    FlUrlReadingHeaderError, ResponseBody,
+    non_wasm::{
+        bandwidth_limit::BandwidthLimit, file_download::FileSink,
+        fl_response_as_multipart::FlResponseAsMultipart, fl_response_as_stream::FlResponseAsStream,
+        progress::ProgressCallback,
+    },
+    ConnectionReturner, FlUrlError, FlUrlReadingHeaderError, ResponseBody, SaveToFileOptions,
+    SavedFile,
 };
 
 pub struct FlUrlResponse {
@@ -14,11 +19,15 @@ pub struct FlUrlResponse {
     status_code: StatusCode,
     response: ResponseBody,
     body_read_timeout: Option<Duration>,
-    decompress_gzip: bool,
+    decompress_response: bool,
     // Owns the checked-out connection until the body is fully consumed. Dropped
     // without returning (dispose) on error, `Connection: close`, or when the
     // response is discarded with the body unread.
     connection_returner: Option<Box<dyn ConnectionReturner>>,
+    // Set by `on_download_progress`; handed to whatever reads the body.
+    download_progress: Option<ProgressCallback>,
+    // The request's (or its cache's) `set_bandwidth_limit`, for reading the body.
+    bandwidth_limit: Option<Arc<BandwidthLimit>>,
 }
 
 impl Debug for FlUrlResponse {
@@ -42,8 +51,10 @@ impl FlUrlResponse {
             response: ResponseBody::Hyper(Some(response.into_response())),
             url,
             body_read_timeout: None,
-            decompress_gzip: false,
+            decompress_response: false,
             connection_returner: None,
+            download_progress: None,
+            bandwidth_limit: None,
         }
     }
 
@@ -51,8 +62,12 @@ impl FlUrlResponse {
         self.body_read_timeout = timeout;
     }
 
-    pub(crate) fn set_decompress_gzip(&mut self, decompress_gzip: bool) {
-        self.decompress_gzip = decompress_gzip;
+    pub(crate) fn set_bandwidth_limit(&mut self, bandwidth_limit: Option<Arc<BandwidthLimit>>) {
+        self.bandwidth_limit = bandwidth_limit;
+    }
+
+    pub(crate) fn set_decompress_response(&mut self, decompress_response: bool) {
+        self.decompress_response = decompress_response;
     }
 
     pub(crate) fn set_connection_returner(&mut self, returner: Box<dyn ConnectionReturner>) {
@@ -65,7 +80,11 @@ impl FlUrlResponse {
     async fn load_body(&mut self) -> Result<(), FlUrlError> {
         let load_result = self
             .response
-            .convert_to_slice_if_needed(self.body_read_timeout)
+            .convert_to_slice_if_needed(
+                self.body_read_timeout,
+                self.download_progress.clone(),
+                self.bandwidth_limit.clone(),
+            )
             .await;
 
         match load_result {
@@ -80,11 +99,11 @@ impl FlUrlResponse {
                 } else {
                     self.connection_returner.take();
                 }
-                // Gzip decoding is a body-level transform run AFTER the
+                // Decompression is a body-level transform run AFTER the
                 // connection is settled: a decode failure is a data error, not a
                 // connection problem — the socket was already fully drained.
-                if self.decompress_gzip {
-                    self.response.decode_gzip_if_needed()?;
+                if self.decompress_response {
+                    self.response.decode_content_encoding_if_needed()?;
                 }
                 Ok(())
             }
@@ -138,7 +157,7 @@ impl FlUrlResponse {
         // The body escapes fl-url's control while still streaming from the
         // checked-out connection: keep the connection alive via a body guard
         // that settles it on end-of-stream instead of disposing it mid-body.
-        match returner {
+        let response = match returner {
             None => response,
             Some(returner) => response.map(|body| {
                 crate::non_wasm::escaped_body_guard::EscapedBodyGuard::new(
@@ -148,9 +167,42 @@ impl FlUrlResponse {
                 )
                 .boxed()
             }),
+        };
+
+        if !self.decompress_response {
+            return response;
+        }
+
+        // Decoded above the guard: the guard still sees the raw body, so the
+        // connection is settled by the wire bytes, not by the decoded ones.
+        let (mut parts, body) = response.into_parts();
+
+        match ResponseBody::create_stream_decoder(&mut parts.headers) {
+            Some(decoder) => my_http_client::HyperResponse::from_parts(
+                parts,
+                crate::non_wasm::decoding_body::DecodingBody::new(body, decoder).boxed(),
+            ),
+            None => my_http_client::HyperResponse::from_parts(parts, body),
         }
     }
 
+    /// Calls `callback` as the body comes in, with the bytes received so far and
+    /// the `Content-Length`, when the response has one. Applies to every way of
+    /// reading the body — `get_body_as_slice`, `get_json`, `receive_body`,
+    /// `get_body_as_stream`, `save_to_file` — except `into_hyper_response`, which
+    /// hands the body out as it is.
+    ///
+    /// The bytes are counted as they come off the wire: with `accept_compression`
+    /// that is the encoded body, which is what the `Content-Length` is the length
+    /// of. The callback runs on the task reading the body, once per chunk.
+    pub fn on_download_progress(
+        mut self,
+        callback: impl Fn(u64, Option<u64>) + Send + Sync + 'static,
+    ) -> Self {
+        self.download_progress = Some(std::sync::Arc::new(callback));
+        self
+    }
+
     pub fn get_header(&self, name: &str) -> Result<Option<&str>, FlUrlReadingHeaderError> {
         self.response.get_header(name)
     }
@@ -208,10 +260,62 @@ impl FlUrlResponse {
             self.url,
             response,
             self.body_read_timeout,
+            self.decompress_response,
             self.connection_returner,
+            self.download_progress,
+            self.bandwidth_limit,
         )
     }
 
+    /// The body of a `multipart/*` response — `multipart/byteranges`,
+    /// `multipart/mixed` — read part by part as it arrives, each part with its
+    /// headers and its content streamed. Fails with
+    /// [`FlUrlError::InvalidMultipart`] when the `Content-Type` is not multipart
+    /// with a boundary. Like [`Self::get_body_as_stream`], it has to be called
+    /// before the body is read into memory.
+    pub fn into_multipart(self) -> Result<FlResponseAsMultipart, FlUrlError> {
+        FlResponseAsMultipart::create(self.get_body_as_stream())
+    }
+
+    /// Streams the body into the file at `path`, at constant memory, and returns
+    /// how many bytes were written. See [`Self::save_to_file_with`].
+    pub async fn save_to_file(self, path: impl AsRef<Path>) -> Result<SavedFile, FlUrlError> {
+        self.save_to_file_with(path, SaveToFileOptions::new()).await
+    }
+
+    /// Streams the body into the file at `path`, chunk by chunk as it arrives.
+    /// The chunks go to a temp file in the same directory, renamed over `path`
+    /// only once the whole body is on disk: a failed or cancelled download leaves
+    /// `path` as it was. `options` adds an fsync and a SHA-256 of the body.
+    ///
+    /// `set_response_body_timeout` bounds the wait for every chunk, as it does for
+    /// `get_body_as_stream`; with `accept_compression` the decoded body is saved.
+    /// The connection goes back to the pool once the body has been read to its
+    /// end. The status code is not looked at — check it before saving an error
+    /// page as the download.
+    pub async fn save_to_file_with(
+        mut self,
+        path: impl AsRef<Path>,
+        options: SaveToFileOptions,
+    ) -> Result<SavedFile, FlUrlError> {
+        let mut sink = FileSink::create(path.as_ref(), &options).await?;
+
+        if !matches!(self.response, ResponseBody::Hyper(_)) {
+            // Already read into memory by `get_body_as_slice` & co.
+            let body = self.response.take_loaded_body()?;
+            sink.write(&body).await?;
+            return sink.commit().await;
+        }
+
+        let mut stream = self.get_body_as_stream();
+
+        while let Some(chunk) = stream.get_next_bytes().await? {
+            sink.write(&chunk).await?;
+        }
+
+        sink.commit().await
+    }
+
     #[deprecated(note = "Use get_body_as_str")]
     pub async fn body_as_str(&mut self) -> Result<&str, FlUrlError> {
         self.load_body().await?;
@@ -0,0 +1,460 @@
+use std::ops::RangeInclusive;
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{FlResponseAsStream, FlUrlError};
+
+// A header block longer than this is taken for a missing blank line, not waited
+// out: the body would otherwise be buffered whole looking for it.
+const MAX_PART_HEADERS_SIZE: usize = 64 * 1024;
+
+/// A `multipart/*` response body read part by part, as it arrives: the ranges
+/// of a `multipart/byteranges` answer to a multi-range request, the responses
+/// of a `multipart/mixed` batch call (OData `$batch`, Google batch). Built with
+/// `FlUrlResponse::into_multipart`.
+///
+/// ```no_run
+/// # async fn doc() -> Result<(), flurl::FlUrlError> {
+/// let mut multipart = flurl::FlUrl::new("https://mirror.example.com/disk.img")
+///     .with_header("Range", "bytes=0-511,1048576-1049087")
+///     .get()
+///     .await?
+///     .into_multipart()?;
+///
+/// while let Some(mut part) = multipart.next_part().await? {
+///     let range = part.get_content_range();
+///     while let Some(chunk) = part.get_next_chunk().await? {
+///         // write `chunk` at `range`
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// A part is read through a borrow of the reader, so only one is open at a
+/// time; `next_part` skips whatever of the current part was left unread. The
+/// preamble before the first part and the epilogue after the last are dropped.
+/// The body is read with everything `get_body_as_stream` applies — the body
+/// timeout, decoding, progress, bandwidth limit — and the connection goes back to
+/// the pool once it has been read to its end.
+pub struct FlResponseAsMultipart {
+    stream: FlResponseAsStream,
+    // `\r\n--boundary`: the line break ahead of a delimiter belongs to it, not
+    // to the content of the part before.
+    delimiter: Bytes,
+    buffer: BytesMut,
+    state: MultipartState,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MultipartState {
+    Preamble,
+    // Right after a delimiter: the next part's headers, or `--` for the end.
+    Delimiter,
+    Content,
+    Done,
+}
+
+impl FlResponseAsMultipart {
+    /// Reads the boundary off the `Content-Type` of `stream`.
+    pub(crate) fn create(stream: FlResponseAsStream) -> Result<Self, FlUrlError> {
+        let content_type = stream
+            .get_parts()
+            .headers
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+
+        let boundary = get_boundary(content_type).ok_or_else(|| {
+            FlUrlError::InvalidMultipart(format!(
+                "Content-Type '{}' is not multipart with a boundary",
+                content_type
+            ))
+        })?;
+
+        // The body starts with the first delimiter, with no line break ahead of
+        // it: one is put in front so that every delimiter looks the same.
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(b"\r\n");
+
+        Ok(Self {
+            stream,
+            delimiter: Bytes::from(format!("\r\n--{}", boundary)),
+            buffer,
+            state: MultipartState::Preamble,
+        })
+    }
+
+    /// The `Content-Type` of the whole response, as `multipart/byteranges;
+    /// boundary=…` or `multipart/mixed; boundary=…`.
+    pub fn get_content_type(&self) -> Option<&str> {
+        self.stream
+            .get_parts()
+            .headers
+            .get(hyper::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+    }
+
+    /// The next part with its headers, or `None` after the last.
+    pub async fn next_part(&mut self) -> Result<Option<MultipartPart<'_>>, FlUrlError> {
+        loop {
+            match self.state {
+                MultipartState::Preamble => {
+                    match find(&self.buffer, &self.delimiter) {
+                        Some(pos) => {
+                            let _ = self.buffer.split_to(pos + self.delimiter.len());
+                            self.state = MultipartState::Delimiter;
+                        }
+                        None => {
+                            // Keep what could be the start of the delimiter.
+                            let keep = self.delimiter.len() - 1;
+                            if self.buffer.len() > keep {
+                                let _ = self.buffer.split_to(self.buffer.len() - keep);
+                            }
+                            self.read_more().await?;
+                        }
+                    }
+                }
+                MultipartState::Delimiter => {
+                    if let Some(headers) = self.read_part_headers().await? {
+                        self.state = MultipartState::Content;
+                        return Ok(Some(MultipartPart {
+                            headers,
+                            multipart: self,
+                        }));
+                    }
+
+                    self.state = MultipartState::Done;
+                }
+                MultipartState::Content => {
+                    // The rest of the part before, unread.
+                    while self.next_content_chunk().await?.is_some() {}
+                }
+                MultipartState::Done => {
+                    self.skip_epilogue().await?;
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// The headers of the part that starts after the delimiter just read, or
+    /// `None` when that was the closing delimiter.
+    async fn read_part_headers(&mut self) -> Result<Option<Vec<(String, String)>>, FlUrlError> {
+        // `--` ends the body; otherwise the delimiter line ends after optional
+        // whitespace. The headers follow up to a blank line.
+        loop {
+            if self.buffer.len() >= 2 && &self.buffer[..2] == b"--" {
+                return Ok(None);
+            }
+
+            if let Some(line_end) = find(&self.buffer, b"\r\n") {
+                if self.buffer[..line_end]
+                    .iter()
+                    .all(|b| *b == b' ' || *b == b'\t')
+                {
+                    let _ = self.buffer.split_to(line_end + 2);
+                    break;
+                }
+
+                return Err(FlUrlError::InvalidMultipart(
+                    "A delimiter is followed by other text on its line".to_string(),
+                ));
+            }
+
+            if self.buffer.len() > MAX_PART_HEADERS_SIZE {
+                return Err(FlUrlError::InvalidMultipart(
+                    "A delimiter line does not end".to_string(),
+                ));
+            }
+
+            self.read_more().await?;
+        }
+
+        loop {
+            // A part without headers: the blank line comes first.
+            if self.buffer.starts_with(b"\r\n") {
+                let _ = self.buffer.split_to(2);
+                return Ok(Some(Vec::new()));
+            }
+
+            if let Some(end) = find(&self.buffer, b"\r\n\r\n") {
+                let block = self.buffer.split_to(end + 4);
+                return Ok(Some(parse_headers(&block[..end])));
+            }
+
+            if self.buffer.len() > MAX_PART_HEADERS_SIZE {
+                return Err(FlUrlError::InvalidMultipart(format!(
+                    "The headers of a part run over {} bytes",
+                    MAX_PART_HEADERS_SIZE
+                )));
+            }
+
+            self.read_more().await?;
+        }
+    }
+
+    /// The next chunk of the current part's content, or `None` once its closing
+    /// delimiter has been reached.
+    async fn next_content_chunk(&mut self) -> Result<Option<Bytes>, FlUrlError> {
+        if self.state != MultipartState::Content {
+            return Ok(None);
+        }
+
+        loop {
+            match find(&self.buffer, &self.delimiter) {
+                Some(0) => {
+                    let _ = self.buffer.split_to(self.delimiter.len());
+                    self.state = MultipartState::Delimiter;
+                    return Ok(None);
+                }
+                Some(pos) => return Ok(Some(self.buffer.split_to(pos).freeze())),
+                None => {
+                    // All but what could be the start of the delimiter is content.
+                    let keep = self.delimiter.len() - 1;
+                    if self.buffer.len() > keep {
+                        let len = self.buffer.len() - keep;
+                        return Ok(Some(self.buffer.split_to(len).freeze()));
+                    }
+
+                    self.read_more().await?;
+                }
+            }
+        }
+    }
+
+    /// Reads the body past the closing delimiter to its end, so that the
+    /// connection can go back to the pool.
+    async fn skip_epilogue(&mut self) -> Result<(), FlUrlError> {
+        self.buffer.clear();
+        while self.stream.get_next_bytes().await?.is_some() {}
+        Ok(())
+    }
+
+    async fn read_more(&mut self) -> Result<(), FlUrlError> {
+        match self.stream.get_next_bytes().await? {
+            Some(chunk) => {
+                self.buffer.extend_from_slice(&chunk);
+                Ok(())
+            }
+            None => Err(FlUrlError::InvalidMultipart(
+                "The body ended before the closing delimiter".to_string(),
+            )),
+        }
+    }
+}
+
+/// One part of a [`FlResponseAsMultipart`]: its headers, and its content read
+/// chunk by chunk.
+pub struct MultipartPart<'s> {
+    headers: Vec<(String, String)>,
+    multipart: &'s mut FlResponseAsMultipart,
+}
+
+impl MultipartPart<'_> {
+    pub fn get_headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// The first header named `name`, in any case.
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(header_name, _)| header_name.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn get_content_type(&self) -> Option<&str> {
+        self.get_header("content-type")
+    }
+
+    /// The bytes of the resource a `multipart/byteranges` part carries, first
+    /// and last included, from its `Content-Range`.
+    pub fn get_content_range(&self) -> Option<RangeInclusive<u64>> {
+        let range =
+            crate::non_wasm::file_download::parse_content_range(self.get_header("content-range")?)?;
+        Some(range.start?..=range.end?)
+    }
+
+    /// The next chunk of the content, or `None` at its end.
+    pub async fn get_next_chunk(&mut self) -> Result<Option<Vec<u8>>, FlUrlError> {
+        let chunk = self.multipart.next_content_chunk().await?;
+        Ok(chunk.map(|chunk| chunk.to_vec()))
+    }
+
+    /// The rest of the content, in memory.
+    pub async fn receive_body(self) -> Result<Vec<u8>, FlUrlError> {
+        let mut result = Vec::new();
+
+        while let Some(chunk) = self.multipart.next_content_chunk().await? {
+            result.extend_from_slice(&chunk);
+        }
+
+        Ok(result)
+    }
+}
+
+/// The `boundary` parameter of a `multipart/*` media type, quoted or not.
+fn get_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+
+    let media_type = params.next()?.trim();
+    if !media_type.get(..10)?.eq_ignore_ascii_case("multipart/") {
+        return None;
+    }
+
+    for param in params {
+        let Some((name, value)) = param.split_once('=') else {
+            continue;
+        };
+
+        if !name.trim().eq_ignore_ascii_case("boundary") {
+            continue;
+        }
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value);
+
+        if value.is_empty() {
+            return None;
+        }
+
+        return Some(value.to_string());
+    }
+
+    None
+}
+
+/// `Name: value` lines; a line starting with whitespace continues the one
+/// before (obsolete folding, still seen in batch responses).
+fn parse_headers(block: &[u8]) -> Vec<(String, String)> {
+    let block = String::from_utf8_lossy(block);
+    let mut result: Vec<(String, String)> = Vec::new();
+
+    for line in block.split("\r\n") {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some((_, value)) = result.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            result.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    result
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if haystack.len() < needle.len() {
+        return None;
+    }
+
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::non_wasm::test_utils::stream_of;
+
+    fn multipart_over(content_type: &str, chunks: &[&'static [u8]]) -> FlResponseAsMultipart {
+        use http_body_util::{BodyExt, StreamBody};
+
+        let frames: Vec<_> = chunks
+            .iter()
+            .map(|chunk| Ok(hyper::body::Frame::data(Bytes::from_static(chunk))))
+            .collect();
+
+        let body = StreamBody::new(stream_of(frames)).boxed();
+        let response = hyper::Response::builder()
+            .header("Content-Type", content_type)
+            .body(body)
+            .unwrap();
+
+        let stream =
+            FlResponseAsStream::new(my_http_utils::UrlBuilder::new("http://localhost"), response);
+
+        FlResponseAsMultipart::create(stream).unwrap()
+    }
+
+    #[tokio::test]
+    async fn byteranges_are_read_part_by_part_across_chunk_borders() {
+        // The delimiters are cut at every awkward place by the chunking.
+        let mut multipart = multipart_over(
+            "multipart/byteranges; boundary=THIS_STRING_SEPARATES",
+            &[
+                b"preamble\r\n--THIS_STRING_SEP",
+                b"ARATES\r\nContent-Type: application/pdf\r\nContent-Range: bytes 500-509/8000\r\n\r\n",
+                b"0123456789\r",
+                b"\n--THIS_STRING_SEPARATES\r\nContent-Type: application/pdf\r\n",
+                b"Content-Range: bytes 7000-7004/8000\r\n\r\nabcde\r\n--THIS_STRING_SEPARATES--",
+                b"\r\nepilogue",
+            ],
+        );
+
+        let part = multipart.next_part().await.unwrap().unwrap();
+        assert_eq!(part.get_content_type(), Some("application/pdf"));
+        assert_eq!(part.get_content_range(), Some(500..=509));
+        assert_eq!(part.receive_body().await.unwrap(), b"0123456789");
+
+        let part = multipart.next_part().await.unwrap().unwrap();
+        assert_eq!(part.get_content_range(), Some(7000..=7004));
+        assert_eq!(part.receive_body().await.unwrap(), b"abcde");
+
+        assert!(multipart.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn an_unread_part_is_skipped_and_a_part_may_have_no_headers() {
+        let mut multipart = multipart_over(
+            "multipart/mixed; boundary=\"batch_1\"",
+            &[b"--batch_1\r\nContent-Type: application/http\r\n\r\nGET /a HTTP/1.1\r\n\r\n\r\n--batch_1  \r\n\r\nsecond\r\n--batch_1--\r\n"],
+        );
+
+        let part = multipart.next_part().await.unwrap().unwrap();
+        assert_eq!(part.get_header("CONTENT-TYPE"), Some("application/http"));
+
+        let part = multipart.next_part().await.unwrap().unwrap();
+        assert_eq!(part.get_headers().count(), 0);
+        assert_eq!(part.receive_body().await.unwrap(), b"second");
+
+        assert!(multipart.next_part().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn a_body_cut_before_the_closing_delimiter_is_an_error() {
+        let mut multipart = multipart_over(
+            "multipart/mixed; boundary=b",
+            &[b"--b\r\n\r\npartial content"],
+        );
+
+        let part = multipart.next_part().await.unwrap().unwrap();
+        let err = part.receive_body().await.unwrap_err();
+        assert!(matches!(err, FlUrlError::InvalidMultipart(_)), "{:?}", err);
+    }
+
+    #[test]
+    fn the_boundary_is_read_off_the_content_type() {
+        assert_eq!(
+            get_boundary("multipart/mixed; charset=utf-8; Boundary=\"a b\""),
+            Some("a b".to_string())
+        );
+        assert_eq!(
+            get_boundary("Multipart/Byteranges;boundary=x"),
+            Some("x".to_string())
+        );
+        assert_eq!(get_boundary("application/json; boundary=x"), None);
+        assert_eq!(get_boundary("multipart/mixed"), None);
+    }
+}
@@ -1,20 +1,46 @@
-use std::time::Duration;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
 
+use bytes::Bytes;
 use http::response::Parts;
-use http_body_util::BodyExt;
+use hyper::body::Body;
 use my_http_utils::UrlBuilder;
 
-use crate::{ConnectionReturner, FlUrlError};
+use crate::{
+    compression::StreamDecoder,
+    non_wasm::{
+        bandwidth_limit::{BandwidthLimit, Direction, Throttle},
+        progress::{get_content_length, ProgressCallback, ProgressCounter},
+    },
+    ConnectionReturner, FlResponseAsyncRead, FlUrlError, ResponseBody,
+};
 
 pub struct FlResponseAsStream {
     pub url: UrlBuilder,
     parts: Parts,
     body: http_body_util::combinators::BoxBody<bytes::Bytes, String>,
     body_read_timeout: Option<Duration>,
+    // Armed while a read waits for the next frame, dropped once one arrives:
+    // `body_read_timeout` bounds every wait, not the whole body.
+    read_deadline: Option<Pin<Box<tokio::time::Sleep>>>,
+    // Set for `accept_compression` when the body came in a coding FlUrl knows;
+    // taken once the raw body has ended and the decoder is finished.
+    decoder: Option<StreamDecoder>,
+    body_ended: bool,
     // Owns the checked-out connection while the body is streaming. Returned to
     // the pool on clean end of stream; dropping the stream mid-way (or a read
     // error) disposes the connection instead.
     connection_returner: Option<Box<dyn ConnectionReturner>>,
+    // Set by `on_download_progress`: counts the raw chunks, before decoding.
+    progress: Option<ProgressCounter>,
+    // Set by `set_bandwidth_limit`: holds the next read back until the chunks
+    // read so far are paid for.
+    throttle: Option<Throttle>,
 }
 
 impl FlResponseAsStream {
@@ -22,69 +48,203 @@ impl FlResponseAsStream {
     /// body-read timeout, no connection returned to the pool). The crate builds
     /// pooled/timed streams via [`Self::create`].
     pub fn new(url: UrlBuilder, response: my_hyper_utils::MyHttpResponse) -> Self {
-        Self::create(url, response, None, None)
+        Self::create(url, response, None, false, None, None, None)
     }
 
     pub(crate) fn create(
         url: UrlBuilder,
         response: my_hyper_utils::MyHttpResponse,
         body_read_timeout: Option<Duration>,
+        decompress_response: bool,
         connection_returner: Option<Box<dyn ConnectionReturner>>,
+        download_progress: Option<ProgressCallback>,
+        bandwidth_limit: Option<Arc<BandwidthLimit>>,
     ) -> Self {
-        let (parts, body) = response.into_parts();
+        let (mut parts, body) = response.into_parts();
+
+        // Taken before the decoder drops `Content-Length`: the progress is of
+        // the body as it comes off the wire.
+        let progress = download_progress
+            .map(|callback| ProgressCounter::new(callback, get_content_length(&parts.headers)));
+
+        let decoder = if decompress_response {
+            ResponseBody::create_stream_decoder(&mut parts.headers)
+        } else {
+            None
+        };
 
         Self {
             url,
             parts,
             body,
             body_read_timeout,
+            read_deadline: None,
+            decoder,
+            body_ended: false,
             connection_returner,
+            progress,
+            throttle: bandwidth_limit.map(|limit| Throttle::new(limit, Direction::Download)),
         }
     }
 
+    /// The next chunk of the body, or `None` at its end. With
+    /// `accept_compression` a body in a coding FlUrl knows comes decoded, one
+    /// chunk at a time, and `get_parts` shows the headers of the decoded body:
+    /// no `Content-Encoding`, no `Content-Length`.
+    ///
+    /// Copies every chunk into a `Vec`; the `futures::Stream` implementation
+    /// hands out the `Bytes` frames themselves.
     pub async fn get_next_chunk(&mut self) -> Result<Option<Vec<u8>>, FlUrlError> {
-        let frame = match self.body_read_timeout {
-            Some(timeout) => match tokio::time::timeout(timeout, self.body.frame()).await {
-                Ok(frame) => frame,
-                Err(_elapsed) => {
+        let chunk = self.get_next_bytes().await?;
+        Ok(chunk.map(|chunk| chunk.to_vec()))
+    }
+
+    /// `get_next_chunk` without the copy, for the crate's own consumers.
+    pub(crate) async fn get_next_bytes(&mut self) -> Result<Option<Bytes>, FlUrlError> {
+        let chunk = std::future::poll_fn(|cx| self.poll_next_chunk(cx)).await;
+
+        // Awaited here, so the connection is back in the pool before `None`
+        // is returned.
+        if self.body_ended {
+            self.release_connection().await;
+        }
+
+        chunk
+    }
+
+    /// The body as a `tokio::io::AsyncRead` (and `AsyncBufRead`), for
+    /// `tokio::io::copy`, decoders and framed codecs. Errors surface as
+    /// `std::io::Error`s wrapping the [`FlUrlError`].
+    pub fn into_async_read(self) -> FlResponseAsyncRead {
+        FlResponseAsyncRead::new(self)
+    }
+
+    /// The next decoded chunk. On a clean end `body_ended` is set and the
+    /// connection is left for the caller to settle, the async way or the
+    /// polled way.
+    fn poll_next_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Bytes>, FlUrlError>> {
+        loop {
+            let chunk = match self.poll_next_raw_chunk(cx) {
+                Poll::Ready(Ok(chunk)) => chunk,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let Some(decoder) = self.decoder.as_mut() else {
+                return Poll::Ready(Ok(chunk));
+            };
+
+            let decoded = match chunk {
+                Some(chunk) => decoder.decode_chunk(&chunk),
+                None => {
+                    let mut decoder = self.decoder.take().unwrap();
+                    decoder.finish()
+                }
+            };
+
+            let decoded = match decoded {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    // Undecodable mid-body: nobody reads the rest of it, so the
+                    // connection can not be reused.
+                    self.decoder = None;
                     self.connection_returner.take();
-                    return Err(FlUrlError::Timeout);
+                    return Poll::Ready(Err(err));
                 }
-            },
-            None => self.body.frame().await,
+            };
+
+            // A chunk that completed nothing is not handed out as an empty one.
+            if !decoded.is_empty() {
+                return Poll::Ready(Ok(Some(Bytes::from(decoded))));
+            }
+
+            if self.decoder.is_none() {
+                return Poll::Ready(Ok(None));
+            }
+        }
+    }
+
+    fn poll_next_raw_chunk(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<Bytes>, FlUrlError>> {
+        if self.body_ended {
+            return Poll::Ready(Ok(None));
+        }
+
+        // Not a wait on the server: `body_read_timeout` is not armed for it.
+        if let Some(throttle) = self.throttle.as_mut() {
+            if throttle.poll_ready(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+
+        let frame = match Pin::new(&mut self.body).poll_frame(cx) {
+            Poll::Ready(frame) => frame,
+            Poll::Pending => return self.poll_read_deadline(cx),
         };
 
+        self.read_deadline = None;
+
         let Some(frame) = frame else {
             // Clean end of stream: the body is fully consumed, the connection
             // can go back to the pool.
-            self.release_connection().await;
-            return Ok(None);
+            self.body_ended = true;
+            return Poll::Ready(Ok(None));
         };
 
         let frame = match frame {
             Ok(frame) => frame,
             Err(err) => {
                 self.connection_returner.take();
-                return Err(FlUrlError::ReadingHyperBodyError(format!("{:?}", err)));
+                return Poll::Ready(Err(FlUrlError::ReadingHyperBodyError(format!("{:?}", err))));
             }
         };
 
         match frame.into_data() {
-            Ok(value) => Ok(Some(value.to_vec())),
+            Ok(value) => {
+                if let Some(progress) = self.progress.as_mut() {
+                    progress.advance(value.len());
+                }
+                if let Some(throttle) = self.throttle.as_mut() {
+                    throttle.consume(value.len());
+                }
+                Poll::Ready(Ok(Some(value)))
+            }
             // A non-data frame (e.g. HTTP/2 trailers) means no more body data
             // follows, so we treat it as a clean end of stream.
             Err(_non_data_frame) => {
-                self.release_connection().await;
-                Ok(None)
+                self.body_ended = true;
+                Poll::Ready(Ok(None))
             }
         }
     }
 
-    async fn release_connection(&mut self) {
-        let Some(returner) = self.connection_returner.take() else {
-            return;
+    /// The body has nothing yet: `Pending`, unless the wait has outlasted
+    /// `body_read_timeout`.
+    fn poll_read_deadline(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<Bytes>, FlUrlError>> {
+        let Some(timeout) = self.body_read_timeout else {
+            return Poll::Pending;
         };
 
+        let deadline = self
+            .read_deadline
+            .get_or_insert_with(|| Box::pin(tokio::time::sleep(timeout)));
+
+        match deadline.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.read_deadline = None;
+                self.connection_returner.take();
+                Poll::Ready(Err(FlUrlError::Timeout))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn connection_is_reusable(&self) -> bool {
         let close_requested = self
             .parts
             .headers
@@ -97,13 +257,208 @@ impl FlResponseAsStream {
             self.parts.status.as_u16(),
         );
 
-        if !close_requested && !drop_by_status {
+        !close_requested && !drop_by_status
+    }
+
+    async fn release_connection(&mut self) {
+        let Some(returner) = self.connection_returner.take() else {
+            return;
+        };
+
+        if self.connection_is_reusable() {
             returner.return_connection().await;
         }
         // else: dropping the returner disposes the connection
     }
 
+    /// `release_connection` for `poll_next`, which can not await: the return to
+    /// the pool is handed off to the runtime, as `EscapedBodyGuard` does.
+    fn release_connection_in_background(&mut self) {
+        let Some(returner) = self.connection_returner.take() else {
+            return;
+        };
+
+        if self.connection_is_reusable() {
+            if let Ok(handle) = tokio::runtime::Handle::try_current() {
+                handle.spawn(returner.return_connection());
+            }
+            // No runtime: dropping the returner disposes the connection.
+        }
+        // else: dropping the returner disposes the connection
+    }
+
     pub fn get_parts(&self) -> &Parts {
         &self.parts
     }
 }
+
+/// The body chunk by chunk, each one a frame as it came off the wire (or as it
+/// was decoded). Ends with the body: the connection goes back to the pool then,
+/// and dropping the stream before that disposes it.
+impl futures_core::Stream for FlResponseAsStream {
+    type Item = Result<Bytes, FlUrlError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        let chunk = match this.poll_next_chunk(cx) {
+            Poll::Ready(chunk) => chunk,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if this.body_ended {
+            this.release_connection_in_background();
+        }
+
+        Poll::Ready(chunk.transpose())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::non_wasm::test_utils::stream_of;
+    use std::sync::atomic::{AtomicU8, Ordering};
+    use std::sync::Arc;
+
+    // 0 = untouched, 1 = returned to pool, 2 = disposed (returner dropped
+    // without return).
+    struct SpyReturner(Arc<AtomicU8>);
+
+    #[async_trait::async_trait]
+    impl ConnectionReturner for SpyReturner {
+        async fn return_connection(self: Box<Self>) {
+            self.0.store(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Drop for SpyReturner {
+        fn drop(&mut self) {
+            let _ = self
+                .0
+                .compare_exchange(0, 2, Ordering::SeqCst, Ordering::SeqCst);
+        }
+    }
+
+    fn stream_over(chunks: &[&'static [u8]], outcome: &Arc<AtomicU8>) -> FlResponseAsStream {
+        use http_body_util::{BodyExt, StreamBody};
+
+        let frames: Vec<Result<hyper::body::Frame<Bytes>, String>> = chunks
+            .iter()
+            .map(|chunk| Ok(hyper::body::Frame::data(Bytes::from_static(chunk))))
+            .collect();
+
+        let body = StreamBody::new(stream_of(frames)).boxed();
+        let response = hyper::Response::new(body);
+
+        FlResponseAsStream::create(
+            UrlBuilder::new("http://localhost"),
+            response,
+            None,
+            false,
+            Some(Box::new(SpyReturner(outcome.clone()))),
+            None,
+            None,
+        )
+    }
+
+    async fn next(stream: &mut FlResponseAsStream) -> Option<Result<Bytes, FlUrlError>> {
+        use futures_core::Stream;
+        std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+    }
+
+    #[tokio::test]
+    async fn stream_hands_out_the_frames_and_pools_at_the_end() {
+        let outcome = Arc::new(AtomicU8::new(0));
+        let mut stream = stream_over(&[b"hello, ", b"world"], &outcome);
+
+        assert_eq!(
+            next(&mut stream).await.unwrap().unwrap().as_ref(),
+            b"hello, "
+        );
+        assert_eq!(next(&mut stream).await.unwrap().unwrap().as_ref(), b"world");
+        assert_eq!(outcome.load(Ordering::SeqCst), 0);
+
+        assert!(next(&mut stream).await.is_none());
+        tokio::task::yield_now().await;
+        assert_eq!(
+            outcome.load(Ordering::SeqCst),
+            1,
+            "connection must be pooled"
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_dropped_mid_body_disposes_the_connection() {
+        let outcome = Arc::new(AtomicU8::new(0));
+        {
+            let mut stream = stream_over(&[b"hello, ", b"world"], &outcome);
+            next(&mut stream).await.unwrap().unwrap();
+        }
+        tokio::task::yield_now().await;
+        assert_eq!(
+            outcome.load(Ordering::SeqCst),
+            2,
+            "unread body must dispose"
+        );
+    }
+
+    #[tokio::test]
+    async fn async_read_reads_the_whole_body_and_pools() {
+        use tokio::io::AsyncReadExt;
+
+        let outcome = Arc::new(AtomicU8::new(0));
+        let mut reader = stream_over(&[b"hello, ", b"", b"world"], &outcome).into_async_read();
+
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body).await.unwrap();
+
+        assert_eq!(body, b"hello, world");
+        tokio::task::yield_now().await;
+        assert_eq!(
+            outcome.load(Ordering::SeqCst),
+            1,
+            "connection must be pooled"
+        );
+    }
+
+    #[tokio::test]
+    async fn download_progress_counts_every_chunk_against_the_content_length() {
+        use http_body_util::{BodyExt, StreamBody};
+        use std::sync::Mutex;
+
+        let frames: Vec<Result<hyper::body::Frame<Bytes>, String>> = vec![
+            Ok(hyper::body::Frame::data(Bytes::from_static(b"hello, "))),
+            Ok(hyper::body::Frame::data(Bytes::from_static(b"world"))),
+        ];
+
+        let response = hyper::Response::builder()
+            .header(hyper::header::CONTENT_LENGTH, "12")
+            .body(StreamBody::new(stream_of(frames)).boxed())
+            .unwrap();
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+
+        let mut stream = FlResponseAsStream::create(
+            UrlBuilder::new("http://localhost"),
+            response,
+            None,
+            false,
+            None,
+            Some(Arc::new(move |received, total| {
+                sink.lock().unwrap().push((received, total));
+            })),
+            None,
+        );
+
+        while let Some(chunk) = next(&mut stream).await {
+            chunk.unwrap();
+        }
+
+        assert_eq!(
+            *reported.lock().unwrap(),
+            vec![(7, Some(12)), (12, Some(12))]
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use bytes::{Buf, Bytes};
+use futures_core::Stream;
+use tokio::io::{AsyncBufRead, AsyncRead, ReadBuf};
+
+use crate::{FlResponseAsStream, FlUrlError};
+
+/// A response body read through `tokio::io`, from
+/// [`FlResponseAsStream::into_async_read`]. Holds on to the frame being read
+/// from, so a small read buffer costs no extra copies. The connection is
+/// settled like the stream's: pooled once the body reads to its end, disposed if
+/// the reader is dropped before that.
+pub struct FlResponseAsyncRead {
+    stream: FlResponseAsStream,
+    // What is left of the frame the last reads were served from.
+    chunk: Bytes,
+    done: bool,
+}
+
+impl FlResponseAsyncRead {
+    pub(crate) fn new(stream: FlResponseAsStream) -> Self {
+        Self {
+            stream,
+            chunk: Bytes::new(),
+            done: false,
+        }
+    }
+
+    pub fn get_parts(&self) -> &http::response::Parts {
+        self.stream.get_parts()
+    }
+
+    /// Refills `chunk` from the stream when it is used up. Ready with an empty
+    /// `chunk` only at the end of the body.
+    fn poll_fill_chunk(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.chunk.is_empty() && !self.done {
+            match Pin::new(&mut self.stream).poll_next(cx) {
+                Poll::Ready(Some(Ok(chunk))) => self.chunk = chunk,
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Err(into_io_error(err))),
+                Poll::Ready(None) => self.done = true,
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+fn into_io_error(err: FlUrlError) -> io::Error {
+    let kind = if err.is_timeout() {
+        io::ErrorKind::TimedOut
+    } else {
+        io::ErrorKind::Other
+    };
+
+    io::Error::new(kind, err)
+}
+
+impl AsyncRead for FlResponseAsyncRead {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        match this.poll_fill_chunk(cx) {
+            Poll::Ready(Ok(())) => {}
+            other => return other,
+        }
+
+        let len = this.chunk.len().min(buf.remaining());
+        buf.put_slice(&this.chunk[..len]);
+        this.chunk.advance(len);
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncBufRead for FlResponseAsyncRead {
+    fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+
+        match this.poll_fill_chunk(cx) {
+            Poll::Ready(Ok(())) => Poll::Ready(Ok(&this.chunk[..])),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        self.get_mut().chunk.advance(amt);
+    }
+}
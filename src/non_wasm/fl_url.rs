@@ -12,7 +12,6 @@ use my_tls::tokio_rustls::client::TlsStream;
 use rust_extensions::remote_endpoint::Scheme;
 use rust_extensions::StrOrString;
 
-use std::io::Write;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpStream;
@@ -22,6 +21,8 @@ use crate::body::HttpRequestBody;
 use crate::non_wasm::compiled_http_request::{CompiledHttpRequest, RequestToExecute};
 use crate::non_wasm::http_connectors::*;
 use crate::non_wasm::model_body_stream::ModelBodyStream;
+#[cfg(feature = "_tls")]
+use crate::non_wasm::my_http_client_wrapper::MyHttpClientWrapper;
 
 use crate::non_wasm::http_clients_cache::*;
 
@@ -38,6 +39,13 @@ pub enum FlUrlMode {
     H2,
     Http1NoHyper,
     Http1Hyper,
+    /// Lets the server choose: an `https://` request offers `h2, http/1.1` via ALPN
+    /// and becomes [`Self::H2`] or [`Self::Http1Hyper`] depending on the answer. The
+    /// answer is cached per host in the connection cache, so only the first request
+    /// to a host pays for the extra handshake. Cleartext and ssh targets have no
+    /// ALPN and resolve to [`Self::Http1Hyper`] — h2 without TLS needs prior
+    /// knowledge, so it is only ever used when asked for explicitly.
+    Auto,
 }
 
 impl FlUrlMode {
@@ -71,6 +79,20 @@ pub struct FlUrl {
     pub headers: FlUrlHeaders,
     #[cfg(feature = "_tls")]
     pub client_cert: Option<my_tls::ClientCertificate>,
+    #[cfg(feature = "_tls")]
+    client_identity: Option<crate::non_wasm::tls::TlsClientIdentitySource>,
+    #[cfg(feature = "_tls")]
+    root_certificates: Option<Arc<crate::TlsRootCertificates>>,
+    #[cfg(feature = "_tls")]
+    certificate_pins: Option<Arc<crate::TlsCertificatePins>>,
+    #[cfg(feature = "_tls")]
+    server_certificate_verifier: Option<Arc<dyn crate::TlsServerCertificateVerifier>>,
+    #[cfg(feature = "_tls")]
+    tls_server_name: Option<String>,
+    #[cfg(feature = "_tls")]
+    tls_policy: Option<Arc<crate::TlsPolicy>>,
+    #[cfg(feature = "_tls")]
+    tls_key_log: Option<Arc<crate::TlsKeyLogFile>>,
     pub accept_invalid_certificate: bool,
     // If we are trying to reuse connection, but it was not used for this time, we will drop it
     pub not_used_connection_timeout: Duration,
@@ -80,11 +102,24 @@ pub struct FlUrl {
     pub do_not_reuse_connection: bool,
     pub connections_cache: Option<Arc<FlUrlHttpConnectionsCache>>,
     pub compress_body: bool,
+    // How `compress_body` compresses: gzip by default, see `compress_with`.
+    compression: crate::CompressionOptions,
+    /// Set by `accept_gzip` / `accept_compression`; despite the name it covers
+    /// every [`crate::Encoding`].
     pub decompress_gzip_response: bool,
     pub print_input_request: bool,
     // If we reuse connection and it has not been used more seconds than this parameter - it disposed
     pub reuse_connection_timeout_sec: i64,
     mode: FlUrlMode,
+    // Set when `FlUrlMode::Auto` was resolved through ALPN: the cached answer to
+    // forget if the connection it picked fails.
+    #[cfg(feature = "_tls")]
+    auto_alpn_key: Option<String>,
+    // Set with `do_not_reuse_connection` when the ALPN probe of `FlUrlMode::Auto`
+    // opened a connection: the request's first attempt goes out on it.
+    #[cfg(feature = "_tls")]
+    probed_connection: Option<Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>>>,
+    h2_keep_alive: Option<crate::H2KeepAlive>,
     #[cfg(all(unix, feature = "with-ssh"))]
     ssh_credentials: Option<my_ssh::SshCredentials>,
     #[cfg(all(unix, feature = "with-ssh"))]
@@ -92,6 +127,12 @@ pub struct FlUrl {
         Option<Arc<dyn my_ssh::ssh_settings::SshSecurityCredentialsResolver + Send + Sync>>,
 
     max_retries: usize,
+    // The ALPN probe's failed tries: they are attempts of `max_retries` too.
+    failed_probe_attempts: usize,
+    // Set by `on_upload_progress`; reported to by streamed request bodies.
+    upload_progress: Option<crate::non_wasm::progress::ProgressCallback>,
+    // Set by `set_bandwidth_limit`; else the cache's applies, if it has one.
+    bandwidth_limit: Option<Arc<crate::non_wasm::bandwidth_limit::BandwidthLimit>>,
 }
 
 impl FlUrl {
@@ -147,23 +188,46 @@ impl FlUrl {
             headers: FlUrlHeaders::new(),
             #[cfg(feature = "_tls")]
             client_cert: Default::default(),
+            #[cfg(feature = "_tls")]
+            client_identity: None,
+            #[cfg(feature = "_tls")]
+            root_certificates: None,
+            #[cfg(feature = "_tls")]
+            certificate_pins: None,
+            #[cfg(feature = "_tls")]
+            server_certificate_verifier: None,
+            #[cfg(feature = "_tls")]
+            tls_server_name: None,
+            #[cfg(feature = "_tls")]
+            tls_policy: None,
+            #[cfg(feature = "_tls")]
+            tls_key_log: None,
             url_builder: url,
             accept_invalid_certificate: false,
             do_not_reuse_connection: false,
             connections_cache: Default::default(),
             not_used_connection_timeout: Duration::from_secs(30),
             max_retries: 0,
+            failed_probe_attempts: 0,
             request_timeout: Duration::from_secs(10),
             response_body_timeout: None,
             print_input_request: false,
             compress_body: false,
+            compression: Default::default(),
             decompress_gzip_response: false,
             #[cfg(all(unix, feature = "with-ssh"))]
             ssh_credentials: credentials,
             #[cfg(all(unix, feature = "with-ssh"))]
             ssh_security_credentials_resolver: None,
             mode: Default::default(),
+            #[cfg(feature = "_tls")]
+            auto_alpn_key: None,
+            #[cfg(feature = "_tls")]
+            probed_connection: None,
+            h2_keep_alive: None,
             reuse_connection_timeout_sec: 120,
+            upload_progress: None,
+            bandwidth_limit: None,
         };
 
         Ok(result)
@@ -174,18 +238,53 @@ impl FlUrl {
         self.ssh_credentials.is_some()
     }
 
-    pub fn compress(mut self) -> Self {
+    /// Gzips the request body when it is 64 bytes or longer — a streamed body
+    /// whatever its size. Same as `compress_with(CompressionOptions::default())`.
+    pub fn compress(self) -> Self {
+        self.compress_with(crate::CompressionOptions::default())
+    }
+
+    /// Compresses the request body with the algorithm, level and size threshold
+    /// of `options`, and sets `Content-Encoding` to match unless the header is
+    /// already there. Applies to buffered and streamed bodies alike; an invalid
+    /// level fails the request with [`FlUrlError::CompressionError`].
+    ///
+    /// ```
+    /// use flurl::{CompressionOptions, Encoding, FlUrl};
+    ///
+    /// let fl_url = FlUrl::new("https://api.example.com").compress_with(CompressionOptions {
+    ///     algorithm: Encoding::Zstd,
+    ///     level: None,
+    ///     min_size: 1024,
+    /// });
+    /// ```
+    pub fn compress_with(mut self, options: crate::CompressionOptions) -> Self {
         self.compress_body = true;
+        self.compression = options;
         self
     }
 
     /// Advertises gzip support to the server (`Accept-Encoding: gzip`) and
-    /// transparently decompresses a gzip-encoded response body on buffered
-    /// reads (`get_body_as_slice`, `get_json`, `get_body_as_str`, `receive_body`).
-    /// Streamed bodies (`get_body_as_stream`) are NOT decompressed.
-    pub fn accept_gzip(mut self) -> Self {
-        if !self.headers.has_header("Accept-Encoding") {
-            self.headers.add("Accept-Encoding", "gzip");
+    /// transparently decompresses a gzip-encoded response body, whichever way it
+    /// is read — see [`Self::accept_compression`].
+    pub fn accept_gzip(self) -> Self {
+        self.accept_compression(&[crate::Encoding::Gzip])
+    }
+
+    /// Like [`Self::accept_gzip`], for several codings: advertises `encodings`
+    /// in `Accept-Encoding` (in the given order, unless the header is already
+    /// set) and decodes a response body in any [`crate::Encoding`], fixing
+    /// `Content-Encoding` / `Content-Length` to describe the decoded body.
+    /// Buffered reads (`get_body_as_slice`, `get_json`, `get_body_as_str`,
+    /// `receive_body`) decode the whole body at once; `get_body_as_stream` and
+    /// `into_hyper_response` decode it chunk by chunk as it arrives, and drop
+    /// `Content-Length` since the decoded length is not known up front.
+    pub fn accept_compression(mut self, encodings: &[crate::Encoding]) -> Self {
+        if !encodings.is_empty() && !self.headers.has_header("Accept-Encoding") {
+            self.headers.add(
+                "Accept-Encoding",
+                crate::Encoding::to_accept_encoding(encodings).as_str(),
+            );
         }
         self.decompress_gzip_response = true;
         self
@@ -200,6 +299,7 @@ impl FlUrl {
         self
     }
 
+    /// Picks the HTTP client. [`FlUrlMode::Auto`] lets the server choose via ALPN.
     pub fn update_mode(mut self, mode: FlUrlMode) -> Self {
         self.mode = mode;
         self
@@ -210,6 +310,16 @@ impl FlUrl {
         self
     }
 
+    /// Sends an H2 PING every `interval` and treats the connection as dead when the
+    /// ack does not arrive within `ack_timeout`. A dead connection is evicted from
+    /// the pool before a request can land on it, instead of being discovered by
+    /// requests timing out one after another. Applies to [`FlUrlMode::H2`] only;
+    /// overrides the default set with `FlUrlHttpConnectionsCache::set_h2_keep_alive`.
+    pub fn set_h2_keep_alive(mut self, interval: Duration, ack_timeout: Duration) -> Self {
+        self.h2_keep_alive = Some(crate::H2KeepAlive::new(interval, ack_timeout));
+        self
+    }
+
     /// Retries the request up to `max_retries` extra times on failure. Only
     /// IDEMPOTENT methods are replayed (a POST that may have reached the server
     /// is never re-sent). Note that my-http-client performs its own internal
@@ -322,22 +432,161 @@ impl FlUrl {
         self
     }
 
+    /// Holds this request to `bytes_per_second` — a streamed request body as it
+    /// is sent, the response body as it is read, buffered or streamed — with a
+    /// token bucket per direction. A transfer may run one second's worth of
+    /// bytes ahead before it is slowed down. Overrides the limit of the
+    /// connections cache (`FlUrlHttpConnectionsCache::set_bandwidth_limit`);
+    /// the requests `download_parallel` and `download_resumable` make for it
+    /// share this one limit.
+    ///
+    /// Reading the body takes as long as the limit makes it:
+    /// [`Self::set_response_body_timeout`] bounds a buffered read as a whole, so
+    /// it has to leave room for `size / bytes_per_second`. A buffered request
+    /// body (`post`, `put`, …) is written in one piece and is not held back.
+    pub fn set_bandwidth_limit(mut self, bytes_per_second: u64) -> Self {
+        self.bandwidth_limit = Some(Arc::new(
+            crate::non_wasm::bandwidth_limit::BandwidthLimit::new(bytes_per_second),
+        ));
+        self
+    }
+
+    /// The request's own limit, else the connections cache's — which a
+    /// `do_not_reuse_connection` request does not get: it is not pooled traffic.
+    fn get_bandwidth_limit(&self) -> Option<Arc<crate::non_wasm::bandwidth_limit::BandwidthLimit>> {
+        if self.bandwidth_limit.is_some() {
+            return self.bandwidth_limit.clone();
+        }
+
+        if self.do_not_reuse_connection {
+            return None;
+        }
+
+        self.get_connections_cache().get_bandwidth_limit()
+    }
+
+    /// Calls `callback` as a streamed request body goes out — `put_file`,
+    /// `post_request_streamed` and the rest of the `execute_streamed` family —
+    /// with the bytes sent so far and the total, when it is known: the
+    /// `content_length` of the call, or the exact size hint of the body.
+    ///
+    /// The count is of the body as handed in, before [`Self::compress`]: the
+    /// total stays meaningful when the compressed length is not known. A
+    /// buffered body (`post`, `put`, …) is written in one piece and does not
+    /// report. The callback runs on the task that drives the request, once per
+    /// chunk — keep it short, and hand heavier work (redrawing a UI) elsewhere.
+    pub fn on_upload_progress(
+        mut self,
+        callback: impl Fn(u64, Option<u64>) + Send + Sync + 'static,
+    ) -> Self {
+        self.upload_progress = Some(Arc::new(callback));
+        self
+    }
+
     /// Only available with a TLS provider feature (`with-ring-tls` or
     /// `with-rust-tls`) — without one the crate does not link a TLS stack at all,
-    /// so there is no certificate type to pass in.
+    /// so there is no certificate type to pass in. Replaces a client certificate
+    /// or identity set before; has no effect on plain `http://` requests.
     #[cfg(feature = "_tls")]
     pub fn with_client_certificate(mut self, certificate: my_tls::ClientCertificate) -> Self {
-        if self.client_cert.is_some() {
-            panic!("Client certificate is already set");
-        }
-        if !self.url_builder.get_scheme().is_https() {
-            panic!("Client certificate can only be used with https");
-        }
-
+        self.client_identity = None;
         self.client_cert = Some(certificate);
         self
     }
 
+    /// A client certificate loaded with [`crate::TlsClientIdentity`] — from PEM
+    /// files or a password-protected PKCS#12 — instead of a pre-built
+    /// `my_tls::ClientCertificate`. Like `with_client_certificate`, the last of
+    /// the client certificate calls wins.
+    #[cfg(feature = "_tls")]
+    pub fn with_client_identity(mut self, identity: crate::TlsClientIdentity) -> Self {
+        self.client_cert = None;
+        self.client_identity = Some(crate::non_wasm::tls::TlsClientIdentitySource::Fixed(
+            Arc::new(identity),
+        ));
+        self
+    }
+
+    /// Like [`Self::with_client_identity`], but the certificate is re-read when
+    /// its files rotate: connections opened after a rotation present the new
+    /// certificate, the ones opened before drain out of the pool.
+    #[cfg(feature = "_tls")]
+    pub fn with_reloadable_client_identity(
+        mut self,
+        identity: crate::ReloadableTlsClientIdentity,
+    ) -> Self {
+        self.client_cert = None;
+        self.client_identity = Some(crate::non_wasm::tls::TlsClientIdentitySource::Reloadable(
+            identity,
+        ));
+        self
+    }
+
+    /// Verifies the server certificate against these trust anchors instead of,
+    /// or on top of, the default roots — see [`crate::TlsTrustMode`]. This is the
+    /// way to talk to a service behind a private CA without resorting to
+    /// `accept_invalid_certificate`. Overrides the default set with
+    /// `FlUrlHttpConnectionsCache::set_root_certificates`; has no effect on plain
+    /// `http://` requests.
+    #[cfg(feature = "_tls")]
+    pub fn with_root_certificates(mut self, root_certificates: crate::TlsRootCertificates) -> Self {
+        self.root_certificates = Some(Arc::new(root_certificates));
+        self
+    }
+
+    /// Accepts the server only if the certificate chain it presents contains a
+    /// public key from `pins`, in addition to the regular verification. A mismatch
+    /// fails with [`FlUrlError::TlsCertificatePinMismatch`]. Overrides the pins
+    /// set for the host with `FlUrlHttpConnectionsCache::set_certificate_pins`.
+    #[cfg(feature = "_tls")]
+    pub fn with_certificate_pins(mut self, pins: crate::TlsCertificatePins) -> Self {
+        self.certificate_pins = Some(Arc::new(pins));
+        self
+    }
+
+    /// Lets `verifier` make the final call on the server certificate, after the
+    /// standard verification ran and with its outcome at hand — see
+    /// [`crate::TlsServerCertificateVerifier`]. Connections are pooled per
+    /// verifier `Arc`: pass clones of one `Arc` for requests to share them.
+    #[cfg(feature = "_tls")]
+    pub fn with_server_certificate_verifier(
+        mut self,
+        verifier: Arc<dyn crate::TlsServerCertificateVerifier>,
+    ) -> Self {
+        self.server_certificate_verifier = Some(verifier);
+        self
+    }
+
+    /// The name sent in SNI and checked against the server certificate, instead
+    /// of the one taken from the `Host` header or the url. The `Host` header is
+    /// left alone, so the url can point at an IP or an internal alias while the
+    /// certificate is still verified for its real name.
+    #[cfg(feature = "_tls")]
+    pub fn with_tls_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.tls_server_name = Some(server_name.into());
+        self
+    }
+
+    /// Restricts the TLS versions and cipher suites the handshake may negotiate
+    /// — see [`crate::TlsPolicy`]. An unknown suite or an empty range fails the
+    /// request with [`FlUrlError::InvalidTlsConfiguration`]. Overrides the default
+    /// set with `FlUrlHttpConnectionsCache::set_tls_policy`.
+    #[cfg(feature = "_tls")]
+    pub fn with_tls_policy(mut self, tls_policy: crate::TlsPolicy) -> Self {
+        self.tls_policy = Some(Arc::new(tls_policy));
+        self
+    }
+
+    /// Writes the TLS secrets of this request's connection to `key_log` in the
+    /// NSS key-log format, for decrypting a capture in Wireshark — see
+    /// [`crate::TlsKeyLogFile`]. Debugging only. Overrides the default set with
+    /// `FlUrlHttpConnectionsCache::set_tls_key_log`.
+    #[cfg(feature = "_tls")]
+    pub fn with_tls_key_log(mut self, key_log: crate::TlsKeyLogFile) -> Self {
+        self.tls_key_log = Some(Arc::new(key_log));
+        self
+    }
+
     /// Without a TLS provider feature this is inert: the request never reaches a
     /// TLS handshake because `https://` panics at execute time. It also needs
     /// `dangerous-tls` to have any effect at all — see that feature's docs.
@@ -478,8 +727,8 @@ impl FlUrl {
     /// The framing comes from the stream itself — the `content_length` given to
     /// `HttpBodyAsStream::create` becomes `Content-Length`, and `None` goes out
     /// chunked. Everything else that applies to a streamed body applies here too
-    /// (see [`Self::execute_streamed`]): no `compress()`, no retries, the timeout
-    /// covers the whole upload.
+    /// (see [`Self::execute_streamed`]): `compress()` compresses it as it streams,
+    /// there are no retries, and the timeout covers the whole upload.
     async fn execute_model_stream(
         self,
         verb: HttpVerb,
@@ -573,14 +822,21 @@ impl FlUrl {
             #[cfg(feature = "_tls")]
             Scheme::Https => {
                 if self.do_not_reuse_connection {
-                    self.execute_with_retry::<TlsStream<TcpStream>, HttpsConnector>(
-                        request,
-                        Arc::new(crate::non_wasm::http_clients_cache::creators::HttpsConnectionCreator),
-                        crate::consts::HTTPS_DEFAULT_PORT.into(),
-                        #[cfg(all(unix, feature = "with-ssh"))]
-                        None,
-                    )
-                    .await?
+                    let mut self_mut = self;
+                    let connection_creator =
+                        crate::non_wasm::http_clients_cache::creators::HttpsProbedConnectionCreator::new(
+                            self_mut.probed_connection.take(),
+                        );
+
+                    self_mut
+                        .execute_with_retry::<TlsStream<TcpStream>, HttpsConnector>(
+                            request,
+                            Arc::new(connection_creator),
+                            crate::consts::HTTPS_DEFAULT_PORT.into(),
+                            #[cfg(all(unix, feature = "with-ssh"))]
+                            None,
+                        )
+                        .await?
                 } else {
                     let clients_cache = self.get_connections_cache();
 
@@ -661,6 +917,73 @@ impl FlUrl {
         )
         .await
     }
+    /// The same GET once more, for the next attempt of `download_resumable` or
+    /// the next connection of `get_event_stream`: every setting and header of this
+    /// request, minus what the attempt decides itself — its `Range` / `If-Range`
+    /// or `Last-Event-ID`, and content coding, since a byte range counts the
+    /// bytes as the server stores them.
+    pub(crate) fn fork_for_download(&self) -> Self {
+        let mut headers = FlUrlHeaders::new();
+        for (name, value) in self.headers.iter() {
+            if name.eq_ignore_ascii_case("Accept-Encoding")
+                || name.eq_ignore_ascii_case("Range")
+                || name.eq_ignore_ascii_case("If-Range")
+                || name.eq_ignore_ascii_case("Last-Event-ID")
+            {
+                continue;
+            }
+            headers.add(name, value);
+        }
+
+        Self {
+            url_builder: self.url_builder.clone(),
+            headers,
+            #[cfg(feature = "_tls")]
+            client_cert: self.client_cert.clone(),
+            #[cfg(feature = "_tls")]
+            client_identity: self.client_identity.clone(),
+            #[cfg(feature = "_tls")]
+            root_certificates: self.root_certificates.clone(),
+            #[cfg(feature = "_tls")]
+            certificate_pins: self.certificate_pins.clone(),
+            #[cfg(feature = "_tls")]
+            server_certificate_verifier: self.server_certificate_verifier.clone(),
+            #[cfg(feature = "_tls")]
+            tls_server_name: self.tls_server_name.clone(),
+            #[cfg(feature = "_tls")]
+            tls_policy: self.tls_policy.clone(),
+            #[cfg(feature = "_tls")]
+            tls_key_log: self.tls_key_log.clone(),
+            accept_invalid_certificate: self.accept_invalid_certificate,
+            not_used_connection_timeout: self.not_used_connection_timeout,
+            request_timeout: self.request_timeout,
+            response_body_timeout: self.response_body_timeout,
+            do_not_reuse_connection: self.do_not_reuse_connection,
+            connections_cache: self.connections_cache.clone(),
+            compress_body: false,
+            compression: self.compression,
+            decompress_gzip_response: false,
+            print_input_request: self.print_input_request,
+            reuse_connection_timeout_sec: self.reuse_connection_timeout_sec,
+            mode: self.mode,
+            #[cfg(feature = "_tls")]
+            auto_alpn_key: None,
+            #[cfg(feature = "_tls")]
+            probed_connection: None,
+            h2_keep_alive: self.h2_keep_alive,
+            #[cfg(all(unix, feature = "with-ssh"))]
+            ssh_credentials: self.ssh_credentials.clone(),
+            #[cfg(all(unix, feature = "with-ssh"))]
+            ssh_security_credentials_resolver: self.ssh_security_credentials_resolver.clone(),
+            max_retries: self.max_retries,
+            failed_probe_attempts: 0,
+            // A download sends no body.
+            upload_progress: None,
+            // Shared, not copied: the forks of one download stay within one budget.
+            bandwidth_limit: self.bandwidth_limit.clone(),
+        }
+    }
+
     pub(crate) fn get_connections_cache(&self) -> Arc<FlUrlHttpConnectionsCache> {
         match self.connections_cache.as_ref() {
             Some(cache) => cache.clone(),
@@ -668,22 +991,19 @@ impl FlUrl {
         }
     }
 
-    fn compress_body(&mut self, body: Vec<u8>) -> Vec<u8> {
-        use flate2::{write::GzEncoder, Compression};
-
-        if body.len() < 64 {
-            return body;
+    fn compress_body(&mut self, body: Vec<u8>) -> Result<Vec<u8>, FlUrlError> {
+        if body.len() < self.compression.min_size {
+            return Ok(body);
         }
 
+        let result = self.compression.compress(body.as_slice())?;
+
         if !self.headers.has_header("Content-Encoding") {
-            self.headers.add("Content-Encoding", "gzip");
+            self.headers
+                .add("Content-Encoding", self.compression.algorithm.as_str());
         }
 
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(body.as_slice()).unwrap();
-        let result = encoder.finish().unwrap();
-
-        result
+        Ok(result)
     }
 
     fn get_path_and_query_with_leading_slash(&self) -> String {
@@ -696,6 +1016,126 @@ impl FlUrl {
         path_and_query
     }
 
+    /// `compile_request` for the verbs: an [`FlUrlMode::Auto`] request has to learn
+    /// its real mode first, because h2 and HTTP/1.1 requests are compiled
+    /// differently.
+    async fn prepare_request(
+        &mut self,
+        method: Method,
+        body: HttpRequestBody,
+        debug: Option<&mut String>,
+    ) -> Result<CompiledHttpRequest, FlUrlError> {
+        self.resolve_auto_mode().await?;
+        self.compile_request(method, body, debug)
+    }
+
+    async fn resolve_auto_mode(&mut self) -> Result<(), FlUrlError> {
+        if !matches!(self.mode, FlUrlMode::Auto) {
+            return Ok(());
+        }
+
+        #[cfg(all(unix, feature = "with-ssh"))]
+        if self.ssh_credentials.is_some() {
+            self.mode = FlUrlMode::Http1Hyper;
+            return Ok(());
+        }
+
+        #[cfg(feature = "_tls")]
+        if self.url_builder.get_scheme().is_https() {
+            self.mode = self.negotiate_https_mode().await?;
+            return Ok(());
+        }
+
+        self.mode = FlUrlMode::Http1Hyper;
+        Ok(())
+    }
+
+    /// Looks the host up in the connection cache and probes it when the answer is
+    /// not known yet. The cache is consulted even with `do_not_reuse_connection`:
+    /// it is the protocol that gets remembered there, not a connection. The probed
+    /// connection is kept for the request itself — pooled, or with
+    /// `do_not_reuse_connection`, held for its first attempt.
+    #[cfg(feature = "_tls")]
+    async fn negotiate_https_mode(&mut self) -> Result<FlUrlMode, FlUrlError> {
+        let cache = self.get_connections_cache();
+
+        let params = self
+            .get_connection_params(
+                crate::consts::HTTPS_DEFAULT_PORT.into(),
+                #[cfg(all(unix, feature = "with-ssh"))]
+                None,
+            )
+            .await;
+
+        let key = utils::get_alpn_key(&params);
+
+        if let Some(mode) = cache.get_alpn_mode(&key) {
+            self.auto_alpn_key = Some(key);
+            return Ok(mode);
+        }
+
+        let mut connector = creators::HttpsConnectionCreator::create_connector(&params);
+        let (h2, failed_attempts) = self.probe_h2_support(&mut connector).await?;
+
+        let mode = if h2 {
+            FlUrlMode::H2
+        } else {
+            FlUrlMode::Http1Hyper
+        };
+        cache.set_alpn_mode(key.clone(), mode);
+
+        let params = ConnectionParams { mode, ..params };
+        let connection = creators::HttpsConnectionCreator::create_connection_with_connector(
+            &params,
+            utils::get_https_connection_key(&params),
+            connector,
+        );
+
+        if self.do_not_reuse_connection {
+            self.probed_connection = Some(connection);
+        } else {
+            cache.add_probed_https_connection(connection);
+        }
+
+        self.failed_probe_attempts = failed_attempts;
+        self.auto_alpn_key = Some(key);
+        Ok(mode)
+    }
+
+    /// The ALPN probe, held to what the request is: every try is bound by
+    /// `set_timeout`, and a failed one takes an attempt of `with_retries`. Returns
+    /// whether the server picked `h2`, and the tries that failed before.
+    #[cfg(feature = "_tls")]
+    async fn probe_h2_support(
+        &self,
+        connector: &mut HttpsConnector,
+    ) -> Result<(bool, usize), FlUrlError> {
+        let mut attempt_no = 0;
+
+        loop {
+            let probe = connector.probe_h2_support();
+            let result = match tokio::time::timeout(self.request_timeout, probe).await {
+                Ok(result) => result,
+                Err(_) => Err(my_http_client::MyHttpClientError::RequestTimeout(
+                    self.request_timeout,
+                )),
+            };
+
+            match result {
+                Ok(h2) => return Ok((h2, attempt_no)),
+                Err(err) => {
+                    // Nothing of the request is on the wire yet, so any method may
+                    // retry — unless the error is one that repeats.
+                    if error_repeats_on_retry(&err) || attempt_no >= self.max_retries {
+                        return Err(map_my_http_client_error(err));
+                    }
+
+                    attempt_no += 1;
+                }
+            }
+        }
+    }
+
     fn compile_request(
         &mut self,
         method: Method,
@@ -711,7 +1151,9 @@ impl FlUrl {
                 self.compile_non_hyper_request(method.clone(), body, debug)?,
                 method,
             ),
-            FlUrlMode::Http1Hyper => CompiledHttpRequest::new_hyper(
+            // `Auto` is resolved by `prepare_request`; an unresolved one compiles
+            // the way the default mode does.
+            FlUrlMode::Http1Hyper | FlUrlMode::Auto => CompiledHttpRequest::new_hyper(
                 self.compile_hyper_request(method.clone(), body, debug)?,
                 method,
             ),
@@ -739,7 +1181,7 @@ impl FlUrl {
         }
 
         if self.compress_body {
-            body = self.compress_body(body);
+            body = self.compress_body(body)?;
         }
 
         let path_and_query = self.get_path_and_query_with_leading_slash();
@@ -842,7 +1284,7 @@ impl FlUrl {
         }
 
         if self.compress_body {
-            body = self.compress_body(body);
+            body = self.compress_body(body)?;
         }
 
         let path_and_query = self.get_path_and_query_with_leading_slash();
@@ -871,7 +1313,9 @@ impl FlUrl {
     }
 
     pub async fn get(mut self) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(Method::GET, HttpRequestBody::Empty, None)?;
+        let request = self
+            .prepare_request(Method::GET, HttpRequestBody::Empty, None)
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -879,13 +1323,131 @@ impl FlUrl {
         mut self,
         request_debug_string: &mut String,
     ) -> Result<FlUrlResponse, FlUrlError> {
-        let request =
-            self.compile_request(Method::GET, HttpRequestBody::Empty, Some(request_debug_string))?;
+        let request = self
+            .prepare_request(
+                Method::GET,
+                HttpRequestBody::Empty,
+                Some(request_debug_string),
+            )
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
+    /// GETs the url into the file at `path`, picking up where an earlier attempt
+    /// left off. See [`Self::download_resumable_with`].
+    pub async fn download_resumable(
+        self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<crate::SavedFile, FlUrlError> {
+        self.download_resumable_with(path, crate::ResumableDownloadOptions::new())
+            .await
+    }
+
+    /// GETs the url into the file at `path`, resuming instead of starting over.
+    ///
+    /// The body is written to `<path>.part`, next to the ETag (or, without a strong
+    /// one, the Last-Modified) it came with. When a `.part` is there — from an
+    /// attempt that failed, or from an earlier run of the process — the request
+    /// carries `Range: bytes=<its length>-` and `If-Range` on that validator: a
+    /// `206` is appended to it, a `200` (the file changed on the server, or it does
+    /// not do ranges) starts it over. Once the body is complete the `.part` is
+    /// renamed to `path`.
+    ///
+    /// A transport failure — a connection that breaks off mid-body, a timeout —
+    /// is retried after a backoff, from the bytes already on disk, up to
+    /// `max_retries` times in a row; an attempt that got further than the last one
+    /// resets the count. A status other than `200` / `206` fails with
+    /// `FlUrlError::UnexpectedStatusCode` and leaves the `.part` for next time.
+    ///
+    /// `accept_compression` does not apply: the ranges count the bytes as the
+    /// server stores them.
+    pub async fn download_resumable_with(
+        self,
+        path: impl AsRef<std::path::Path>,
+        options: crate::ResumableDownloadOptions,
+    ) -> Result<crate::SavedFile, FlUrlError> {
+        crate::non_wasm::file_download::download_resumable(self, path.as_ref(), options).await
+    }
+
+    /// GETs the url into the file at `path` as `segment_size` ranges, `segments`
+    /// of them at a time. See [`Self::download_parallel_with`].
+    pub async fn download_parallel(
+        self,
+        path: impl AsRef<std::path::Path>,
+        segments: usize,
+        segment_size: u64,
+    ) -> Result<crate::SavedFile, FlUrlError> {
+        self.download_parallel_with(
+            path,
+            segments,
+            segment_size,
+            crate::SaveToFileOptions::new(),
+        )
+        .await
+    }
+
+    /// GETs the url into the file at `path` as several ranges fetched at once.
+    ///
+    /// A `HEAD` probes the size and `Accept-Ranges` first. The file is then cut
+    /// into `segment_size` ranges, and `segments` of them are in flight at a time
+    /// — each on a connection of its own from the pool for HTTP/1.1, as streams
+    /// of the one connection for HTTP/2. Each range is written at its offset in a
+    /// temp file the size of the whole, renamed to `path` once every range is in
+    /// and the bytes add up to the length the `HEAD` announced.
+    ///
+    /// The ranges carry `If-Range` on the ETag (or Last-Modified) of the `HEAD`:
+    /// a file that changes on the server mid-way fails the download with
+    /// `FlUrlError::DownloadMismatch` rather than stitching two versions together,
+    /// as does a range that comes back shorter, longer or elsewhere than asked.
+    /// A server that does not answer the `HEAD` with `200`, or does not announce
+    /// a length or `Accept-Ranges: bytes`, and a file no bigger than one segment
+    /// are downloaded with a single `GET`, as `save_to_file_with` would.
+    ///
+    /// Nothing is retried — a failed range fails the download, aborts the others
+    /// and leaves `path` as it was. With `sha256` set the file is hashed once
+    /// complete, since the ranges land out of order.
+    pub async fn download_parallel_with(
+        self,
+        path: impl AsRef<std::path::Path>,
+        segments: usize,
+        segment_size: u64,
+        options: crate::SaveToFileOptions,
+    ) -> Result<crate::SavedFile, FlUrlError> {
+        crate::non_wasm::parallel_download::download_parallel(
+            self,
+            path.as_ref(),
+            segments,
+            segment_size,
+            options,
+        )
+        .await
+    }
+
+    /// Reads the url as a Server-Sent Events stream with the default
+    /// reconnection. See [`Self::get_event_stream_with`].
+    pub fn get_event_stream(self) -> crate::FlUrlEventStream {
+        self.get_event_stream_with(crate::EventStreamOptions::new())
+    }
+
+    /// Reads the url as a Server-Sent Events stream: a GET with `Accept:
+    /// text/event-stream` whose body is parsed into [`crate::SseEvent`]s as it
+    /// arrives, made again with `Last-Event-ID` whenever the connection ends.
+    ///
+    /// Nothing is sent until the first `next_event`. Every setting and header
+    /// of the request applies to each connection, except `accept_compression`:
+    /// events are read as they come off the wire. A `Last-Event-ID` header set
+    /// here resumes the stream the same way `options.set_last_event_id` does.
+    pub fn get_event_stream_with(
+        self,
+        options: crate::EventStreamOptions,
+    ) -> crate::FlUrlEventStream {
+        crate::FlUrlEventStream::new(self, options)
+    }
+
     pub async fn head(mut self) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(Method::HEAD, HttpRequestBody::Empty, None)?;
+        let request = self
+            .prepare_request(Method::HEAD, HttpRequestBody::Empty, None)
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -893,16 +1455,20 @@ impl FlUrl {
         mut self,
         request_debug_string: &mut String,
     ) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(
-            Method::HEAD,
-            HttpRequestBody::Empty,
-            Some(request_debug_string),
-        )?;
+        let request = self
+            .prepare_request(
+                Method::HEAD,
+                HttpRequestBody::Empty,
+                Some(request_debug_string),
+            )
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
     pub async fn post(mut self, body: impl Into<HttpRequestBody>) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(Method::POST, body.into(), None)?;
+        let request = self
+            .prepare_request(Method::POST, body.into(), None)
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -913,7 +1479,9 @@ impl FlUrl {
     ) -> Result<FlUrlResponse, FlUrlError> {
         let body = body.into();
 
-        let request = self.compile_request(Method::POST, body, Some(request_debug_string))?;
+        let request = self
+            .prepare_request(Method::POST, body, Some(request_debug_string))
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -1053,6 +1621,106 @@ impl FlUrl {
             .await
     }
 
+    /// PUTs the file at `path` as a streamed body. See [`Self::post_file`].
+    pub async fn put_file(
+        self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<FlUrlResponse, FlUrlError> {
+        self.execute_file(Method::PUT, path.as_ref()).await
+    }
+
+    /// POSTs the file at `path` as a streamed body, at constant memory.
+    ///
+    /// The `Content-Length` is the length of the open file, taken from its metadata
+    /// — the length and the bytes come from one source, as
+    /// [`Self::execute_streamed`] requires. A file that grows while it is being
+    /// sent is cut at that length; one that shrinks fails the request instead of
+    /// sending less than announced.
+    ///
+    /// The `Content-Type` is guessed from the extension — `application/json` for
+    /// `.json`, `application/gzip` for `.tar.gz`, … — unless one was set with
+    /// [`Self::with_header`]; a file with an extension it does not know goes out
+    /// without one. Everything else is as for any streamed body: attempted once,
+    /// with [`Self::set_timeout`] covering the whole upload, and sent chunked when
+    /// [`Self::compress`] is on.
+    pub async fn post_file(
+        self,
+        path: impl AsRef<std::path::Path>,
+    ) -> Result<FlUrlResponse, FlUrlError> {
+        self.execute_file(Method::POST, path.as_ref()).await
+    }
+
+    /// POSTs a [`crate::FormDataStream`] — text fields next to files and readers of
+    /// any size — as a streamed `multipart/form-data` body, at constant memory.
+    ///
+    /// It goes out with a `Content-Length` when the size of every part is known,
+    /// chunked when a reader was given none. The `Content-Type` carries the form's
+    /// boundary; one set with [`Self::with_header`] is kept, and then has to name
+    /// that same boundary. Everything else is as for any streamed body (see
+    /// [`Self::execute_streamed`]): attempted once, with [`Self::set_timeout`]
+    /// covering the whole upload.
+    pub async fn post_form_data_streamed(
+        self,
+        form: crate::FormDataStream,
+    ) -> Result<FlUrlResponse, FlUrlError> {
+        self.execute_form_data_streamed(Method::POST, form).await
+    }
+
+    /// PUTs a [`crate::FormDataStream`]. See [`Self::post_form_data_streamed`].
+    pub async fn put_form_data_streamed(
+        self,
+        form: crate::FormDataStream,
+    ) -> Result<FlUrlResponse, FlUrlError> {
+        self.execute_form_data_streamed(Method::PUT, form).await
+    }
+
+    async fn execute_form_data_streamed(
+        mut self,
+        method: Method,
+        form: crate::FormDataStream,
+    ) -> Result<FlUrlResponse, FlUrlError> {
+        let content_length = match form.get_content_length() {
+            Some(len) => Some(usize::try_from(len).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "The form is too large to send from this platform",
+                )
+            })?),
+            None => None,
+        };
+
+        if !self.headers.has_header("Content-Type") {
+            self.headers.add("Content-Type", &form.get_content_type());
+        }
+
+        self.execute_streamed(method, form.into_body(), content_length)
+            .await
+    }
+
+    async fn execute_file(
+        mut self,
+        method: Method,
+        path: &std::path::Path,
+    ) -> Result<FlUrlResponse, FlUrlError> {
+        let body = crate::non_wasm::file_body::FileBody::open(path).await?;
+
+        let content_length = usize::try_from(body.get_len()).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("{} is too large to send from this platform", path.display()),
+            )
+        })?;
+
+        if !self.headers.has_header("Content-Type") {
+            if let Some(content_type) = crate::non_wasm::file_body::guess_content_type(path) {
+                self.headers.add("Content-Type", content_type);
+            }
+        }
+
+        self.execute_streamed(method, body, Some(content_length))
+            .await
+    }
+
     /// Sends `body` as a stream under `method` — a [`my_http_client::RequestBodyStream`]
     /// fed by a publisher, a proxied `hyper::body::Incoming`, a `StreamBody` over a
     /// file reader, anything implementing `hyper::body::Body<Data = Bytes>`. Peak
@@ -1088,10 +1756,13 @@ impl FlUrl {
     /// violation), and `None` removes it, because a body of unknown size must not
     /// claim a length it may not deliver.
     ///
-    /// Three builder knobs do not apply, and none of them fails quietly:
+    /// [`Self::compress`] / [`Self::compress_with`] compress the body as it streams,
+    /// chunk by chunk, at the same constant memory. The compressed length is not
+    /// known up front, so a compressed body always goes out chunked and
+    /// `content_length` is ignored.
+    ///
+    /// Two builder knobs do not apply, and neither of them fails quietly:
     ///
-    /// * [`Self::compress`] gzips the body as one buffer, which is exactly what
-    ///   streaming avoids → [`FlUrlError::StreamedBodyCanNotBeCompressed`].
     /// * [`Self::with_retries`] is ignored: the payload is consumed as it is sent, so
     ///   the request is attempted exactly once. Rebuilding the stream and calling
     ///   again is the caller's decision — it owns the source data.
@@ -1144,19 +1815,62 @@ impl FlUrl {
         TBody: hyper::body::Body<Data = Bytes> + Send + Sync + 'static,
         TBody::Error: std::fmt::Display,
     {
-        if self.compress_body {
-            return Err(FlUrlError::StreamedBodyCanNotBeCompressed);
+        self.mode = FlUrlMode::Http1Hyper;
+
+        let body = match self.upload_progress.take() {
+            Some(callback) => {
+                let total = match content_length {
+                    Some(content_length) => Some(content_length as u64),
+                    None => hyper::body::Body::size_hint(&body).exact(),
+                };
+
+                let counter = crate::non_wasm::progress::ProgressCounter::new(callback, total);
+                http_body_util::BodyExt::boxed(crate::non_wasm::progress::ProgressBody::new(
+                    body, counter,
+                ))
+            }
+            None => http_body_util::BodyExt::boxed(body),
+        };
+
+        // Held back as it goes on the wire: after compression, when there is any.
+        let throttle = self.get_bandwidth_limit().map(|limit| {
+            crate::non_wasm::bandwidth_limit::Throttle::new(
+                limit,
+                crate::non_wasm::bandwidth_limit::Direction::Upload,
+            )
+        });
+
+        if !self.compress_body {
+            if let Some(debug) = debug {
+                self.compile_debug_info_streamed(debug, method.as_str());
+            }
+
+            let body = crate::non_wasm::bandwidth_limit::ThrottledBody::new(body, throttle);
+            let request = self.compile_streamed_request(method, body)?;
+
+            return self
+                .execute(RequestToExecute::streamed(request, content_length))
+                .await;
         }
 
-        self.mode = FlUrlMode::Http1Hyper;
+        // Compressed on the fly: the encoded length is unknown until the body has
+        // ended, so the request goes out chunked whatever `content_length` says.
+        let encoder = crate::compression::StreamEncoder::new(&self.compression)?;
+
+        if !self.headers.has_header("Content-Encoding") {
+            self.headers
+                .add("Content-Encoding", encoder.get_encoding().as_str());
+        }
 
         if let Some(debug) = debug {
             self.compile_debug_info_streamed(debug, method.as_str());
         }
 
+        let body = crate::non_wasm::encoding_body::EncodingBody::new(body, encoder);
+        let body = crate::non_wasm::bandwidth_limit::ThrottledBody::new(body, throttle);
         let request = self.compile_streamed_request(method, body)?;
 
-        self.execute(RequestToExecute::streamed(request, content_length))
+        self.execute(RequestToExecute::streamed(request, None))
             .await
     }
 
@@ -1164,7 +1878,8 @@ impl FlUrl {
     /// the trait object the connection carries. Mirrors the header work of
     /// `compile_hyper_request`, minus everything that needs the body in hand:
     /// no `Content-Type` derived from the payload (a stream carries none — set it with
-    /// [`Self::with_header`]), no debug dump of the body, no compression.
+    /// [`Self::with_header`]), no debug dump of the body. Compression is the
+    /// caller's: it wraps the body before it gets here.
     fn compile_streamed_request<TBody>(
         &mut self,
         method: Method,
@@ -1226,13 +1941,15 @@ impl FlUrl {
         json: &impl serde::Serialize,
     ) -> Result<FlUrlResponse, FlUrlError> {
         let body = HttpRequestBody::try_as_json(json)?;
-        let request = self.compile_request(Method::POST, body, None)?;
+        let request = self.prepare_request(Method::POST, body, None).await?;
 
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
     pub async fn patch(mut self, body: impl Into<HttpRequestBody>) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(Method::PATCH, body.into(), None)?;
+        let request = self
+            .prepare_request(Method::PATCH, body.into(), None)
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -1241,7 +1958,9 @@ impl FlUrl {
         body: impl Into<HttpRequestBody>,
         request_debug_string: &mut String,
     ) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(Method::PATCH, body.into(), Some(request_debug_string))?;
+        let request = self
+            .prepare_request(Method::PATCH, body.into(), Some(request_debug_string))
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -1251,13 +1970,13 @@ impl FlUrl {
         json: &impl serde::Serialize,
     ) -> Result<FlUrlResponse, FlUrlError> {
         let body = HttpRequestBody::try_as_json(json)?;
-        let request = self.compile_request(Method::PATCH, body, None)?;
+        let request = self.prepare_request(Method::PATCH, body, None).await?;
 
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
     pub async fn put(mut self, body: impl Into<HttpRequestBody>) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(Method::PUT, body.into(), None)?;
+        let request = self.prepare_request(Method::PUT, body.into(), None).await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -1266,7 +1985,9 @@ impl FlUrl {
         body: impl Into<HttpRequestBody>,
         request_debug_string: &mut String,
     ) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(Method::PUT, body.into(), Some(request_debug_string))?;
+        let request = self
+            .prepare_request(Method::PUT, body.into(), Some(request_debug_string))
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -1276,12 +1997,14 @@ impl FlUrl {
         json: &impl serde::Serialize,
     ) -> Result<FlUrlResponse, FlUrlError> {
         let body = HttpRequestBody::try_as_json(json)?;
-        let request = self.compile_request(Method::PUT, body, None)?;
+        let request = self.prepare_request(Method::PUT, body, None).await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
     pub async fn delete(mut self) -> Result<FlUrlResponse, FlUrlError> {
-        let request = self.compile_request(Method::DELETE, HttpRequestBody::Empty, None)?;
+        let request = self
+            .prepare_request(Method::DELETE, HttpRequestBody::Empty, None)
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
 
@@ -1289,8 +2012,13 @@ impl FlUrl {
         mut self,
         request_debug_string: &mut String,
     ) -> Result<FlUrlResponse, FlUrlError> {
-        let request =
-            self.compile_request(Method::DELETE, HttpRequestBody::Empty, Some(request_debug_string))?;
+        let request = self
+            .prepare_request(
+                Method::DELETE,
+                HttpRequestBody::Empty,
+                Some(request_debug_string),
+            )
+            .await?;
         self.execute(RequestToExecute::Compiled(request)).await
     }
     fn compile_debug_info(&self, out: &mut String) {
@@ -1365,17 +2093,43 @@ impl FlUrl {
             None => None,
         };
 
-        ConnectionParams {
+        let params = ConnectionParams {
             mode: self.mode,
             remote_endpoint,
             host_header: self.headers.get_host_header_value(),
             #[cfg(feature = "_tls")]
+            tls_server_name: self.tls_server_name.as_deref(),
+            #[cfg(feature = "_tls")]
             client_certificate: self.client_cert.as_ref(),
+            #[cfg(feature = "_tls")]
+            client_identity: self
+                .client_identity
+                .as_ref()
+                .map(|identity| identity.get_current()),
             accept_invalid_certificate: self.accept_invalid_certificate,
+            #[cfg(feature = "_tls")]
+            root_certificates: self.root_certificates.clone(),
+            #[cfg(feature = "_tls")]
+            certificate_pins: self.certificate_pins.clone(),
+            #[cfg(feature = "_tls")]
+            server_certificate_verifier: self.server_certificate_verifier.clone(),
+            #[cfg(feature = "_tls")]
+            tls_policy: self.tls_policy.clone(),
+            #[cfg(feature = "_tls")]
+            tls_key_log: self.tls_key_log.clone(),
+            #[cfg(feature = "_tls")]
+            tls_client_configs: None,
             #[cfg(all(unix, feature = "with-ssh"))]
             ssh_session,
             reuse_connection_timeout_seconds: self.reuse_connection_timeout_sec,
-        }
+            h2_keep_alive: self.h2_keep_alive,
+        };
+
+        // Here and not in the cache's `get_*_connection`: a connection opened with
+        // `do_not_reuse_connection` never gets there, and it is no less a
+        // connection of this cache — its CA, pins and policy apply, and its TLS
+        // config with the session tickets is shared.
+        self.get_connections_cache().apply_defaults(&params)
     }
 
     async fn execute_with_retry<
@@ -1391,7 +2145,8 @@ impl FlUrl {
         if self.print_input_request {
             request.print_http_headers();
         }
-        let mut attempt_no = 0;
+        // A failed ALPN probe was an attempt too.
+        let mut attempt_no = self.failed_probe_attempts;
         // A streamed body is consumed as it is sent, so there is nothing left to
         // replay — `with_retries` does not apply to it, whatever it was set to.
         let max_retries = if request.is_streamed() {
@@ -1400,6 +2155,7 @@ impl FlUrl {
             self.max_retries
         };
         let request_timeout = self.request_timeout;
+        let bandwidth_limit = self.get_bandwidth_limit();
         let params: ConnectionParams<'_> = self
             .get_connection_params(
                 default_port,
@@ -1410,6 +2166,12 @@ impl FlUrl {
 
         loop {
             let connection = http_connection_resolver.get_http_connection(&params).await;
+            // Ends the request on the connection however it goes from here: handed
+            // to the response, or dropped with an error.
+            let returner = crate::non_wasm::http_clients_cache::PooledConnectionReturner {
+                resolver: http_connection_resolver.clone(),
+                connection: connection.clone(),
+            };
 
             let response = match &mut request {
                 RequestToExecute::Compiled(request) => {
@@ -1440,28 +2202,34 @@ impl FlUrl {
                     let mut response =
                         FlUrlResponse::from_http1_response(self.url_builder, response);
                     response.set_body_read_timeout(self.response_body_timeout);
-                    response.set_decompress_gzip(self.decompress_gzip_response);
+                    response.set_decompress_response(self.decompress_gzip_response);
+                    response.set_bandwidth_limit(bandwidth_limit);
                     // The connection stays checked out until the response body
                     // is fully consumed; the returner puts it back (or disposes
                     // it) at that point.
-                    response.set_connection_returner(Box::new(
-                        crate::non_wasm::http_clients_cache::PooledConnectionReturner {
-                            resolver: http_connection_resolver.clone(),
-                            connection,
-                        },
-                    ));
+                    response.set_connection_returner(Box::new(returner));
                     return Ok(response);
                 }
                 Err(err) => {
                     // A single timeout means a slow response, not a dead
-                    // connection — the shared H2 client must survive it (its
-                    // own consecutive-timeouts policy handles dead peers). Any
-                    // other error evicts the connection from the pool; dropping
-                    // the Arc disposes it.
+                    // connection — the shared H2 client must survive it. Dead
+                    // peers are `MyHttp2Client`'s to notice, through its own
+                    // consecutive-timeouts policy, or the keep-alive client's,
+                    // through an unanswered PING; the pool then evicts the
+                    // latter as `is_dead`. Any other error evicts the
+                    // connection from the pool; dropping the returner ends the
+                    // request and, with the last Arc, disposes the connection.
                     if matches!(&err, my_http_client::MyHttpClientError::RequestTimeout(_)) {
-                        drop(connection);
+                        drop(returner);
                     } else {
                         http_connection_resolver.drop_connection(connection).await;
+
+                        // The upstream may have changed what it speaks since the
+                        // ALPN answer was cached — the next request probes again.
+                        #[cfg(feature = "_tls")]
+                        if let Some(key) = self.auto_alpn_key.as_ref() {
+                            self.get_connections_cache().forget_alpn_mode(key);
+                        }
                     }
 
                     if !error_is_safe_to_retry(&err, &request) || attempt_no >= max_retries {
@@ -1489,13 +2257,40 @@ fn error_is_safe_to_retry(
         // The connection is consumed by the upgrade; a retry would just
         // re-trigger it.
         my_http_client::MyHttpClientError::UpgradedToWebSocket => false,
+        #[cfg(feature = "_tls")]
+        _ if error_repeats_on_retry(err) => false,
         _ => request.method_is_idempotent(),
     }
 }
 
+/// A pin mismatch or an invalid TLS configuration fails every attempt the same.
+#[cfg(feature = "_tls")]
+fn error_repeats_on_retry(err: &my_http_client::MyHttpClientError) -> bool {
+    matches!(
+        err,
+        my_http_client::MyHttpClientError::CanNotConnectToRemoteHost(message)
+            if message.starts_with(crate::non_wasm::tls::PIN_MISMATCH_ERROR_PREFIX)
+                || message
+                    .starts_with(crate::non_wasm::tls::INVALID_TLS_CONFIGURATION_ERROR_PREFIX)
+    )
+}
+
 fn map_my_http_client_error(err: my_http_client::MyHttpClientError) -> FlUrlError {
     match err {
         my_http_client::MyHttpClientError::RequestTimeout(_) => FlUrlError::Timeout,
+        #[cfg(feature = "_tls")]
+        my_http_client::MyHttpClientError::CanNotConnectToRemoteHost(message)
+            if message.starts_with(crate::non_wasm::tls::PIN_MISMATCH_ERROR_PREFIX) =>
+        {
+            FlUrlError::TlsCertificatePinMismatch(message)
+        }
+        #[cfg(feature = "_tls")]
+        my_http_client::MyHttpClientError::CanNotConnectToRemoteHost(message)
+            if message
+                .starts_with(crate::non_wasm::tls::INVALID_TLS_CONFIGURATION_ERROR_PREFIX) =>
+        {
+            FlUrlError::InvalidTlsConfiguration(message)
+        }
         other => FlUrlError::MyHttpClientError(other),
     }
 }
@@ -1547,6 +2342,143 @@ mod test {
         println!("{}", resp.len());
     }
 
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    fn test_root_certificates() -> crate::TlsRootCertificates {
+        crate::TlsRootCertificates::from_pem(
+            crate::non_wasm::tls::test_certificates::TEST_ROOT_PEM,
+            crate::TlsTrustMode::ReplaceDefaults,
+        )
+        .unwrap()
+    }
+
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    #[tokio::test]
+    async fn auto_mode_speaks_what_alpn_negotiated() {
+        use crate::non_wasm::tls::test_server::TestTlsServer;
+
+        let servers: [(&[&[u8]], &str); 2] = [
+            (&[b"h2", b"http/1.1"], "HTTP/2.0"),
+            (&[b"http/1.1"], "HTTP/1.1"),
+        ];
+
+        for (alpn_protocols, expected_version) in servers {
+            let server = TestTlsServer::start(alpn_protocols).await;
+
+            // The CA is the cache's only: the probe that picks the protocol has
+            // to trust it as much as the request that follows.
+            let cache = crate::FlUrlHttpConnectionsCache::new();
+            cache.set_root_certificates(test_root_certificates());
+
+            let mut response = FlUrl::new(format!("https://localhost:{}", server.port))
+                .update_mode(crate::FlUrlMode::Auto)
+                .set_connections_cache(std::sync::Arc::new(cache))
+                .get()
+                .await
+                .unwrap();
+
+            assert_eq!(response.get_body_as_str().await.unwrap(), expected_version);
+        }
+    }
+
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    #[tokio::test]
+    async fn auto_mode_sends_the_request_on_the_probed_connection() {
+        use crate::non_wasm::tls::test_server::TestTlsServer;
+
+        let alpn_protocols: [&[&[u8]]; 2] = [&[b"h2", b"http/1.1"], &[b"http/1.1"]];
+
+        for alpn_protocols in alpn_protocols {
+            for do_not_reuse_connection in [false, true] {
+                let server = TestTlsServer::start(alpn_protocols).await;
+
+                let mut fl_url = FlUrl::new(format!("https://localhost:{}", server.port))
+                    .update_mode(crate::FlUrlMode::Auto)
+                    .with_root_certificates(test_root_certificates())
+                    .set_connections_cache(std::sync::Arc::new(
+                        crate::FlUrlHttpConnectionsCache::new(),
+                    ));
+                if do_not_reuse_connection {
+                    fl_url = fl_url.do_not_reuse_connection();
+                }
+
+                let mut response = fl_url.get().await.unwrap();
+                response.get_body_as_str().await.unwrap();
+
+                assert_eq!(server.get_connections(), 1);
+            }
+        }
+    }
+
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    #[tokio::test]
+    async fn cache_roots_apply_to_a_connection_that_is_not_reused() {
+        let server = crate::non_wasm::tls::test_server::TestTlsServer::start(&[b"http/1.1"]).await;
+
+        let cache = crate::FlUrlHttpConnectionsCache::new();
+        cache.set_root_certificates(test_root_certificates());
+
+        let response = FlUrl::new(format!("https://localhost:{}", server.port))
+            .set_connections_cache(std::sync::Arc::new(cache))
+            .do_not_reuse_connection()
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(response.get_status_code(), 200);
+    }
+
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    #[tokio::test]
+    async fn cache_pins_apply_to_a_connection_that_is_not_reused() {
+        let server = crate::non_wasm::tls::test_server::TestTlsServer::start(&[b"http/1.1"]).await;
+        let url = format!("https://localhost:{}", server.port);
+
+        let cache = std::sync::Arc::new(crate::FlUrlHttpConnectionsCache::new());
+        cache.set_certificate_pins(
+            "localhost",
+            crate::TlsCertificatePins::new().add_sha256([0; 32]),
+        );
+
+        let result = FlUrl::new(url.as_str())
+            .with_root_certificates(test_root_certificates())
+            .set_connections_cache(cache)
+            .do_not_reuse_connection()
+            .get()
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::FlUrlError::TlsCertificatePinMismatch(_))
+        ));
+
+        // Without the pin, the very same request goes through.
+        let response = FlUrl::new(url.as_str())
+            .with_root_certificates(test_root_certificates())
+            .do_not_reuse_connection()
+            .get()
+            .await
+            .unwrap();
+        assert_eq!(response.get_status_code(), 200);
+    }
+
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    #[tokio::test]
+    async fn cache_tls_policy_applies_to_a_connection_that_is_not_reused() {
+        let server = crate::non_wasm::tls::test_server::TestTlsServer::start(&[b"http/1.1"]).await;
+
+        let cache = crate::FlUrlHttpConnectionsCache::new();
+        cache.set_tls_policy(crate::TlsPolicy::new().set_cipher_suites(["TLS_NOT_A_SUITE"]));
+
+        let result = FlUrl::new(format!("https://localhost:{}", server.port))
+            .with_root_certificates(test_root_certificates())
+            .set_connections_cache(std::sync::Arc::new(cache))
+            .do_not_reuse_connection()
+            .get()
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::FlUrlError::InvalidTlsConfiguration(_))
+        ));
+    }
+
     #[test]
     fn execute_request_fills_url_headers_and_body_from_model() {
         use my_http_utils::macros::MyHttpInput;
@@ -0,0 +1,173 @@
+use std::collections::VecDeque;
+
+use crate::{
+    non_wasm::file_download::is_transport_error,
+    sse::{Reconnect, ResponseVerdict, SseParser},
+    EventStreamOptions, FlResponseAsStream, FlUrl, FlUrlError, SseEvent,
+};
+
+/// A `text/event-stream` (Server-Sent Events) response read event by event,
+/// reconnecting when the connection ends. Built with `FlUrl::get_event_stream`.
+///
+/// ```no_run
+/// # async fn doc() -> Result<(), flurl::FlUrlError> {
+/// let mut events = flurl::FlUrl::new("https://api.example.com/updates").get_event_stream();
+///
+/// while let Some(event) = events.next_event().await? {
+///     println!("{} #{:?}: {}", event.event, event.id, event.data);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Every connection is a GET with `Accept: text/event-stream`, `Cache-Control:
+/// no-cache` — unless the request sets either itself — and, once the server has
+/// sent an `id:`, `Last-Event-ID`. When one ends — the server closes it, the
+/// body breaks off, `response_body_timeout` runs out — the next is made after
+/// the reconnect delay (the server's `retry:` when it sent one), and doubled on
+/// every failure in a row; see [`EventStreamOptions`]. An event cut off with
+/// its connection is dropped.
+///
+/// `next_event` returns `None` once the server answers `204 No Content`. Any
+/// other status than `200` and the retried `408` / `429` / `502` / `503` /
+/// `504`, or a `200` that is not `text/event-stream`, ends the stream with an
+/// error, as does running out of `max_reconnect_attempts`.
+pub struct FlUrlEventStream {
+    fl_url: FlUrl,
+    reconnect: Reconnect,
+    parser: SseParser,
+    // The connection being read; `None` between connections.
+    stream: Option<FlResponseAsStream>,
+    // Parsed from a chunk, not handed out yet.
+    events: VecDeque<SseEvent>,
+    ended: bool,
+}
+
+impl FlUrlEventStream {
+    pub(crate) fn new(fl_url: FlUrl, options: EventStreamOptions) -> Self {
+        // A `Last-Event-ID` set on the request resumes the stream like the
+        // option does; it is sent by the parser from then on.
+        let last_event_id = options.last_event_id.clone().or_else(|| {
+            fl_url
+                .headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("Last-Event-ID"))
+                .map(|(_, value)| value.to_string())
+        });
+
+        Self {
+            fl_url,
+            reconnect: Reconnect::new(options),
+            parser: SseParser::new(last_event_id),
+            stream: None,
+            events: VecDeque::new(),
+            ended: false,
+        }
+    }
+
+    /// The ID the next reconnect sends as `Last-Event-ID`: keep it to resume
+    /// the stream in a later process with `EventStreamOptions::set_last_event_id`.
+    pub fn get_last_event_id(&self) -> Option<&str> {
+        self.parser.get_last_event_id()
+    }
+
+    /// The next event, connecting or reconnecting as needed; `None` once the
+    /// server has answered `204`.
+    pub async fn next_event(&mut self) -> Result<Option<SseEvent>, FlUrlError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+
+            if self.ended {
+                return Ok(None);
+            }
+
+            // Taken out while it is read: a `next_event` dropped mid-read leaves
+            // no half-read connection behind, the next one reconnects.
+            let mut stream = match self.stream.take() {
+                Some(stream) => stream,
+                None => match self.connect().await? {
+                    Some(stream) => stream,
+                    None => {
+                        self.ended = true;
+                        return Ok(None);
+                    }
+                },
+            };
+
+            match stream.get_next_bytes().await {
+                Ok(Some(chunk)) => {
+                    self.reconnect.on_data();
+
+                    let mut events = Vec::new();
+                    if let Some(retry) = self.parser.feed(&chunk, &mut events)? {
+                        self.reconnect.on_retry(retry);
+                    }
+                    self.events.extend(events);
+                    self.stream = Some(stream);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    if !is_transport_error(&err) || !self.reconnect.on_failure() {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Makes connections until one answers with an event stream (`Some`) or
+    /// with `204` (`None`).
+    async fn connect(&mut self) -> Result<Option<FlResponseAsStream>, FlUrlError> {
+        // Whatever the connection before left of an unfinished event goes.
+        self.parser.reset();
+
+        loop {
+            let delay = self.reconnect.get_delay();
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+
+            let mut request = self.fl_url.fork_for_download();
+            if !request.headers.has_header("Accept") {
+                request.headers.add("Accept", "text/event-stream");
+            }
+            if !request.headers.has_header("Cache-Control") {
+                request.headers.add("Cache-Control", "no-cache");
+            }
+            if let Some(last_event_id) = self.parser.get_last_event_id() {
+                request.headers.add("Last-Event-ID", last_event_id);
+            }
+
+            let response = match request.get().await {
+                Ok(response) => response,
+                Err(err) => {
+                    if is_transport_error(&err) && self.reconnect.on_failure() {
+                        continue;
+                    }
+                    return Err(err);
+                }
+            };
+
+            let status_code = response.get_status_code();
+            let content_type = response
+                .get_header_case_insensitive("content-type")
+                .ok()
+                .flatten();
+
+            match self.reconnect.on_response(status_code, content_type)? {
+                ResponseVerdict::Stream => {
+                    self.reconnect.on_connected();
+                    return Ok(Some(response.get_body_as_stream()));
+                }
+                ResponseVerdict::End => return Ok(None),
+                ResponseVerdict::Retry => {
+                    if !self.reconnect.on_failure() {
+                        return Err(FlUrlError::UnexpectedStatusCode(status_code));
+                    }
+                }
+            }
+        }
+    }
+}
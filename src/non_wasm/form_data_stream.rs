@@ -0,0 +1,443 @@
+use std::collections::VecDeque;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use hyper::body::{Body, Frame, SizeHint};
+use tokio::io::AsyncRead;
+
+use crate::non_wasm::file_body::{poll_read_chunk, FileBody};
+use crate::FlUrlError;
+
+/// A `multipart/form-data` body that is streamed rather than built in memory the
+/// way [`crate::body::FormDataBody`] is: text fields next to files and readers of
+/// any size, sent with [`crate::FlUrl::post_form_data_streamed`] at constant
+/// memory.
+///
+/// ```no_run
+/// # async fn doc() -> Result<(), flurl::FlUrlError> {
+/// use flurl::{FlUrl, FormDataPart, FormDataStream};
+///
+/// let form = FormDataStream::new()
+///     .append_form_data_field("title", "Nightly backup")
+///     .append_part(FormDataPart::file("archive", "/backups/archive.tar").await?);
+///
+/// let response = FlUrl::new("https://api.example.com")
+///     .append_path_segment("upload")
+///     .set_timeout(std::time::Duration::from_secs(600))
+///     .post_form_data_streamed(form)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// When the size of every part is known — text fields, files, readers given a
+/// length — so is the size of the whole body, and it goes out with that
+/// `Content-Length`; a single reader of unknown length sends it chunked.
+pub struct FormDataStream {
+    boundary: String,
+    parts: Vec<FormDataPart>,
+}
+
+impl FormDataStream {
+    /// An empty form with a randomly generated boundary.
+    pub fn new() -> Self {
+        Self {
+            boundary: format!("----FlUrlFormBoundary{}", crate::body::rand_string(16)),
+            parts: Vec::new(),
+        }
+    }
+
+    pub fn append_form_data_field(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.append_part(FormDataPart::text(name, value))
+    }
+
+    pub fn append_part(mut self, part: FormDataPart) -> Self {
+        self.parts.push(part);
+        self
+    }
+
+    pub fn get_content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// The exact size of the encoded body, or `None` when a part has no known
+    /// length.
+    pub fn get_content_length(&self) -> Option<u64> {
+        let parts_len = get_parts_len(&self.boundary, self.parts.iter())?;
+        Some(parts_len + self.get_closing().len() as u64)
+    }
+
+    pub(crate) fn into_body(self) -> FormDataStreamBody {
+        FormDataStreamBody {
+            closing: Some(self.get_closing()),
+            boundary: self.boundary,
+            parts: self.parts.into(),
+            current: None,
+        }
+    }
+
+    fn get_closing(&self) -> Bytes {
+        Bytes::from(format!("--{}--\r\n", self.boundary))
+    }
+}
+
+impl Default for FormDataStream {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Ends the content of every part, ahead of the next delimiter.
+const PART_END: &[u8] = b"\r\n";
+
+/// One part of a [`FormDataStream`]: its `name`, and for a file its `filename`,
+/// a `Content-Type` and any other headers of its own.
+pub struct FormDataPart {
+    name: String,
+    file_name: Option<String>,
+    content_type: Option<String>,
+    headers: Vec<(String, String)>,
+    content: PartContent,
+}
+
+impl FormDataPart {
+    /// A plain text field, sent without a `Content-Type`.
+    pub fn text(name: impl Into<String>, value: impl Into<String>) -> Self {
+        Self::with_content(name, PartContent::Bytes(Bytes::from(value.into())))
+    }
+
+    /// The file at `path`, read as it is sent. The `filename` is the name of the
+    /// file and the `Content-Type` is guessed from its extension, falling back to
+    /// `application/octet-stream`; both can be overridden. The length is taken
+    /// from the metadata of the open file, as for [`crate::FlUrl::post_file`].
+    pub async fn file(name: impl Into<String>, path: impl AsRef<Path>) -> Result<Self, FlUrlError> {
+        let path = path.as_ref();
+        let body = FileBody::open(path).await?;
+
+        let content_type = crate::non_wasm::file_body::guess_content_type(path)
+            .unwrap_or("application/octet-stream");
+
+        let mut result = Self::with_content(name, PartContent::File(body));
+        result.file_name = path
+            .file_name()
+            .map(|file_name| file_name.to_string_lossy().to_string());
+        result.content_type = Some(content_type.to_string());
+
+        Ok(result)
+    }
+
+    /// Content pulled from `reader` as it is sent, as `application/octet-stream`
+    /// unless set otherwise. With `len` the reader must deliver that many bytes —
+    /// it is cut there, and one that ends short fails the request; with `None`
+    /// the form goes out chunked.
+    pub fn reader(
+        name: impl Into<String>,
+        reader: impl AsyncRead + Send + Sync + 'static,
+        len: Option<u64>,
+    ) -> Self {
+        let content = PartContent::Reader {
+            reader: Box::pin(reader),
+            remaining: len,
+            buffer: BytesMut::new(),
+        };
+
+        let mut result = Self::with_content(name, content);
+        result.content_type = Some("application/octet-stream".to_string());
+        result
+    }
+
+    pub fn set_file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    pub fn set_content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    /// Adds a header of the part, after its `Content-Disposition` and
+    /// `Content-Type`. Line breaks in the name or the value are dropped.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    fn with_content(name: impl Into<String>, content: PartContent) -> Self {
+        Self {
+            name: name.into(),
+            file_name: None,
+            content_type: None,
+            headers: Vec::new(),
+            content,
+        }
+    }
+
+    /// The delimiter and headers that go ahead of the content.
+    fn get_head(&self, boundary: &str) -> Bytes {
+        let mut result = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"",
+            boundary,
+            escape_quoted(&self.name)
+        );
+
+        if let Some(file_name) = self.file_name.as_ref() {
+            result.push_str("; filename=\"");
+            result.push_str(&escape_quoted(file_name));
+            result.push('"');
+        }
+
+        result.push_str("\r\n");
+
+        if let Some(content_type) = self.content_type.as_ref() {
+            result.push_str("Content-Type: ");
+            result.push_str(&strip_line_breaks(content_type));
+            result.push_str("\r\n");
+        }
+
+        for (name, value) in self.headers.iter() {
+            result.push_str(&strip_line_breaks(name));
+            result.push_str(": ");
+            result.push_str(&strip_line_breaks(value));
+            result.push_str("\r\n");
+        }
+
+        result.push_str("\r\n");
+
+        Bytes::from(result)
+    }
+}
+
+/// The encoded size of `parts` — heads, contents and line breaks — if every
+/// content has a known length.
+fn get_parts_len<'s>(boundary: &str, parts: impl Iterator<Item = &'s FormDataPart>) -> Option<u64> {
+    let mut result = 0;
+
+    for part in parts {
+        result += part.get_head(boundary).len() as u64;
+        result += part.content.get_len()?;
+        result += PART_END.len() as u64;
+    }
+
+    Some(result)
+}
+
+/// A quoted `name` / `filename`, escaped the way browsers do it (the HTML
+/// standard's form encoding): `"` and line breaks become percent escapes.
+fn escape_quoted(src: &str) -> String {
+    src.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn strip_line_breaks(src: &str) -> String {
+    src.chars().filter(|c| *c != '\r' && *c != '\n').collect()
+}
+
+enum PartContent {
+    Bytes(Bytes),
+    File(FileBody),
+    Reader {
+        reader: Pin<Box<dyn AsyncRead + Send + Sync>>,
+        // None: read to the end, whatever the length.
+        remaining: Option<u64>,
+        buffer: BytesMut,
+    },
+}
+
+impl PartContent {
+    fn get_len(&self) -> Option<u64> {
+        match self {
+            Self::Bytes(bytes) => Some(bytes.len() as u64),
+            Self::File(file) => Some(file.get_len()),
+            Self::Reader { remaining, .. } => *remaining,
+        }
+    }
+
+    fn poll_chunk(&mut self, cx: &mut Context<'_>) -> Poll<Option<Result<Bytes, String>>> {
+        match self {
+            Self::Bytes(bytes) => {
+                if bytes.is_empty() {
+                    return Poll::Ready(None);
+                }
+
+                Poll::Ready(Some(Ok(std::mem::take(bytes))))
+            }
+            Self::File(file) => match Pin::new(file).poll_frame(cx) {
+                Poll::Ready(Some(Ok(frame))) => match frame.into_data() {
+                    Ok(data) => Poll::Ready(Some(Ok(data))),
+                    Err(_) => Poll::Ready(None),
+                },
+                Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => Poll::Ready(None),
+                Poll::Pending => Poll::Pending,
+            },
+            Self::Reader {
+                reader,
+                remaining,
+                buffer,
+            } => {
+                let max_len = match remaining {
+                    Some(0) => return Poll::Ready(None),
+                    Some(remaining) => *remaining,
+                    None => u64::MAX,
+                };
+
+                let chunk = match poll_read_chunk(reader.as_mut(), buffer, cx, max_len) {
+                    Poll::Ready(Ok(chunk)) => chunk,
+                    Poll::Ready(Err(err)) => return Poll::Ready(Some(Err(err.to_string()))),
+                    Poll::Pending => return Poll::Pending,
+                };
+
+                if chunk.is_empty() {
+                    return match remaining {
+                        Some(remaining) => Poll::Ready(Some(Err(format!(
+                            "A form data reader ended {} bytes short of the length it was given",
+                            remaining
+                        )))),
+                        None => Poll::Ready(None),
+                    };
+                }
+
+                if let Some(remaining) = remaining.as_mut() {
+                    *remaining -= chunk.len() as u64;
+                }
+
+                Poll::Ready(Some(Ok(chunk)))
+            }
+        }
+    }
+}
+
+/// A [`FormDataStream`] as the body `execute_streamed` sends: for each part its
+/// head, its content chunk by chunk, and the line break that ends it, then the
+/// closing delimiter.
+pub(crate) struct FormDataStreamBody {
+    boundary: String,
+    parts: VecDeque<FormDataPart>,
+    current: Option<PartContent>,
+    // Taken once the last part has been sent.
+    closing: Option<Bytes>,
+}
+
+impl Body for FormDataStreamBody {
+    type Data = Bytes;
+    type Error = String;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        if let Some(content) = this.current.as_mut() {
+            return match content.poll_chunk(cx) {
+                Poll::Ready(Some(Ok(chunk))) => Poll::Ready(Some(Ok(Frame::data(chunk)))),
+                Poll::Ready(Some(Err(err))) => Poll::Ready(Some(Err(err))),
+                Poll::Ready(None) => {
+                    this.current = None;
+                    Poll::Ready(Some(Ok(Frame::data(Bytes::from_static(PART_END)))))
+                }
+                Poll::Pending => Poll::Pending,
+            };
+        }
+
+        if let Some(part) = this.parts.pop_front() {
+            let head = part.get_head(&this.boundary);
+            this.current = Some(part.content);
+            return Poll::Ready(Some(Ok(Frame::data(head))));
+        }
+
+        Poll::Ready(this.closing.take().map(|closing| Ok(Frame::data(closing))))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.closing.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        // Exact only before the first frame: the lengths are of the whole form.
+        if self.current.is_some() || self.closing.is_none() {
+            return SizeHint::default();
+        }
+
+        let closing_len = self
+            .closing
+            .as_ref()
+            .map_or(0, |closing| closing.len() as u64);
+
+        match get_parts_len(&self.boundary, self.parts.iter()) {
+            Some(parts_len) => SizeHint::with_exact(parts_len + closing_len),
+            None => SizeHint::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn the_form_is_encoded_part_by_part_to_its_content_length() {
+        let form = FormDataStream::new()
+            .append_form_data_field("title", "Q\"3\" report")
+            .append_part(
+                FormDataPart::reader("data", &b"a,b\n1,2\n"[..], Some(8))
+                    .set_file_name("report.csv")
+                    .set_content_type("text/csv")
+                    .with_header("Content-Language", "en"),
+            );
+
+        let boundary = form.boundary.clone();
+        let content_length = form.get_content_length().unwrap();
+
+        let body = form.into_body();
+        assert_eq!(body.size_hint().exact(), Some(content_length));
+
+        let collected = body.collect().await.unwrap().to_bytes();
+
+        let expected = format!(
+            "--{b}\r\n\
+             Content-Disposition: form-data; name=\"title\"\r\n\
+             \r\n\
+             Q\"3\" report\r\n\
+             --{b}\r\n\
+             Content-Disposition: form-data; name=\"data\"; filename=\"report.csv\"\r\n\
+             Content-Type: text/csv\r\n\
+             Content-Language: en\r\n\
+             \r\n\
+             a,b\n1,2\n\r\n\
+             --{b}--\r\n",
+            b = boundary
+        );
+
+        assert_eq!(std::str::from_utf8(&collected).unwrap(), expected);
+        assert_eq!(collected.len() as u64, content_length);
+    }
+
+    #[tokio::test]
+    async fn a_reader_of_unknown_length_leaves_the_form_unsized() {
+        let form = FormDataStream::new()
+            .append_form_data_field("a", "1")
+            .append_part(FormDataPart::reader("b", &b"xyz"[..], None));
+
+        assert_eq!(form.get_content_length(), None);
+
+        let collected = form.into_body().collect().await.unwrap().to_bytes();
+        let text = std::str::from_utf8(&collected).unwrap();
+        assert!(text.contains("\r\n\r\nxyz\r\n--"), "{}", text);
+        assert!(text.ends_with("--\r\n"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn a_reader_that_ends_short_fails_the_body() {
+        let form =
+            FormDataStream::new().append_part(FormDataPart::reader("b", &b"xyz"[..], Some(10)));
+
+        let err = form.into_body().collect().await.unwrap_err();
+        assert!(err.contains("7 bytes short"), "{}", err);
+    }
+}
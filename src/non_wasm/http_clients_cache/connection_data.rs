@@ -1,11 +1,16 @@
-#[cfg(all(unix, feature = "with-ssh"))]
+#[cfg(any(feature = "_tls", all(unix, feature = "with-ssh")))]
 use std::sync::Arc;
 
 #[cfg(feature = "_tls")]
 use my_tls::ClientCertificate;
 use rust_extensions::remote_endpoint::RemoteEndpoint;
 
-use crate::FlUrlMode;
+#[cfg(feature = "_tls")]
+use crate::{
+    non_wasm::tls::TlsClientConfigCache, TlsCertificatePins, TlsClientIdentity, TlsKeyLogFile,
+    TlsPolicy, TlsRootCertificates, TlsServerCertificateVerifier,
+};
+use crate::{FlUrlMode, H2KeepAlive};
 
 #[derive(Clone)]
 pub struct ConnectionParams<'s> {
@@ -13,16 +18,40 @@ pub struct ConnectionParams<'s> {
     pub remote_endpoint: RemoteEndpoint<'s>,
     pub host_header: Option<&'s str>,
     #[cfg(feature = "_tls")]
+    pub tls_server_name: Option<&'s str>,
+    #[cfg(feature = "_tls")]
     pub client_certificate: Option<&'s ClientCertificate>,
+    #[cfg(feature = "_tls")]
+    pub client_identity: Option<Arc<TlsClientIdentity>>,
     pub accept_invalid_certificate: bool,
+    #[cfg(feature = "_tls")]
+    pub root_certificates: Option<Arc<TlsRootCertificates>>,
+    #[cfg(feature = "_tls")]
+    pub certificate_pins: Option<Arc<TlsCertificatePins>>,
+    #[cfg(feature = "_tls")]
+    pub server_certificate_verifier: Option<Arc<dyn TlsServerCertificateVerifier>>,
+    #[cfg(feature = "_tls")]
+    pub tls_policy: Option<Arc<TlsPolicy>>,
+    #[cfg(feature = "_tls")]
+    pub tls_key_log: Option<Arc<TlsKeyLogFile>>,
+    #[cfg(feature = "_tls")]
+    pub tls_client_configs: Option<Arc<TlsClientConfigCache>>,
     pub reuse_connection_timeout_seconds: i64,
+    pub h2_keep_alive: Option<H2KeepAlive>,
     #[cfg(all(unix, feature = "with-ssh"))]
     pub ssh_session: Option<Arc<my_ssh::SshSession>>,
 }
 
 #[cfg(feature = "_tls")]
 impl<'s> ConnectionParams<'s> {
-    pub fn get_server_name(&'s self) -> &'s str {
+    /// The name used for SNI and for verifying the server certificate: the
+    /// explicit `FlUrl::with_tls_server_name` override, else the `Host` header,
+    /// else the url host.
+    pub fn get_server_name(&self) -> &str {
+        if let Some(tls_server_name) = self.tls_server_name {
+            return tls_server_name;
+        }
+
         let host = if let Some(host_header) = self.host_header {
             host_header
         } else {
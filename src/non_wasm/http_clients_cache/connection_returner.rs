@@ -19,6 +19,9 @@ pub trait ConnectionReturner: Send + Sync {
     async fn return_connection(self: Box<Self>);
 }
 
+/// Made right after the checkout, so the request ends — and the stream it holds
+/// on a shared H2 connection is counted off — whichever way it goes: returned,
+/// dropped with its response, or dropped on an error.
 pub(crate) struct PooledConnectionReturner<
     TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
     TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
@@ -34,6 +37,18 @@ impl<
     > ConnectionReturner for PooledConnectionReturner<TStream, TConnector>
 {
     async fn return_connection(self: Box<Self>) {
-        self.resolver.put_connection_back(self.connection).await;
+        self.resolver
+            .put_connection_back(self.connection.clone())
+            .await;
+    }
+}
+
+impl<
+        TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
+        TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+    > Drop for PooledConnectionReturner<TStream, TConnector>
+{
+    fn drop(&mut self) {
+        self.resolver.end_stream(&self.connection);
     }
 }
@@ -1,10 +1,12 @@
 use std::sync::Arc;
 
-use my_http_client::{http1::MyHttpClient, http1_hyper::MyHttpHyperClient, http2::MyHttp2Client};
+use my_http_client::{http1::MyHttpClient, http1_hyper::MyHttpHyperClient};
 use tokio::net::TcpStream;
 
 use crate::{
-    non_wasm::fl_url::FlUrlMode, non_wasm::http_connectors::HttpConnector, non_wasm::my_http_client_wrapper::MyHttpClientWrapper,
+    non_wasm::fl_url::FlUrlMode,
+    non_wasm::http_connectors::HttpConnector,
+    non_wasm::my_http_client_wrapper::{MyHttpClientWrapper, MyHttpClientWrapperInner},
     ConnectionParams, FlUrlHttpConnectionsCache,
 };
 
@@ -23,13 +25,13 @@ impl HttpConnectionCreator {
         match params.mode {
             FlUrlMode::H2 => Arc::new(MyHttpClientWrapper::new(
                 key.to_string(),
-                MyHttp2Client::new(http_connector).into(),
+                MyHttpClientWrapperInner::new_h2(http_connector, params.h2_keep_alive),
             )),
             FlUrlMode::Http1NoHyper => Arc::new(MyHttpClientWrapper::new(
                 key.to_string(),
                 MyHttpClient::new(http_connector).into(),
             )),
-            FlUrlMode::Http1Hyper => Arc::new(MyHttpClientWrapper::new(
+            FlUrlMode::Http1Hyper | FlUrlMode::Auto => Arc::new(MyHttpClientWrapper::new(
                 key.to_string(),
                 MyHttpHyperClient::new(http_connector).into(),
             )),
@@ -76,4 +78,8 @@ impl HttpConnectionResolver<TcpStream, HttpConnector> for FlUrlHttpConnectionsCa
     ) {
         self.drop_http_connection_sync(&connection);
     }
+
+    fn end_stream(&self, connection: &Arc<MyHttpClientWrapper<TcpStream, HttpConnector>>) {
+        self.end_pooled_stream(connection);
+    }
 }
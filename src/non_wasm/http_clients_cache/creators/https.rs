@@ -1,12 +1,16 @@
 use std::sync::Arc;
 
-use my_http_client::{http1::MyHttpClient, http1_hyper::MyHttpHyperClient, http2::MyHttp2Client};
+use my_http_client::{http1::MyHttpClient, http1_hyper::MyHttpHyperClient};
 use my_tls::tokio_rustls::client::TlsStream;
+use parking_lot::Mutex;
 
 use tokio::net::TcpStream;
 
 use crate::{
-    non_wasm::fl_url::FlUrlMode, non_wasm::http_connectors::HttpsConnector, non_wasm::my_http_client_wrapper::MyHttpClientWrapper,
+    non_wasm::fl_url::FlUrlMode,
+    non_wasm::http_connectors::HttpsConnector,
+    non_wasm::my_http_client_wrapper::{MyHttpClientWrapper, MyHttpClientWrapperInner},
+    non_wasm::tls::TlsClientSettings,
 };
 
 use super::super::*;
@@ -14,30 +18,51 @@ use super::super::*;
 pub struct HttpsConnectionCreator;
 
 impl HttpsConnectionCreator {
-    pub fn create_connection(
-        params: &ConnectionParams<'_>,
-        key: String,
-    ) -> Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>> {
+    pub fn create_connector(params: &ConnectionParams<'_>) -> HttpsConnector {
         let server_name = params.get_server_name().to_string();
 
-        let connector = HttpsConnector::new(
+        HttpsConnector::new(
             params.remote_endpoint.to_owned(),
             server_name,
-            params.client_certificate.map(|x| x.clone()),
-            params.accept_invalid_certificate,
+            TlsClientSettings {
+                client_certificate: params.client_certificate.map(|x| x.clone()),
+                client_identity: params.client_identity.clone(),
+                accept_invalid_certificate: params.accept_invalid_certificate,
+                root_certificates: params.root_certificates.clone(),
+                certificate_pins: params.certificate_pins.clone(),
+                server_certificate_verifier: params.server_certificate_verifier.clone(),
+                tls_policy: params.tls_policy.clone(),
+                key_log: params.tls_key_log.clone(),
+                client_configs: params.tls_client_configs.clone(),
+            },
             params.mode.is_h2(),
-        );
+        )
+    }
+
+    pub fn create_connection(
+        params: &ConnectionParams<'_>,
+        key: String,
+    ) -> Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>> {
+        Self::create_connection_with_connector(params, key, Self::create_connector(params))
+    }
 
+    /// `create_connection` over a connector that is already made — the one the
+    /// ALPN probe of `FlUrlMode::Auto` left its connection in.
+    pub fn create_connection_with_connector(
+        params: &ConnectionParams<'_>,
+        key: String,
+        connector: HttpsConnector,
+    ) -> Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>> {
         match params.mode {
             FlUrlMode::H2 => Arc::new(MyHttpClientWrapper::new(
                 key.to_string(),
-                MyHttp2Client::new(connector).into(),
+                MyHttpClientWrapperInner::new_h2(connector, params.h2_keep_alive),
             )),
             FlUrlMode::Http1NoHyper => Arc::new(MyHttpClientWrapper::new(
                 key.to_string(),
                 MyHttpClient::new(connector).into(),
             )),
-            FlUrlMode::Http1Hyper => Arc::new(MyHttpClientWrapper::new(
+            FlUrlMode::Http1Hyper | FlUrlMode::Auto => Arc::new(MyHttpClientWrapper::new(
                 key.to_string(),
                 MyHttpHyperClient::new(connector).into(),
             )),
@@ -62,6 +87,44 @@ impl HttpConnectionResolver<TlsStream<TcpStream>, HttpsConnector> for HttpsConne
     }
 }
 
+/// `HttpsConnectionCreator` for a request that does not reuse connections but
+/// may have probed the host for `FlUrlMode::Auto`: the probed connection serves
+/// the first attempt, a retry opens its own.
+pub struct HttpsProbedConnectionCreator {
+    connection: Mutex<Option<Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>>>>,
+}
+
+impl HttpsProbedConnectionCreator {
+    pub fn new(
+        connection: Option<Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>>>,
+    ) -> Self {
+        Self {
+            connection: Mutex::new(connection),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl HttpConnectionResolver<TlsStream<TcpStream>, HttpsConnector> for HttpsProbedConnectionCreator {
+    async fn get_http_connection(
+        &self,
+        params: &ConnectionParams<'_>,
+    ) -> Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>> {
+        let probed_connection = self.connection.lock().take();
+        if let Some(connection) = probed_connection {
+            return connection;
+        }
+
+        HttpsConnectionCreator.get_http_connection(params).await
+    }
+
+    async fn put_connection_back(
+        &self,
+        _connection: Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>>,
+    ) {
+    }
+}
+
 #[async_trait::async_trait]
 impl HttpConnectionResolver<TlsStream<TcpStream>, HttpsConnector> for FlUrlHttpConnectionsCache {
     async fn get_http_connection(
@@ -84,4 +147,11 @@ impl HttpConnectionResolver<TlsStream<TcpStream>, HttpsConnector> for FlUrlHttpC
     ) {
         self.drop_https_connection_sync(&connection);
     }
+
+    fn end_stream(
+        &self,
+        connection: &Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>>,
+    ) {
+        self.end_pooled_stream(connection);
+    }
 }
@@ -1,11 +1,12 @@
 use std::sync::Arc;
 
-use my_http_client::{http1::MyHttpClient, http1_hyper::MyHttpHyperClient, http2::MyHttp2Client};
+use my_http_client::{http1::MyHttpClient, http1_hyper::MyHttpHyperClient};
 
 use crate::{
-    non_wasm::fl_url::FlUrlMode, non_wasm::http_connectors::SshHttpConnector,
-    non_wasm::my_http_client_wrapper::MyHttpClientWrapper, ConnectionParams, FlUrlHttpConnectionsCache,
-    HttpConnectionResolver,
+    non_wasm::fl_url::FlUrlMode,
+    non_wasm::http_connectors::SshHttpConnector,
+    non_wasm::my_http_client_wrapper::{MyHttpClientWrapper, MyHttpClientWrapperInner},
+    ConnectionParams, FlUrlHttpConnectionsCache, HttpConnectionResolver,
 };
 
 pub struct SshConnectionCreator;
@@ -26,13 +27,13 @@ impl SshConnectionCreator {
         match params.mode {
             FlUrlMode::H2 => Arc::new(MyHttpClientWrapper::new(
                 key,
-                MyHttp2Client::new(connector).into(),
+                MyHttpClientWrapperInner::new_h2(connector, params.h2_keep_alive),
             )),
             FlUrlMode::Http1NoHyper => Arc::new(MyHttpClientWrapper::new(
                 key,
                 MyHttpClient::new(connector).into(),
             )),
-            FlUrlMode::Http1Hyper => Arc::new(MyHttpClientWrapper::new(
+            FlUrlMode::Http1Hyper | FlUrlMode::Auto => Arc::new(MyHttpClientWrapper::new(
                 key,
                 MyHttpHyperClient::new(connector).into(),
             )),
@@ -54,6 +55,7 @@ impl HttpConnectionResolver<my_ssh::SshAsyncChannel, SshHttpConnector> for SshCo
             ssh_session.get_ssh_credentials(),
             params.remote_endpoint,
             params.mode,
+            params.h2_keep_alive,
         );
         Self::create_connection(params, key)
     }
@@ -89,4 +91,11 @@ impl HttpConnectionResolver<my_ssh::SshAsyncChannel, SshHttpConnector>
     ) {
         self.drop_ssh_connection_sync(&connection);
     }
+
+    fn end_stream(
+        &self,
+        connection: &Arc<MyHttpClientWrapper<my_ssh::SshAsyncChannel, SshHttpConnector>>,
+    ) {
+        self.end_pooled_stream(connection);
+    }
 }
@@ -3,9 +3,9 @@ use std::sync::Arc;
 use crate::{
     non_wasm::fl_url::FlUrlMode,
     non_wasm::http_connectors::{UnixSocketConnector, UnixSocketStream},
-    non_wasm::my_http_client_wrapper::MyHttpClientWrapper,
+    non_wasm::my_http_client_wrapper::{MyHttpClientWrapper, MyHttpClientWrapperInner},
 };
-use my_http_client::{http1::MyHttpClient, http1_hyper::MyHttpHyperClient, http2::MyHttp2Client};
+use my_http_client::{http1::MyHttpClient, http1_hyper::MyHttpHyperClient};
 
 use super::super::*;
 
@@ -21,13 +21,13 @@ impl UnixSocketHttpClientCreator {
         match params.mode {
             FlUrlMode::H2 => Arc::new(MyHttpClientWrapper::new(
                 key,
-                MyHttp2Client::new(connector).into(),
+                MyHttpClientWrapperInner::new_h2(connector, params.h2_keep_alive),
             )),
             FlUrlMode::Http1NoHyper => Arc::new(MyHttpClientWrapper::new(
                 key,
                 MyHttpClient::new(connector).into(),
             )),
-            FlUrlMode::Http1Hyper => Arc::new(MyHttpClientWrapper::new(
+            FlUrlMode::Http1Hyper | FlUrlMode::Auto => Arc::new(MyHttpClientWrapper::new(
                 key,
                 MyHttpHyperClient::new(connector).into(),
             )),
@@ -74,4 +74,11 @@ impl HttpConnectionResolver<UnixSocketStream, UnixSocketConnector> for FlUrlHttp
     ) {
         self.drop_unix_socket_connection_sync(&connection);
     }
+
+    fn end_stream(
+        &self,
+        connection: &Arc<MyHttpClientWrapper<UnixSocketStream, UnixSocketConnector>>,
+    ) {
+        self.end_pooled_stream(connection);
+    }
 }
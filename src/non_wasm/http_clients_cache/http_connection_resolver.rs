@@ -27,4 +27,11 @@ pub trait HttpConnectionResolver<
     async fn drop_connection(&self, connection: Arc<MyHttpClientWrapper<TStream, TConnector>>) {
         let _ = connection;
     }
+
+    /// The request `get_http_connection` handed `connection` to has ended, its
+    /// response body included. Pooling resolvers count it off the streams of a
+    /// shared H2 connection. Default: no-op.
+    fn end_stream(&self, connection: &Arc<MyHttpClientWrapper<TStream, TConnector>>) {
+        let _ = connection;
+    }
 }
@@ -10,7 +10,11 @@ use tokio::net::TcpStream;
 #[cfg(feature = "_tls")]
 use my_tls::tokio_rustls::client::TlsStream;
 
-use crate::{non_wasm::http_connectors::*, non_wasm::my_http_client_wrapper::MyHttpClientWrapper, ConnectionParams};
+use crate::{
+    non_wasm::http_connectors::*,
+    non_wasm::my_http_client_wrapper::{H2KeepAlive, MyHttpClientWrapper},
+    ConnectionParams,
+};
 
 pub struct ConnectionItem<
     TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
@@ -20,8 +24,42 @@ pub struct ConnectionItem<
     pub connection: Arc<MyHttpClientWrapper<TStream, TConnector>>,
 }
 
+/// Default per-connection stream budget for H2. Matches the
+/// `SETTINGS_MAX_CONCURRENT_STREAMS` most servers (and gRPC stacks) announce; a
+/// server that announces fewer gets fewer.
+pub const DEFAULT_H2_MAX_CONCURRENT_STREAMS: usize = 100;
+
+/// Default cap on how many H2 connections a single key may fan out to.
+pub const DEFAULT_H2_MAX_CONNECTIONS_PER_KEY: usize = 8;
+
+/// An extra H2 connection (anything beyond the first one of a key) that has had
+/// no streams in flight for this long is closed again.
+pub const DEFAULT_H2_IDLE_SCALE_DOWN_SECONDS: i64 = 30;
+
+/// How long an ALPN answer (`FlUrlMode::Auto`) is trusted before the host is
+/// probed again, so an upstream that starts speaking h2 is eventually noticed.
+#[cfg(feature = "_tls")]
+pub const ALPN_OUTCOME_TTL_SECONDS: i64 = 600;
+
 pub struct FlUrlHttpConnectionsCacheInner {
     max_connections: usize,
+    h2_max_concurrent_streams: usize,
+    h2_max_connections_per_key: usize,
+    h2_idle_scale_down_seconds: i64,
+    h2_keep_alive: Option<H2KeepAlive>,
+    bandwidth_limit: Option<Arc<crate::non_wasm::bandwidth_limit::BandwidthLimit>>,
+    #[cfg(feature = "_tls")]
+    root_certificates: Option<Arc<crate::TlsRootCertificates>>,
+    #[cfg(feature = "_tls")]
+    certificate_pins: AHashMap<String, Arc<crate::TlsCertificatePins>>,
+    #[cfg(feature = "_tls")]
+    tls_policy: Option<Arc<crate::TlsPolicy>>,
+    #[cfg(feature = "_tls")]
+    tls_key_log: Option<Arc<crate::TlsKeyLogFile>>,
+    #[cfg(feature = "_tls")]
+    tls_client_configs: Arc<crate::non_wasm::tls::TlsClientConfigCache>,
+    #[cfg(feature = "_tls")]
+    alpn_modes: AHashMap<String, (crate::FlUrlMode, DateTimeAsMicroseconds)>,
     http: AHashMap<String, Vec<ConnectionItem<TcpStream, HttpConnector>>>,
     #[cfg(feature = "_tls")]
     https: AHashMap<String, Vec<ConnectionItem<TlsStream<TcpStream>, HttpsConnector>>>,
@@ -31,10 +69,67 @@ pub struct FlUrlHttpConnectionsCacheInner {
     ssh: AHashMap<String, Vec<ConnectionItem<my_ssh::SshAsyncChannel, SshHttpConnector>>>,
 }
 
+impl FlUrlHttpConnectionsCacheInner {
+    fn h2_scaling(&self) -> H2Scaling {
+        H2Scaling {
+            max_concurrent_streams: self.h2_max_concurrent_streams,
+            max_connections: self.h2_max_connections_per_key,
+            idle_scale_down_seconds: self.h2_idle_scale_down_seconds,
+        }
+    }
+
+    /// Fills what the request left unset with the cache-level defaults, before
+    /// the key is computed — the key has to describe the client that gets built.
+    fn apply_defaults<'s>(&self, params: &ConnectionParams<'s>) -> ConnectionParams<'s> {
+        let mut params = params.clone();
+        if params.h2_keep_alive.is_none() {
+            params.h2_keep_alive = self.h2_keep_alive;
+        }
+        #[cfg(feature = "_tls")]
+        if params.root_certificates.is_none() {
+            params.root_certificates = self.root_certificates.clone();
+        }
+        #[cfg(feature = "_tls")]
+        if params.certificate_pins.is_none() && !self.certificate_pins.is_empty() {
+            params.certificate_pins = self.certificate_pins.get(params.get_server_name()).cloned();
+        }
+        #[cfg(feature = "_tls")]
+        if params.tls_policy.is_none() {
+            params.tls_policy = self.tls_policy.clone();
+        }
+        #[cfg(feature = "_tls")]
+        if params.tls_key_log.is_none() {
+            params.tls_key_log = self.tls_key_log.clone();
+        }
+        #[cfg(feature = "_tls")]
+        if params.tls_client_configs.is_none() {
+            params.tls_client_configs = Some(self.tls_client_configs.clone());
+        }
+        params
+    }
+}
+
 impl Default for FlUrlHttpConnectionsCacheInner {
     fn default() -> Self {
         Self {
             max_connections: 5,
+            h2_max_concurrent_streams: DEFAULT_H2_MAX_CONCURRENT_STREAMS,
+            h2_max_connections_per_key: DEFAULT_H2_MAX_CONNECTIONS_PER_KEY,
+            h2_idle_scale_down_seconds: DEFAULT_H2_IDLE_SCALE_DOWN_SECONDS,
+            h2_keep_alive: None,
+            bandwidth_limit: None,
+            #[cfg(feature = "_tls")]
+            root_certificates: None,
+            #[cfg(feature = "_tls")]
+            certificate_pins: Default::default(),
+            #[cfg(feature = "_tls")]
+            tls_policy: None,
+            #[cfg(feature = "_tls")]
+            tls_key_log: None,
+            #[cfg(feature = "_tls")]
+            tls_client_configs: Arc::new(crate::non_wasm::tls::TlsClientConfigCache::new()),
+            #[cfg(feature = "_tls")]
+            alpn_modes: Default::default(),
             http: Default::default(),
             #[cfg(feature = "_tls")]
             https: Default::default(),
@@ -67,6 +162,114 @@ impl FlUrlHttpConnectionsCache {
         }
     }
 
+    /// The most streams one H2 connection is allowed to carry before the pool
+    /// opens another connection for the same key. Once a connection has the
+    /// server's `SETTINGS_MAX_CONCURRENT_STREAMS`, that is its budget when it is
+    /// lower — above it, requests would queue inside the client instead of going
+    /// out. Until then, and against a server that announces more, this value
+    /// applies.
+    pub fn set_h2_max_concurrent_streams(&self, value: usize) {
+        self.inner.lock().h2_max_concurrent_streams = value.max(1);
+    }
+
+    /// Upper bound on the H2 connections opened for one key. Once every one of
+    /// them is saturated, new streams are balanced across them anyway and wait
+    /// for the server to free a slot — the behavior of the single-client pool.
+    pub fn set_h2_max_connections_per_key(&self, value: usize) {
+        self.inner.lock().h2_max_connections_per_key = value.max(1);
+    }
+
+    /// How long an extra H2 connection may sit without streams before it is
+    /// closed. The first connection of a key is governed by the regular reuse
+    /// timeout only.
+    pub fn set_h2_idle_scale_down_timeout(&self, timeout: std::time::Duration) {
+        self.inner.lock().h2_idle_scale_down_seconds = timeout.as_secs() as i64;
+    }
+
+    /// Default H2 keep-alive for every H2 connection this cache creates, unless
+    /// the request sets its own with `FlUrl::set_h2_keep_alive`. A connection
+    /// whose PING goes unanswered for `ack_timeout` is evicted before the next
+    /// request can land on it.
+    pub fn set_h2_keep_alive(
+        &self,
+        interval: std::time::Duration,
+        ack_timeout: std::time::Duration,
+    ) {
+        self.inner.lock().h2_keep_alive = Some(H2KeepAlive::new(interval, ack_timeout));
+    }
+
+    /// Holds every request that goes through this cache to `bytes_per_second`
+    /// together — one budget for what they send, one for what they receive —
+    /// unless the request sets its own with `FlUrl::set_bandwidth_limit`. For
+    /// keeping a batch job off a link production traffic shares: give the job a
+    /// cache of its own with a limit. Requests made with `do_not_reuse_connection`
+    /// are not held to it. Requests already under way keep the limit they started
+    /// with.
+    pub fn set_bandwidth_limit(&self, bytes_per_second: u64) {
+        self.inner.lock().bandwidth_limit = Some(Arc::new(
+            crate::non_wasm::bandwidth_limit::BandwidthLimit::new(bytes_per_second),
+        ));
+    }
+
+    /// Lifts the limit set with [`Self::set_bandwidth_limit`].
+    pub fn remove_bandwidth_limit(&self) {
+        self.inner.lock().bandwidth_limit = None;
+    }
+
+    pub(crate) fn get_bandwidth_limit(
+        &self,
+    ) -> Option<Arc<crate::non_wasm::bandwidth_limit::BandwidthLimit>> {
+        self.inner.lock().bandwidth_limit.clone()
+    }
+
+    /// Default trust anchors for every https connection this cache creates,
+    /// unless the request brings its own with `FlUrl::with_root_certificates`.
+    #[cfg(feature = "_tls")]
+    pub fn set_root_certificates(&self, root_certificates: crate::TlsRootCertificates) {
+        self.inner.lock().root_certificates = Some(Arc::new(root_certificates));
+    }
+
+    /// Pins every https connection this cache creates for `host` — the TLS server
+    /// name, i.e. the `Host` header or the url host without the port — unless the
+    /// request brings its own with `FlUrl::with_certificate_pins`. Replaces pins
+    /// set earlier for the same host.
+    #[cfg(feature = "_tls")]
+    pub fn set_certificate_pins(&self, host: impl Into<String>, pins: crate::TlsCertificatePins) {
+        self.inner
+            .lock()
+            .certificate_pins
+            .insert(host.into(), Arc::new(pins));
+    }
+
+    /// Default TLS version and cipher-suite policy for every https connection
+    /// this cache creates, unless the request brings its own with
+    /// `FlUrl::with_tls_policy`. Connections already pooled keep the policy they
+    /// were opened with.
+    #[cfg(feature = "_tls")]
+    pub fn set_tls_policy(&self, tls_policy: crate::TlsPolicy) {
+        self.inner.lock().tls_policy = Some(Arc::new(tls_policy));
+    }
+
+    /// Writes the TLS secrets of every https connection this cache creates to
+    /// `key_log`, unless the request brings its own with `FlUrl::with_tls_key_log`.
+    /// Meant to be switched on from the environment while debugging:
+    ///
+    /// ```ignore
+    /// if let Some(key_log) = TlsKeyLogFile::from_env() {
+    ///     cache.set_tls_key_log(key_log);
+    /// }
+    /// ```
+    #[cfg(feature = "_tls")]
+    pub fn set_tls_key_log(&self, key_log: crate::TlsKeyLogFile) {
+        self.inner.lock().tls_key_log = Some(Arc::new(key_log));
+    }
+
+    /// `params` with what the request left unset filled in from the cache-level
+    /// defaults, as the cache's own `get_*_connection` does it.
+    pub(crate) fn apply_defaults<'s>(&self, params: &ConnectionParams<'s>) -> ConnectionParams<'s> {
+        self.inner.lock().apply_defaults(params)
+    }
+
     /// Drops every pooled connection. Existing checked-out connections are
     /// unaffected and get disposed when their last user drops them.
     pub fn clear(&self) {
@@ -74,6 +277,8 @@ impl FlUrlHttpConnectionsCache {
         write_access.http.clear();
         #[cfg(feature = "_tls")]
         write_access.https.clear();
+        #[cfg(feature = "_tls")]
+        write_access.alpn_modes.clear();
         #[cfg(unix)]
         write_access.unix_socket.clear();
         #[cfg(all(unix, feature = "with-ssh"))]
@@ -86,36 +291,54 @@ impl FlUrlHttpConnectionsCache {
     pub fn gc(&self, reuse_connection_timeout_seconds: i64) {
         let now = DateTimeAsMicroseconds::now();
         let mut write_access = self.inner.lock();
-        gc_map(&mut write_access.http, now, reuse_connection_timeout_seconds);
+        let scale_down_seconds = write_access.h2_idle_scale_down_seconds;
+        gc_map(
+            &mut write_access.http,
+            now,
+            reuse_connection_timeout_seconds,
+            scale_down_seconds,
+        );
         #[cfg(feature = "_tls")]
         gc_map(
             &mut write_access.https,
             now,
             reuse_connection_timeout_seconds,
+            scale_down_seconds,
         );
+        #[cfg(feature = "_tls")]
+        write_access.alpn_modes.retain(|_, (_, resolved_at)| {
+            now.duration_since(*resolved_at).get_full_seconds() < ALPN_OUTCOME_TTL_SECONDS
+        });
         #[cfg(unix)]
         gc_map(
             &mut write_access.unix_socket,
             now,
             reuse_connection_timeout_seconds,
+            scale_down_seconds,
         );
         #[cfg(all(unix, feature = "with-ssh"))]
-        gc_map(&mut write_access.ssh, now, reuse_connection_timeout_seconds);
+        gc_map(
+            &mut write_access.ssh,
+            now,
+            reuse_connection_timeout_seconds,
+            scale_down_seconds,
+        );
     }
 
     pub async fn get_http_connection(
         &self,
         params: &ConnectionParams<'_>,
     ) -> Arc<MyHttpClientWrapper<TcpStream, HttpConnector>> {
-        let connection_key = super::utils::get_http_connection_key(params);
-
         let mut write_access = self.inner.lock();
+        let params = &write_access.apply_defaults(params);
+        let connection_key = super::utils::get_http_connection_key(params);
+        let h2_scaling = write_access.h2_scaling();
 
         checkout_connection(
             &mut write_access.http,
             connection_key.as_str(),
             params.reuse_connection_timeout_seconds,
-            params.mode.is_h2(),
+            params.mode.is_h2().then_some(h2_scaling),
             || {
                 super::creators::HttpConnectionCreator::create_connection(
                     params,
@@ -149,20 +372,61 @@ impl FlUrlHttpConnectionsCache {
         remove_connection(&mut write_access.http, connection);
     }
 
+    /// A request a connection of this cache was handed to has ended. Only the
+    /// shared H2 connections count their streams; see `checkout_connection`.
+    pub(crate) fn end_pooled_stream<
+        TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
+        TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+    >(
+        &self,
+        connection: &Arc<MyHttpClientWrapper<TStream, TConnector>>,
+    ) {
+        if connection.is_h2() {
+            connection.end_stream();
+        }
+    }
+
+    /// The mode a host picked through ALPN for `FlUrlMode::Auto`, while it is
+    /// still fresh.
+    #[cfg(feature = "_tls")]
+    pub(crate) fn get_alpn_mode(&self, key: &str) -> Option<crate::FlUrlMode> {
+        let now = DateTimeAsMicroseconds::now();
+        let read_access = self.inner.lock();
+        let (mode, resolved_at) = read_access.alpn_modes.get(key)?;
+
+        if now.duration_since(*resolved_at).get_full_seconds() >= ALPN_OUTCOME_TTL_SECONDS {
+            return None;
+        }
+
+        Some(*mode)
+    }
+
+    #[cfg(feature = "_tls")]
+    pub(crate) fn set_alpn_mode(&self, key: String, mode: crate::FlUrlMode) {
+        let now = DateTimeAsMicroseconds::now();
+        self.inner.lock().alpn_modes.insert(key, (mode, now));
+    }
+
+    #[cfg(feature = "_tls")]
+    pub(crate) fn forget_alpn_mode(&self, key: &str) {
+        self.inner.lock().alpn_modes.remove(key);
+    }
+
     #[cfg(feature = "_tls")]
     pub async fn get_https_connection(
         &self,
         params: &ConnectionParams<'_>,
     ) -> Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>> {
-        let connection_key = super::utils::get_https_connection_key(params);
-
         let mut write_access = self.inner.lock();
+        let params = &write_access.apply_defaults(params);
+        let connection_key = super::utils::get_https_connection_key(params);
+        let h2_scaling = write_access.h2_scaling();
 
         checkout_connection(
             &mut write_access.https,
             connection_key.as_str(),
             params.reuse_connection_timeout_seconds,
-            params.mode.is_h2(),
+            params.mode.is_h2().then_some(h2_scaling),
             || {
                 super::creators::HttpsConnectionCreator::create_connection(
                     params,
@@ -182,6 +446,22 @@ impl FlUrlHttpConnectionsCache {
         put_connection_back(&mut write_access.https, max_connections, connection);
     }
 
+    /// Pools the connection the ALPN probe of `FlUrlMode::Auto` opened, idle, for
+    /// the request that probed to check out. A pool that is full drops it.
+    #[cfg(feature = "_tls")]
+    pub(crate) fn add_probed_https_connection(
+        &self,
+        connection: Arc<MyHttpClientWrapper<TlsStream<TcpStream>, HttpsConnector>>,
+    ) {
+        let mut write_access = self.inner.lock();
+        let max_connections = if connection.is_h2() {
+            write_access.h2_scaling().max_connections
+        } else {
+            write_access.max_connections
+        };
+        add_connection(&mut write_access.https, max_connections, connection);
+    }
+
     #[cfg(feature = "_tls")]
     pub async fn put_https_connection_back(
         &self,
@@ -208,19 +488,21 @@ impl FlUrlHttpConnectionsCache {
             panic!("ssh_credentials is none");
         };
 
+        let mut write_access = self.inner.lock();
+        let params = &write_access.apply_defaults(params);
         let connection_key = super::utils::get_ssh_connection_key(
             ssh_session.get_ssh_credentials(),
             params.remote_endpoint,
             params.mode,
+            params.h2_keep_alive,
         );
-
-        let mut write_access = self.inner.lock();
+        let h2_scaling = write_access.h2_scaling();
 
         checkout_connection(
             &mut write_access.ssh,
             connection_key.as_str(),
             params.reuse_connection_timeout_seconds,
-            params.mode.is_h2(),
+            params.mode.is_h2().then_some(h2_scaling),
             || {
                 super::creators::SshConnectionCreator::create_connection(
                     params,
@@ -262,15 +544,16 @@ impl FlUrlHttpConnectionsCache {
         &self,
         params: &ConnectionParams<'_>,
     ) -> Arc<MyHttpClientWrapper<UnixSocketStream, UnixSocketConnector>> {
-        let connection_key = super::utils::get_unix_socket_connection_key(params);
-
         let mut write_access = self.inner.lock();
+        let params = &write_access.apply_defaults(params);
+        let connection_key = super::utils::get_unix_socket_connection_key(params);
+        let h2_scaling = write_access.h2_scaling();
 
         checkout_connection(
             &mut write_access.unix_socket,
             connection_key.as_str(),
             params.reuse_connection_timeout_seconds,
-            params.mode.is_h2(),
+            params.mode.is_h2().then_some(h2_scaling),
             || {
                 super::creators::UnixSocketHttpClientCreator::create_connection(
                     params,
@@ -308,11 +591,50 @@ impl FlUrlHttpConnectionsCache {
     }
 }
 
+#[derive(Clone, Copy)]
+struct H2Scaling {
+    max_concurrent_streams: usize,
+    max_connections: usize,
+    idle_scale_down_seconds: i64,
+}
+
+impl H2Scaling {
+    /// The streams `connection` carries before another one is opened: what the
+    /// server announced, capped by the configured budget.
+    fn get_stream_budget<
+        TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
+        TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+    >(
+        &self,
+        connection: &MyHttpClientWrapper<TStream, TConnector>,
+    ) -> usize {
+        match connection.get_h2_max_concurrent_streams() {
+            Some(announced) => announced.clamp(1, self.max_concurrent_streams),
+            None => self.max_concurrent_streams,
+        }
+    }
+}
+
+/// Streams currently running on a pooled connection: counted in by
+/// `checkout_connection`, counted off by the request's returner guard once the
+/// response body is consumed or dropped.
+fn streams_in_flight<
+    TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
+    TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+>(
+    item: &ConnectionItem<TStream, TConnector>,
+) -> usize {
+    item.connection.get_streams_in_flight()
+}
+
 /// Pool contract: HTTP/1 connections are checked out EXCLUSIVELY — the item is
 /// removed from the pool for the duration of the request (and of the response
 /// body) and comes back via `put_connection_back` only when it is safe to reuse.
-/// H2 connections multiplex, so a single client per key is SHARED: checkout
-/// clones the Arc and leaves the item in place.
+/// H2 connections multiplex, so they are SHARED: checkout clones the Arc and
+/// leaves the item in place. A key starts with one H2 connection; when every
+/// connection of the key carries `max_concurrent_streams`, another one is opened
+/// (up to `max_connections`) and new streams go to the least loaded connection.
+/// Extra connections that stay without streams are closed again.
 fn checkout_connection<
     TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
     TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
@@ -320,21 +642,34 @@ fn checkout_connection<
     connections: &mut AHashMap<String, Vec<ConnectionItem<TStream, TConnector>>>,
     hash_map_key: &str,
     connection_timeout_seconds: i64,
-    is_h2: bool,
+    h2_scaling: Option<H2Scaling>,
     create_connection: impl Fn() -> Arc<MyHttpClientWrapper<TStream, TConnector>>,
 ) -> Arc<MyHttpClientWrapper<TStream, TConnector>> {
     let now = DateTimeAsMicroseconds::now();
 
     if let Some(items) = connections.get_mut(hash_map_key) {
-        // Expired idle connections are dropped (drop of the last Arc disposes them)
+        // Expired idle connections are dropped (drop of the last Arc disposes them),
+        // and so are connections known to be dead — an H2 client whose PING went
+        // unanswered must not be handed to a request.
         items.retain(|itm| {
             now.duration_since(itm.last_update).get_full_seconds() < connection_timeout_seconds
+                && !itm.connection.is_dead()
         });
 
-        if is_h2 {
-            if let Some(item) = items.first_mut() {
-                item.last_update = now;
-                return item.connection.clone();
+        if let Some(h2_scaling) = h2_scaling {
+            scale_down_idle_h2(items, now, h2_scaling.idle_scale_down_seconds);
+            let at_connections_cap = items.len() >= h2_scaling.max_connections;
+
+            // `min_by_key` keeps the first of equally loaded items, so an idle
+            // pool keeps using its oldest connection and extras can drain.
+            if let Some(item) = items.iter_mut().min_by_key(|itm| streams_in_flight(itm)) {
+                if streams_in_flight(item) < h2_scaling.get_stream_budget(&item.connection)
+                    || at_connections_cap
+                {
+                    item.last_update = now;
+                    item.connection.start_stream();
+                    return item.connection.clone();
+                }
             }
         } else if let Some(item) = items.pop() {
             if items.is_empty() {
@@ -350,14 +685,15 @@ fn checkout_connection<
 
     let new_one = create_connection();
 
-    if is_h2 {
-        connections.insert(
-            hash_map_key.to_string(),
-            vec![ConnectionItem {
+    if h2_scaling.is_some() {
+        new_one.start_stream();
+        connections
+            .entry(hash_map_key.to_string())
+            .or_default()
+            .push(ConnectionItem {
                 last_update: now,
                 connection: new_one.clone(),
-            }],
-        );
+            });
     }
     // HTTP/1: the new connection is checked out — it enters the pool only via
     // put_connection_back, once the response body has been fully consumed.
@@ -365,6 +701,31 @@ fn checkout_connection<
     new_one
 }
 
+/// Closes the extra H2 connections of a key that have had no streams for
+/// `idle_seconds`. The first connection stays: it is the one a quiet key keeps
+/// using, and the regular reuse timeout decides when it goes.
+fn scale_down_idle_h2<
+    TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
+    TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+>(
+    items: &mut Vec<ConnectionItem<TStream, TConnector>>,
+    now: DateTimeAsMicroseconds,
+    idle_seconds: i64,
+) {
+    if items.len() < 2 {
+        return;
+    }
+
+    let mut index = 0;
+    items.retain(|itm| {
+        let keep = index == 0
+            || streams_in_flight(itm) > 0
+            || now.duration_since(itm.last_update).get_full_seconds() < idle_seconds;
+        index += 1;
+        keep
+    });
+}
+
 fn put_connection_back<
     TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
     TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
@@ -390,11 +751,22 @@ fn put_connection_back<
         return;
     }
 
+    add_connection(connections, max_connections, connection);
+}
+
+fn add_connection<
+    TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
+    TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+>(
+    connections: &mut AHashMap<String, Vec<ConnectionItem<TStream, TConnector>>>,
+    max_connections: usize,
+    connection: Arc<MyHttpClientWrapper<TStream, TConnector>>,
+) {
     let items = connections.entry(connection.key.to_string()).or_default();
 
     if items.len() < max_connections {
         items.push(ConnectionItem {
-            last_update: now,
+            last_update: DateTimeAsMicroseconds::now(),
             connection,
         });
     }
@@ -423,11 +795,16 @@ fn gc_map<
     connections: &mut AHashMap<String, Vec<ConnectionItem<TStream, TConnector>>>,
     now: DateTimeAsMicroseconds,
     timeout_seconds: i64,
+    h2_idle_scale_down_seconds: i64,
 ) {
     connections.retain(|_, items| {
         items.retain(|itm| {
             now.duration_since(itm.last_update).get_full_seconds() < timeout_seconds
+                && !itm.connection.is_dead()
         });
+        if items.first().is_some_and(|itm| itm.connection.is_h2()) {
+            scale_down_idle_h2(items, now, h2_idle_scale_down_seconds);
+        }
         !items.is_empty()
     });
 }
@@ -444,9 +821,26 @@ mod tests {
             remote_endpoint: endpoint.to_ref(),
             host_header: None,
             #[cfg(feature = "_tls")]
+            tls_server_name: None,
+            #[cfg(feature = "_tls")]
             client_certificate: None,
+            #[cfg(feature = "_tls")]
+            client_identity: None,
             accept_invalid_certificate: false,
+            #[cfg(feature = "_tls")]
+            root_certificates: None,
+            #[cfg(feature = "_tls")]
+            certificate_pins: None,
+            #[cfg(feature = "_tls")]
+            server_certificate_verifier: None,
+            #[cfg(feature = "_tls")]
+            tls_policy: None,
+            #[cfg(feature = "_tls")]
+            tls_key_log: None,
+            #[cfg(feature = "_tls")]
+            tls_client_configs: None,
             reuse_connection_timeout_seconds: 120,
+            h2_keep_alive: None,
             #[cfg(all(unix, feature = "with-ssh"))]
             ssh_session: None,
         }
@@ -488,6 +882,376 @@ mod tests {
         assert!(!Arc::ptr_eq(&first, &third));
     }
 
+    #[tokio::test]
+    async fn h2_opens_extra_connection_when_streams_saturate() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        cache.set_h2_max_concurrent_streams(2);
+        cache.set_h2_max_connections_per_key(2);
+        let endpoint = RemoteEndpointOwned::try_parse("http://localhost:9999".to_string()).unwrap();
+        let params = make_params(&endpoint, FlUrlMode::H2);
+
+        let a1 = cache.get_http_connection(&params).await;
+        let a2 = cache.get_http_connection(&params).await;
+        assert!(Arc::ptr_eq(&a1, &a2));
+
+        // The first connection carries its two streams — a third one goes to a
+        // second connection instead of queueing behind the stream limit.
+        let b1 = cache.get_http_connection(&params).await;
+        assert!(!Arc::ptr_eq(&a1, &b1));
+
+        // New streams are balanced onto the least loaded connection.
+        let b2 = cache.get_http_connection(&params).await;
+        assert!(Arc::ptr_eq(&b1, &b2));
+
+        // At the connections cap a saturated key still hands out a connection.
+        let overflow = cache.get_http_connection(&params).await;
+        assert!(Arc::ptr_eq(&overflow, &a1) || Arc::ptr_eq(&overflow, &b1));
+
+        let pooled = cache.inner.lock().http.get(&a1.key).map(|v| v.len());
+        assert_eq!(pooled, Some(2));
+    }
+
+    #[tokio::test]
+    async fn only_checkouts_count_as_h2_streams() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        cache.set_h2_max_concurrent_streams(2);
+        let endpoint = RemoteEndpointOwned::try_parse("http://localhost:9999".to_string()).unwrap();
+        let params = make_params(&endpoint, FlUrlMode::H2);
+
+        // Clones of the Arc held elsewhere are no streams.
+        let first = cache.get_http_connection(&params).await;
+        let clones = [first.clone(), first.clone(), first.clone()];
+        let second = cache.get_http_connection(&params).await;
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(first.get_streams_in_flight(), 2);
+
+        // An ended stream frees its place on the connection.
+        cache.end_pooled_stream(&second);
+        let third = cache.get_http_connection(&params).await;
+        assert!(Arc::ptr_eq(&first, &third));
+
+        let fourth = cache.get_http_connection(&params).await;
+        assert!(!Arc::ptr_eq(&first, &fourth));
+        drop(clones);
+    }
+
+    #[tokio::test]
+    async fn h2_extra_connections_shrink_when_idle() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        cache.set_h2_max_concurrent_streams(1);
+        cache.set_h2_idle_scale_down_timeout(std::time::Duration::ZERO);
+        let endpoint = RemoteEndpointOwned::try_parse("http://localhost:9999".to_string()).unwrap();
+        let params = make_params(&endpoint, FlUrlMode::H2);
+
+        let first = cache.get_http_connection(&params).await;
+        let second = cache.get_http_connection(&params).await;
+        assert!(!Arc::ptr_eq(&first, &second));
+
+        let key = first.key.clone();
+        cache.end_pooled_stream(&second);
+
+        // The extra connection has no streams left, so the next checkout closes
+        // it and keeps using the first connection of the key.
+        cache.end_pooled_stream(&first);
+        let third = cache.get_http_connection(&params).await;
+        let pooled = cache.inner.lock().http.get(&key).map(|v| v.len());
+        assert_eq!(pooled, Some(1));
+
+        // A busy extra connection is never closed under its streams.
+        let fourth = cache.get_http_connection(&params).await;
+        assert!(!Arc::ptr_eq(&third, &fourth));
+        cache.gc(120);
+        let pooled = cache.inner.lock().http.get(&key).map(|v| v.len());
+        assert_eq!(pooled, Some(2));
+    }
+
+    #[tokio::test]
+    async fn h2_keep_alive_is_part_of_the_key() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        let endpoint = RemoteEndpointOwned::try_parse("http://localhost:9999".to_string()).unwrap();
+
+        let plain = cache
+            .get_http_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+
+        let mut params = make_params(&endpoint, FlUrlMode::H2);
+        params.h2_keep_alive = Some(H2KeepAlive::new(
+            std::time::Duration::from_secs(10),
+            std::time::Duration::from_secs(5),
+        ));
+        let with_ping = cache.get_http_connection(&params).await;
+        assert!(!Arc::ptr_eq(&plain, &with_ping));
+
+        // The cache-level default turns plain requests into PING-ing ones.
+        cache.set_h2_keep_alive(
+            std::time::Duration::from_secs(10),
+            std::time::Duration::from_secs(5),
+        );
+        let defaulted = cache
+            .get_http_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+        assert!(Arc::ptr_eq(&with_ping, &defaulted));
+    }
+
+    #[cfg(feature = "_tls")]
+    #[tokio::test]
+    async fn root_certificates_are_part_of_the_https_key() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        let endpoint =
+            RemoteEndpointOwned::try_parse("https://localhost:9999".to_string()).unwrap();
+        let root_certificates = crate::TlsRootCertificates::from_pem(
+            crate::non_wasm::tls::test_certificates::TEST_ROOT_PEM,
+            crate::TlsTrustMode::ReplaceDefaults,
+        )
+        .unwrap();
+
+        let system_roots = cache
+            .get_https_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+
+        let mut params = make_params(&endpoint, FlUrlMode::H2);
+        params.root_certificates = Some(Arc::new(root_certificates.clone()));
+        let private_roots = cache.get_https_connection(&params).await;
+        assert!(!Arc::ptr_eq(&system_roots, &private_roots));
+
+        // The cache-level default applies to requests that bring no roots.
+        cache.set_root_certificates(root_certificates);
+        let defaulted = cache
+            .get_https_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+        assert!(Arc::ptr_eq(&private_roots, &defaulted));
+    }
+
+    #[cfg(feature = "_tls")]
+    #[tokio::test]
+    async fn certificate_pins_apply_per_host() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        let pinned_endpoint =
+            RemoteEndpointOwned::try_parse("https://pinned.example.com:443".to_string()).unwrap();
+        let other_endpoint =
+            RemoteEndpointOwned::try_parse("https://other.example.com:443".to_string()).unwrap();
+
+        let unpinned = cache
+            .get_https_connection(&make_params(&pinned_endpoint, FlUrlMode::H2))
+            .await;
+
+        cache.set_certificate_pins(
+            "pinned.example.com",
+            crate::TlsCertificatePins::new().add_sha256([1u8; 32]),
+        );
+
+        let pinned = cache
+            .get_https_connection(&make_params(&pinned_endpoint, FlUrlMode::H2))
+            .await;
+        assert!(!Arc::ptr_eq(&unpinned, &pinned));
+        assert!(pinned.key.ends_with(&format!(
+            "pins{:016x}",
+            crate::TlsCertificatePins::new()
+                .add_sha256([1u8; 32])
+                .get_fingerprint()
+        )));
+
+        let other = cache
+            .get_https_connection(&make_params(&other_endpoint, FlUrlMode::H2))
+            .await;
+        assert!(other.key.ends_with("|nopins"));
+    }
+
+    #[cfg(feature = "_tls")]
+    #[test]
+    fn tls_server_name_overrides_host_header_in_the_key() {
+        let endpoint =
+            RemoteEndpointOwned::try_parse("https://10.0.3.17:8443".to_string()).unwrap();
+
+        let mut params = make_params(&endpoint, FlUrlMode::H2);
+        params.host_header = Some("api.example.com");
+        assert_eq!(params.get_server_name(), "api.example.com");
+
+        params.tls_server_name = Some("billing.internal");
+        assert_eq!(params.get_server_name(), "billing.internal");
+        assert!(
+            super::super::utils::get_https_connection_key(&params).contains("|billing.internal|")
+        );
+    }
+
+    #[cfg(feature = "_tls")]
+    #[tokio::test]
+    async fn server_certificate_verifier_is_keyed_by_its_arc() {
+        struct AcceptAll;
+
+        impl crate::TlsServerCertificateVerifier for AcceptAll {
+            fn verify(&self, _ctx: &crate::TlsServerCertificateContext<'_>) -> Result<(), String> {
+                Ok(())
+            }
+        }
+
+        let cache = FlUrlHttpConnectionsCache::new();
+        let endpoint =
+            RemoteEndpointOwned::try_parse("https://localhost:9999".to_string()).unwrap();
+        let verifier: Arc<dyn crate::TlsServerCertificateVerifier> = Arc::new(AcceptAll);
+
+        let mut params = make_params(&endpoint, FlUrlMode::H2);
+        params.server_certificate_verifier = Some(verifier.clone());
+        let first = cache.get_https_connection(&params).await;
+        let same_verifier = cache.get_https_connection(&params).await;
+        assert!(Arc::ptr_eq(&first, &same_verifier));
+
+        params.server_certificate_verifier = Some(Arc::new(AcceptAll));
+        let other_verifier = cache.get_https_connection(&params).await;
+        assert!(!Arc::ptr_eq(&first, &other_verifier));
+
+        let no_verifier = cache
+            .get_https_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+        assert!(!Arc::ptr_eq(&first, &no_verifier));
+    }
+
+    #[cfg(feature = "_tls")]
+    #[tokio::test]
+    async fn tls_policy_is_part_of_the_https_key() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        let endpoint =
+            RemoteEndpointOwned::try_parse("https://localhost:9999".to_string()).unwrap();
+
+        let default_policy = cache
+            .get_https_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+
+        let mut params = make_params(&endpoint, FlUrlMode::H2);
+        params.tls_policy = Some(Arc::new(crate::TlsPolicy::tls13_only()));
+        let tls13_only = cache.get_https_connection(&params).await;
+        assert!(!Arc::ptr_eq(&default_policy, &tls13_only));
+
+        cache.set_tls_policy(crate::TlsPolicy::tls13_only());
+        let cache_default = cache
+            .get_https_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+        assert!(Arc::ptr_eq(&tls13_only, &cache_default));
+    }
+
+    #[cfg(feature = "_tls")]
+    #[tokio::test]
+    async fn key_log_is_part_of_the_https_key() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        let endpoint =
+            RemoteEndpointOwned::try_parse("https://localhost:9999".to_string()).unwrap();
+
+        let not_logged = cache
+            .get_https_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+
+        cache.set_tls_key_log(crate::TlsKeyLogFile::new("/tmp/flurl-keys.log"));
+        let logged = cache
+            .get_https_connection(&make_params(&endpoint, FlUrlMode::H2))
+            .await;
+        assert!(!Arc::ptr_eq(&not_logged, &logged));
+    }
+
+    #[tokio::test]
+    async fn dead_h2_connection_is_evicted() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let service = hyper::service::service_fn(|_req| async {
+                Ok::<_, std::convert::Infallible>(hyper::Response::new(http_body_util::Full::new(
+                    bytes::Bytes::new(),
+                )))
+            });
+            let _ = hyper::server::conn::http2::Builder::new(hyper_util::rt::TokioExecutor::new())
+                .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+                .await;
+        });
+
+        let cache = FlUrlHttpConnectionsCache::new();
+        cache.set_h2_keep_alive(
+            std::time::Duration::from_millis(100),
+            std::time::Duration::from_millis(100),
+        );
+        let endpoint = RemoteEndpointOwned::try_parse(format!("http://{}", addr)).unwrap();
+        let params = make_params(&endpoint, FlUrlMode::H2);
+
+        let first = cache.get_http_connection(&params).await;
+        first.connect().await.unwrap();
+        assert!(!first.is_dead());
+
+        // The peer goes away; the connection task notices and the client reports
+        // itself dead without a request having to fail on it first.
+        server.abort();
+        for _ in 0..100 {
+            if first.is_dead() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert!(first.is_dead());
+
+        let second = cache.get_http_connection(&params).await;
+        assert!(!Arc::ptr_eq(&first, &second));
+    }
+
+    #[tokio::test]
+    async fn h2_scales_out_at_the_streams_the_server_allows() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                tokio::spawn(async move {
+                    let service = hyper::service::service_fn(|_req| async {
+                        Ok::<_, std::convert::Infallible>(hyper::Response::new(
+                            http_body_util::Full::new(bytes::Bytes::new()),
+                        ))
+                    });
+                    let _ = hyper::server::conn::http2::Builder::new(
+                        hyper_util::rt::TokioExecutor::new(),
+                    )
+                    .max_concurrent_streams(1)
+                    .serve_connection(hyper_util::rt::TokioIo::new(stream), service)
+                    .await;
+                });
+            }
+        });
+
+        // The cache would put 100 streams on a connection; the server takes one.
+        let cache = FlUrlHttpConnectionsCache::new();
+        let endpoint = RemoteEndpointOwned::try_parse(format!("http://{}", addr)).unwrap();
+        let params = make_params(&endpoint, FlUrlMode::H2);
+
+        let first = cache.get_http_connection(&params).await;
+        first.connect().await.unwrap();
+        for _ in 0..100 {
+            if first.get_h2_max_concurrent_streams().is_some() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        assert_eq!(first.get_h2_max_concurrent_streams(), Some(1));
+
+        let second = cache.get_http_connection(&params).await;
+        assert!(!Arc::ptr_eq(&first, &second));
+    }
+
+    #[cfg(feature = "_tls")]
+    #[test]
+    fn alpn_outcome_is_cached_until_forgotten() {
+        let cache = FlUrlHttpConnectionsCache::new();
+        assert!(cache.get_alpn_mode("example.com:443|example.com").is_none());
+
+        cache.set_alpn_mode("example.com:443|example.com".to_string(), FlUrlMode::H2);
+        assert!(matches!(
+            cache.get_alpn_mode("example.com:443|example.com"),
+            Some(FlUrlMode::H2)
+        ));
+
+        cache.forget_alpn_mode("example.com:443|example.com");
+        assert!(cache.get_alpn_mode("example.com:443|example.com").is_none());
+    }
+
     #[tokio::test]
     async fn different_modes_do_not_share_connections() {
         let cache = FlUrlHttpConnectionsCache::new();
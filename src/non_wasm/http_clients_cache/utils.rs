@@ -1,16 +1,32 @@
 #[cfg(feature = "_tls")]
-use std::hash::{Hash, Hasher};
+use std::sync::Arc;
 
 #[cfg(all(unix, feature = "with-ssh"))]
 use rust_extensions::remote_endpoint::RemoteEndpoint;
 
-use crate::ConnectionParams;
+use crate::{ConnectionParams, H2KeepAlive};
 
 fn mode_tag(mode: crate::FlUrlMode) -> &'static str {
     match mode {
         crate::FlUrlMode::H2 => "h2",
         crate::FlUrlMode::Http1NoHyper => "h1",
         crate::FlUrlMode::Http1Hyper => "h1h",
+        // Resolved before a connection is requested; never part of a real key.
+        crate::FlUrlMode::Auto => "auto",
+    }
+}
+
+/// A keep-alive H2 client is a different client from the plain one, so the PING
+/// settings are part of the key: a request that asked for PINGs must not land on
+/// a connection that sends none.
+fn keep_alive_tag(mode: crate::FlUrlMode, h2_keep_alive: Option<H2KeepAlive>) -> String {
+    match h2_keep_alive {
+        Some(keep_alive) if mode.is_h2() => format!(
+            "|ping{}/{}",
+            keep_alive.interval.as_millis(),
+            keep_alive.ack_timeout.as_millis()
+        ),
+        _ => String::new(),
     }
 }
 
@@ -20,43 +36,90 @@ fn mode_tag(mode: crate::FlUrlMode) -> &'static str {
 /// fail (see `CompiledHttpRequest::as_hyper` / `as_my_http_client_request`).
 pub fn get_http_connection_key(params: &ConnectionParams<'_>) -> String {
     format!(
-        "{}|{}",
+        "{}|{}{}",
         params.remote_endpoint.get_host_port().as_str(),
-        mode_tag(params.mode)
+        mode_tag(params.mode),
+        keep_alive_tag(params.mode, params.h2_keep_alive)
     )
 }
 
 /// For HTTPS the TLS identity is baked into the connector at creation, so the
-/// key also includes the SNI server name and the client certificate — otherwise
-/// requests with different identities would silently share a handshake.
+/// key also includes the SNI server name, the client certificate, the trust
+/// anchors, the certificate pins, the verifier hook, the version/cipher policy
+/// and the key log — otherwise requests with
+/// different identities would silently share a handshake, and a pinned request
+/// could be served over a connection that was never checked against its pins.
+/// A verifier hook has no identity of its own, so it is keyed by its `Arc`: share
+/// one `Arc` between requests for them to share connections.
 #[cfg(feature = "_tls")]
 pub fn get_https_connection_key(params: &ConnectionParams<'_>) -> String {
-    let cert_tag = match params.client_certificate {
-        Some(cert) => {
-            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-            for der in &cert.cert_chain {
-                der.as_ref().hash(&mut hasher);
-            }
-            format!("{:016x}", hasher.finish())
-        }
-        None => "nocert".to_string(),
+    let cert_tag = match (params.client_identity.as_ref(), params.client_certificate) {
+        (Some(identity), _) => format!("{:016x}", identity.get_fingerprint()),
+        (None, Some(cert)) => format!(
+            "{:016x}",
+            crate::non_wasm::tls::get_client_certificate_fingerprint(cert)
+        ),
+        (None, None) => "nocert".to_string(),
+    };
+
+    let roots_tag = match params.root_certificates.as_ref() {
+        Some(root_certificates) => format!("{:016x}", root_certificates.get_fingerprint()),
+        None => "sysroots".to_string(),
+    };
+
+    let pins_tag = match params.certificate_pins.as_ref() {
+        Some(certificate_pins) => format!("pins{:016x}", certificate_pins.get_fingerprint()),
+        None => "nopins".to_string(),
+    };
+
+    let verifier_tag = match params.server_certificate_verifier.as_ref() {
+        Some(verifier) => format!("vfy{:x}", Arc::as_ptr(verifier) as *const () as usize),
+        None => "novfy".to_string(),
+    };
+
+    let policy_tag = match params.tls_policy.as_ref() {
+        Some(tls_policy) => format!("pol{:016x}", tls_policy.get_fingerprint()),
+        None => "nopol".to_string(),
+    };
+
+    let key_log_tag = match params.tls_key_log.as_ref() {
+        Some(key_log) => format!("klog{:016x}", key_log.get_fingerprint()),
+        None => "noklog".to_string(),
     };
 
     format!(
-        "{}|{}|{}|{}",
+        "{}|{}{}|{}|{}|{}|{}|{}|{}|{}",
         params.remote_endpoint.get_host_port().as_str(),
         mode_tag(params.mode),
+        keep_alive_tag(params.mode, params.h2_keep_alive),
         params.get_server_name(),
-        cert_tag
+        cert_tag,
+        roots_tag,
+        pins_tag,
+        verifier_tag,
+        policy_tag,
+        key_log_tag
+    )
+}
+
+/// What a server answered to ALPN depends on the host and on the name it was
+/// asked under, not on the client identity or the mode of the request.
+#[cfg(feature = "_tls")]
+pub fn get_alpn_key(params: &ConnectionParams<'_>) -> String {
+    format!(
+        "{}|{}",
+        params.remote_endpoint.get_host_port().as_str(),
+        params.get_server_name()
     )
 }
 
 #[cfg(unix)]
 pub fn get_unix_socket_connection_key(params: &ConnectionParams<'_>) -> String {
     format!(
-        "{}|{}",
+        "{}|{}{}",
         params.remote_endpoint.get_host(),
-        mode_tag(params.mode)
+        mode_tag(params.mode),
+        keep_alive_tag(params.mode, params.h2_keep_alive)
     )
 }
 
@@ -65,15 +128,17 @@ pub fn get_ssh_connection_key(
     ssh_credentials: &my_ssh::SshCredentials,
     remote_endpoint: RemoteEndpoint,
     mode: crate::FlUrlMode,
+    h2_keep_alive: Option<H2KeepAlive>,
 ) -> String {
     let (host, port) = ssh_credentials.get_host_port();
 
     format!(
-        "{}@{}:{}->{}|{}",
+        "{}@{}:{}->{}|{}{}",
         ssh_credentials.get_user_name(),
         host,
         port,
         remote_endpoint.get_host_port().as_str(),
-        mode_tag(mode)
+        mode_tag(mode),
+        keep_alive_tag(mode, h2_keep_alive)
     )
 }
@@ -1,42 +1,58 @@
 use std::sync::Arc;
 
 use my_http_client::{MyHttpClientConnector, MyHttpClientError};
-use my_tls::{
-    tokio_rustls::{client::TlsStream, TlsConnector},
-    ClientCertificate,
-};
+use my_tls::tokio_rustls::{client::TlsStream, rustls::ClientConfig, TlsConnector};
+use parking_lot::Mutex;
 use rust_extensions::remote_endpoint::{RemoteEndpoint, RemoteEndpointOwned};
 use tokio::net::TcpStream;
 
+use crate::non_wasm::tls::TlsClientSettings;
+
 pub struct HttpsConnector {
     pub remote_host: RemoteEndpointOwned,
     pub server_name: String,
-    pub client_certificate: Option<ClientCertificate>,
-    pub accept_invalid_certificate: bool,
+    pub tls_settings: TlsClientSettings,
     h2: bool,
+    // The connection `probe_h2_support` opened, until `connect` hands it out.
+    probed_stream: Mutex<Option<TlsStream<TcpStream>>>,
 }
 
 impl HttpsConnector {
     pub fn new(
         remote_host: RemoteEndpointOwned,
         server_name: String,
-        client_certificate: Option<ClientCertificate>,
-        accept_invalid_certificate: bool,
+        tls_settings: TlsClientSettings,
         h2: bool,
     ) -> Self {
         Self {
             remote_host,
             server_name,
-            client_certificate,
-            accept_invalid_certificate,
+            tls_settings,
             h2,
+            probed_stream: Mutex::new(None),
         }
     }
-}
 
-#[async_trait::async_trait]
-impl MyHttpClientConnector<TlsStream<TcpStream>> for HttpsConnector {
-    async fn connect(&self) -> Result<TlsStream<TcpStream>, MyHttpClientError> {
+    /// Opens one TLS connection offering `h2, http/1.1` and reports whether the
+    /// server picked `h2`. From then on the connector speaks what was picked, and
+    /// the connection is not wasted: the first `connect` hands it out instead of
+    /// opening another one.
+    pub async fn probe_h2_support(&mut self) -> Result<bool, MyHttpClientError> {
+        let tls_stream = self
+            .connect_with_alpn(vec![b"h2".to_vec(), b"http/1.1".to_vec()])
+            .await?;
+
+        let (_, session) = tls_stream.get_ref();
+        self.h2 = session.alpn_protocol() == Some(b"h2".as_slice());
+        *self.probed_stream.get_mut() = Some(tls_stream);
+
+        Ok(self.h2)
+    }
+
+    async fn connect_with_alpn(
+        &self,
+        alpn_protocols: Vec<Vec<u8>>,
+    ) -> Result<TlsStream<TcpStream>, MyHttpClientError> {
         let host_port = self.remote_host.get_host_port();
         let connect_result = TcpStream::connect(host_port.as_str()).await;
 
@@ -51,24 +67,25 @@ impl MyHttpClientConnector<TlsStream<TcpStream>> for HttpsConnector {
 
         let tcp_stream = connect_result.unwrap();
 
-        let client_config = my_tls::create_tls_client_config_ex(
-            &self.client_certificate,
-            self.accept_invalid_certificate,
-        );
+        let client_config = self.tls_settings.get_client_config();
 
         if let Err(err) = client_config {
             return Err(
                 my_http_client::MyHttpClientError::CanNotConnectToRemoteHost(format!(
-                    "{}. Err:{}",
-                    host_port, err
+                    "{}{}. Host:{}",
+                    crate::non_wasm::tls::INVALID_TLS_CONFIGURATION_ERROR_PREFIX,
+                    err,
+                    host_port
                 )),
             );
         }
 
-        let mut client_config = client_config.unwrap();
+        // A clone shares the resumption store with the cached config, so the
+        // session tickets of this connection serve the next one.
+        let mut client_config = ClientConfig::clone(&client_config.unwrap());
 
-        if self.h2 {
-            client_config.alpn_protocols = vec![b"h2".to_vec()];
+        if !alpn_protocols.is_empty() {
+            client_config.alpn_protocols = alpn_protocols;
         }
 
         let connector = TlsConnector::from(Arc::new(client_config));
@@ -87,15 +104,46 @@ impl MyHttpClientConnector<TlsStream<TcpStream>> for HttpsConnector {
             }
         };
 
-        match connector.connect(server_name, tcp_stream).await {
-            Ok(tls_stream) => Ok(tls_stream),
-            Err(err) => Err(
-                my_http_client::MyHttpClientError::CanNotConnectToRemoteHost(format!(
-                    "{}. Err:{}",
-                    host_port, err
-                )),
-            ),
+        let tls_stream = match connector.connect(server_name, tcp_stream).await {
+            Ok(tls_stream) => tls_stream,
+            Err(err) => {
+                return Err(
+                    my_http_client::MyHttpClientError::CanNotConnectToRemoteHost(format!(
+                        "{}. Err:{}",
+                        host_port, err
+                    )),
+                );
+            }
+        };
+
+        if let Some(certificate_pins) = self.tls_settings.certificate_pins.as_ref() {
+            let (_, session) = tls_stream.get_ref();
+            let peer_certificates = session.peer_certificates().unwrap_or_default();
+
+            if let Err(err) = certificate_pins.check(&self.server_name, peer_certificates) {
+                return Err(my_http_client::MyHttpClientError::CanNotConnectToRemoteHost(err));
+            }
+        }
+
+        Ok(tls_stream)
+    }
+}
+
+#[async_trait::async_trait]
+impl MyHttpClientConnector<TlsStream<TcpStream>> for HttpsConnector {
+    async fn connect(&self) -> Result<TlsStream<TcpStream>, MyHttpClientError> {
+        let probed_stream = self.probed_stream.lock().take();
+        if let Some(tls_stream) = probed_stream {
+            return Ok(tls_stream);
         }
+
+        let alpn_protocols = if self.h2 {
+            vec![b"h2".to_vec()]
+        } else {
+            vec![]
+        };
+
+        self.connect_with_alpn(alpn_protocols).await
     }
 
     fn get_remote_endpoint<'s>(&'s self) -> RemoteEndpoint<'s> {
@@ -11,26 +11,49 @@
 
 use std::sync::Arc;
 
+mod bandwidth_limit;
 mod compiled_http_request;
+mod decoding_body;
+mod encoding_body;
 mod escaped_body_guard;
+mod file_body;
+mod file_download;
 mod fl_response;
+mod fl_response_as_multipart;
 mod fl_response_as_stream;
+mod fl_response_async_read;
 mod fl_url;
+mod fl_url_event_stream;
 mod fl_url_headers;
+mod form_data_stream;
 mod http_clients_cache;
 mod http_connectors;
 mod into_fl_url;
 mod model_body_stream;
 mod my_http_client_wrapper;
+mod parallel_download;
+mod progress;
 mod response_body;
+#[cfg(test)]
+mod test_utils;
+#[cfg(feature = "_tls")]
+mod tls;
 
+pub use file_download::*;
 pub use fl_response::*;
+pub use fl_response_as_multipart::*;
 pub use fl_response_as_stream::*;
+pub use fl_response_async_read::*;
 pub use fl_url::{FlUrl, FlUrlMode, HttpVerb};
+pub use fl_url_event_stream::*;
 pub use fl_url_headers::*;
+pub use form_data_stream::{FormDataPart, FormDataStream};
 pub use http_clients_cache::*;
 pub use into_fl_url::*;
+pub use my_http_client_wrapper::H2KeepAlive;
 pub use response_body::*;
+#[cfg(feature = "_tls")]
+pub use tls::*;
 
 pub extern crate hyper;
 
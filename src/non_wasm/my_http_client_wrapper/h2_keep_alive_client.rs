@@ -0,0 +1,183 @@
+use std::{
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::client::conn::http2::SendRequest;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use my_http_client::{MyHttpClientConnector, MyHttpClientError};
+use parking_lot::Mutex;
+
+/// HTTP/2 keep-alive: a PING frame goes out every `interval`, and a connection
+/// whose PING is not acknowledged within `ack_timeout` is treated as dead.
+///
+/// PINGs are sent on idle connections too — an idle flow is exactly what a NAT
+/// gateway or a load balancer drops without telling either side, and a dead
+/// connection is only harmless while nothing is sent over it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct H2KeepAlive {
+    pub interval: Duration,
+    pub ack_timeout: Duration,
+}
+
+impl H2KeepAlive {
+    pub fn new(interval: Duration, ack_timeout: Duration) -> Self {
+        Self {
+            interval,
+            ack_timeout,
+        }
+    }
+}
+
+struct H2Connection {
+    sender: SendRequest<Full<Bytes>>,
+    closed: Arc<AtomicBool>,
+}
+
+impl H2Connection {
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed) || self.sender.is_closed()
+    }
+}
+
+/// The H2 client used when [`H2KeepAlive`] is configured. my-http-client's
+/// `MyHttp2Client` has no PING settings, so this one drives hyper's HTTP/2
+/// connection directly over the same connectors and reports the same errors.
+///
+/// The connection is established on the first request. When the connection task
+/// ends — PING ack timeout, GOAWAY, a transport error — the client reports itself
+/// dead through [`Self::is_dead`], and `FlUrlHttpConnectionsCache` evicts it at
+/// the next checkout or `gc` instead of handing it to a request.
+pub struct H2KeepAliveClient<
+    TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
+    TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+> {
+    connector: TConnector,
+    keep_alive: H2KeepAlive,
+    connection: Mutex<Option<H2Connection>>,
+    connect_gate: tokio::sync::Mutex<()>,
+    _stream: PhantomData<TStream>,
+}
+
+impl<
+        TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
+        TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+    > H2KeepAliveClient<TStream, TConnector>
+{
+    pub fn new(connector: TConnector, keep_alive: H2KeepAlive) -> Self {
+        Self {
+            connector,
+            keep_alive,
+            connection: Mutex::new(None),
+            connect_gate: tokio::sync::Mutex::new(()),
+            _stream: PhantomData,
+        }
+    }
+
+    /// `true` once an established connection has gone away. A client that has
+    /// not connected yet is not dead — it connects on its first request.
+    pub fn is_dead(&self) -> bool {
+        match self.connection.lock().as_ref() {
+            Some(connection) => connection.is_closed(),
+            None => false,
+        }
+    }
+
+    pub async fn connect(&self) -> Result<(), MyHttpClientError> {
+        self.get_sender().await?;
+        Ok(())
+    }
+
+    pub async fn do_request(
+        &self,
+        request: &my_http_client::http::request::Request<Full<Bytes>>,
+        request_timeout: Duration,
+    ) -> Result<my_http_client::HyperResponse, MyHttpClientError> {
+        let future = async {
+            let mut sender = self.get_sender().await?;
+
+            let response = sender
+                .send_request(request.clone())
+                .await
+                .map_err(|err| MyHttpClientError::CanNotExecuteRequest(err.to_string()))?;
+
+            Ok(response.map(|body| body.map_err(|err| err.to_string()).boxed()))
+        };
+
+        match tokio::time::timeout(request_timeout, future).await {
+            Ok(result) => result,
+            Err(_) => Err(MyHttpClientError::RequestTimeout(request_timeout)),
+        }
+    }
+
+    async fn get_sender(&self) -> Result<SendRequest<Full<Bytes>>, MyHttpClientError> {
+        if let Some(sender) = self.get_open_sender() {
+            return Ok(sender);
+        }
+
+        // One handshake at a time: concurrent first requests wait for it and then
+        // share the connection, instead of each opening its own.
+        let _gate = self.connect_gate.lock().await;
+
+        if let Some(sender) = self.get_open_sender() {
+            return Ok(sender);
+        }
+
+        let stream = self.connector.connect().await?;
+
+        let (sender, connection) = hyper::client::conn::http2::Builder::new(TokioExecutor::new())
+            .timer(TokioTimer::new())
+            .keep_alive_interval(self.keep_alive.interval)
+            .keep_alive_timeout(self.keep_alive.ack_timeout)
+            .keep_alive_while_idle(true)
+            .handshake(TokioIo::new(stream))
+            .await
+            .map_err(|err| {
+                MyHttpClientError::CanNotConnectToRemoteHost(format!(
+                    "{}. H2 handshake failed: {}",
+                    self.connector
+                        .get_remote_endpoint()
+                        .get_host_port()
+                        .as_str(),
+                    err
+                ))
+            })?;
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let closed_by_task = closed.clone();
+        let is_debug = self.connector.is_debug();
+
+        tokio::spawn(async move {
+            if let Err(err) = connection.await {
+                if is_debug {
+                    println!("H2 connection closed: {}", err);
+                }
+            }
+            closed_by_task.store(true, Ordering::Relaxed);
+        });
+
+        *self.connection.lock() = Some(H2Connection {
+            sender: sender.clone(),
+            closed,
+        });
+
+        Ok(sender)
+    }
+
+    fn get_open_sender(&self) -> Option<SendRequest<Full<Bytes>>> {
+        let connection = self.connection.lock();
+        let connection = connection.as_ref()?;
+
+        if connection.is_closed() {
+            return None;
+        }
+
+        Some(connection.sender.clone())
+    }
+}
@@ -0,0 +1,281 @@
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use my_http_client::{MyHttpClientConnector, MyHttpClientError};
+use rust_extensions::remote_endpoint::RemoteEndpoint;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+// A SETTINGS frame bigger than this is not kept to be read: h2 refuses any frame
+// above the default 16 KiB `SETTINGS_MAX_FRAME_SIZE` anyway.
+const MAX_SETTINGS_PAYLOAD_SIZE: usize = 16 * 1024;
+
+// `SETTINGS_MAX_CONCURRENT_STREAMS` as the server has not announced it.
+const NOT_ANNOUNCED: usize = usize::MAX;
+
+/// The server's `SETTINGS_MAX_CONCURRENT_STREAMS` on the current connection of
+/// an H2 client, shared between the connector that reads it and the pool that
+/// scales out on it.
+#[derive(Clone)]
+pub struct H2PeerSettings {
+    max_concurrent_streams: Arc<AtomicUsize>,
+}
+
+impl Default for H2PeerSettings {
+    fn default() -> Self {
+        Self {
+            max_concurrent_streams: Arc::new(AtomicUsize::new(NOT_ANNOUNCED)),
+        }
+    }
+}
+
+impl H2PeerSettings {
+    /// `None` until the server has announced a value on the current connection.
+    pub fn get_max_concurrent_streams(&self) -> Option<usize> {
+        let max_concurrent_streams = self.max_concurrent_streams.load(Ordering::Relaxed);
+        (max_concurrent_streams != NOT_ANNOUNCED).then_some(max_concurrent_streams)
+    }
+}
+
+/// The connector of an H2 client, wrapped so every stream it makes follows the
+/// frames the server sends. Neither `MyHttp2Client` nor hyper hand out the
+/// server's SETTINGS, so `SETTINGS_MAX_CONCURRENT_STREAMS` is picked up here, on
+/// the way in.
+pub struct PeerSettingsConnector<TConnector> {
+    connector: TConnector,
+    peer_settings: H2PeerSettings,
+}
+
+impl<TConnector> PeerSettingsConnector<TConnector> {
+    pub fn new(connector: TConnector, peer_settings: H2PeerSettings) -> Self {
+        Self {
+            connector,
+            peer_settings,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<
+        TStream: AsyncRead + AsyncWrite + Unpin + Send + Sync + 'static,
+        TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
+    > MyHttpClientConnector<PeerSettingsStream<TStream>> for PeerSettingsConnector<TConnector>
+{
+    async fn connect(&self) -> Result<PeerSettingsStream<TStream>, MyHttpClientError> {
+        let stream = self.connector.connect().await?;
+
+        // A new connection: what the one before announced no longer holds.
+        let max_concurrent_streams = self.peer_settings.max_concurrent_streams.clone();
+        max_concurrent_streams.store(NOT_ANNOUNCED, Ordering::Relaxed);
+
+        Ok(PeerSettingsStream {
+            stream,
+            scanner: FrameScanner::new(max_concurrent_streams),
+        })
+    }
+
+    fn get_remote_endpoint<'s>(&'s self) -> RemoteEndpoint<'s> {
+        self.connector.get_remote_endpoint()
+    }
+
+    fn is_debug(&self) -> bool {
+        self.connector.is_debug()
+    }
+
+    fn reunite(
+        read: tokio::io::ReadHalf<PeerSettingsStream<TStream>>,
+        write: tokio::io::WriteHalf<PeerSettingsStream<TStream>>,
+    ) -> PeerSettingsStream<TStream> {
+        read.unsplit(write)
+    }
+}
+
+/// A connector's stream as the H2 connection reads it, with the frames the
+/// server sends followed along.
+pub struct PeerSettingsStream<TStream> {
+    stream: TStream,
+    scanner: FrameScanner,
+}
+
+impl<TStream: AsyncRead + Unpin> AsyncRead for PeerSettingsStream<TStream> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled_before = buf.filled().len();
+
+        let result = Pin::new(&mut this.stream).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = &result {
+            this.scanner.scan(&buf.filled()[filled_before..]);
+        }
+
+        result
+    }
+}
+
+impl<TStream: AsyncWrite + Unpin> AsyncWrite for PeerSettingsStream<TStream> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.get_mut().stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.get_mut().stream).poll_shutdown(cx)
+    }
+}
+
+/// Follows the frames of an H2 byte stream from their 9-byte headers, and reads
+/// the payload of SETTINGS frames for `SETTINGS_MAX_CONCURRENT_STREAMS`. The
+/// server's first frame is its SETTINGS; a later one may change the value.
+struct FrameScanner {
+    header: [u8; 9],
+    header_len: usize,
+    // Payload bytes of the current frame still to come.
+    remaining: usize,
+    // Collected while the current frame is a SETTINGS frame that is not an ACK.
+    settings: Option<Vec<u8>>,
+    max_concurrent_streams: Arc<AtomicUsize>,
+}
+
+impl FrameScanner {
+    const SETTINGS_FRAME: u8 = 0x4;
+    const ACK_FLAG: u8 = 0x1;
+    const SETTINGS_MAX_CONCURRENT_STREAMS: u16 = 0x3;
+
+    fn new(max_concurrent_streams: Arc<AtomicUsize>) -> Self {
+        Self {
+            header: [0; 9],
+            header_len: 0,
+            remaining: 0,
+            settings: None,
+            max_concurrent_streams,
+        }
+    }
+
+    fn scan(&mut self, mut bytes: &[u8]) {
+        while !bytes.is_empty() {
+            if self.header_len < self.header.len() {
+                let taken = (self.header.len() - self.header_len).min(bytes.len());
+                self.header[self.header_len..self.header_len + taken]
+                    .copy_from_slice(&bytes[..taken]);
+                self.header_len += taken;
+                bytes = &bytes[taken..];
+
+                if self.header_len == self.header.len() {
+                    self.start_frame();
+                }
+                continue;
+            }
+
+            let taken = self.remaining.min(bytes.len());
+            if let Some(settings) = self.settings.as_mut() {
+                settings.extend_from_slice(&bytes[..taken]);
+            }
+            self.remaining -= taken;
+            bytes = &bytes[taken..];
+
+            if self.remaining == 0 {
+                self.end_frame();
+            }
+        }
+    }
+
+    fn start_frame(&mut self) {
+        let [len_0, len_1, len_2, frame_type, flags, ..] = self.header;
+        self.remaining = u32::from_be_bytes([0, len_0, len_1, len_2]) as usize;
+
+        let is_settings = frame_type == Self::SETTINGS_FRAME
+            && flags & Self::ACK_FLAG == 0
+            && self.remaining <= MAX_SETTINGS_PAYLOAD_SIZE;
+        self.settings = is_settings.then(Vec::new);
+
+        if self.remaining == 0 {
+            self.end_frame();
+        }
+    }
+
+    fn end_frame(&mut self) {
+        self.header_len = 0;
+
+        let Some(settings) = self.settings.take() else {
+            return;
+        };
+
+        for entry in settings.chunks_exact(6) {
+            let id = u16::from_be_bytes([entry[0], entry[1]]);
+            if id == Self::SETTINGS_MAX_CONCURRENT_STREAMS {
+                let value = u32::from_be_bytes([entry[2], entry[3], entry[4], entry[5]]);
+                self.max_concurrent_streams
+                    .store(value as usize, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(frame_type: u8, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let len = (payload.len() as u32).to_be_bytes();
+        let mut frame = vec![len[1], len[2], len[3], frame_type, flags, 0, 0, 0, 0];
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn setting(id: u16, value: u32) -> Vec<u8> {
+        let mut entry = id.to_be_bytes().to_vec();
+        entry.extend_from_slice(&value.to_be_bytes());
+        entry
+    }
+
+    #[test]
+    fn max_concurrent_streams_is_read_off_settings_frames_cut_anywhere() {
+        let peer_settings = H2PeerSettings::default();
+        let mut scanner = FrameScanner::new(peer_settings.max_concurrent_streams.clone());
+
+        let mut settings = setting(0x4, 65_535);
+        settings.extend(setting(0x3, 10));
+
+        let mut bytes = frame(0x4, 0, &settings);
+        // A DATA frame whose payload looks like a SETTINGS entry, and an ACK.
+        bytes.extend(frame(0x0, 0, &setting(0x3, 1)));
+        bytes.extend(frame(0x4, 0x1, &[]));
+
+        assert_eq!(peer_settings.get_max_concurrent_streams(), None);
+        for byte in &bytes {
+            scanner.scan(std::slice::from_ref(byte));
+        }
+        assert_eq!(peer_settings.get_max_concurrent_streams(), Some(10));
+
+        // A later SETTINGS frame changes the value.
+        scanner.scan(&frame(0x4, 0, &setting(0x3, 3)));
+        assert_eq!(peer_settings.get_max_concurrent_streams(), Some(3));
+    }
+}
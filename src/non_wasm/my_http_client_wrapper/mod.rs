@@ -2,3 +2,7 @@ mod my_http_client_wrapper_inner;
 pub use my_http_client_wrapper_inner::*;
 mod my_http_client_wrapper;
 pub use my_http_client_wrapper::*;
+mod h2_keep_alive_client;
+pub use h2_keep_alive_client::*;
+mod h2_peer_settings;
+pub use h2_peer_settings::*;
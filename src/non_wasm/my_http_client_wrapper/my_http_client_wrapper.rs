@@ -1,4 +1,7 @@
-use std::time::Duration;
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
 
 use my_http_client::{http1::MyHttpResponse, MyHttpClientConnector, MyHttpClientError};
 
@@ -12,6 +15,9 @@ pub struct MyHttpClientWrapper<
 > {
     pub key: String,
     inner: MyHttpClientWrapperInner<TStream, TConnector>,
+    // Requests the pool has handed this shared H2 connection to that have not
+    // ended yet; see `FlUrlHttpConnectionsCache`.
+    streams_in_flight: AtomicUsize,
 }
 
 impl<
@@ -20,13 +26,37 @@ impl<
     > MyHttpClientWrapper<TStream, TConnector>
 {
     pub fn new(key: String, inner: MyHttpClientWrapperInner<TStream, TConnector>) -> Self {
-        Self { key, inner }
+        Self {
+            key,
+            inner,
+            streams_in_flight: AtomicUsize::new(0),
+        }
     }
 
     pub fn is_h2(&self) -> bool {
         self.inner.is_h2()
     }
 
+    pub fn is_dead(&self) -> bool {
+        self.inner.is_dead()
+    }
+
+    pub fn get_h2_max_concurrent_streams(&self) -> Option<usize> {
+        self.inner.get_h2_max_concurrent_streams()
+    }
+
+    pub(crate) fn get_streams_in_flight(&self) -> usize {
+        self.streams_in_flight.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn start_stream(&self) {
+        self.streams_in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn end_stream(&self) {
+        self.streams_in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
     pub async fn do_request(
         &self,
         request: &CompiledHttpRequest,
@@ -54,7 +84,8 @@ impl<
                 my_http_client.connect().await
             }
             MyHttpClientWrapperInner::Hyper(my_http_client) => my_http_client.connect().await,
-            MyHttpClientWrapperInner::H2(my_http_client) => my_http_client.connect().await,
+            MyHttpClientWrapperInner::H2(my_http_client, _) => my_http_client.connect().await,
+            MyHttpClientWrapperInner::H2KeepAlive(client, _) => client.connect().await,
         }
     }
 }
@@ -4,13 +4,25 @@ use my_http_client::{http1::MyHttpResponse, MyHttpClientConnector, MyHttpClientE
 
 use crate::non_wasm::compiled_http_request::CompiledHttpRequest;
 
+use super::{H2KeepAlive, H2PeerSettings, PeerSettingsConnector, PeerSettingsStream};
+
 pub enum MyHttpClientWrapperInner<
     TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
     TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
 > {
     MyHttpClient(my_http_client::http1::MyHttpClient<TStream, TConnector>),
     Hyper(my_http_client::http1_hyper::MyHttpHyperClient<TStream, TConnector>),
-    H2(my_http_client::http2::MyHttp2Client<TStream, TConnector>),
+    H2(
+        my_http_client::http2::MyHttp2Client<
+            PeerSettingsStream<TStream>,
+            PeerSettingsConnector<TConnector>,
+        >,
+        H2PeerSettings,
+    ),
+    H2KeepAlive(
+        super::H2KeepAliveClient<PeerSettingsStream<TStream>, PeerSettingsConnector<TConnector>>,
+        H2PeerSettings,
+    ),
 }
 
 impl<
@@ -18,8 +30,44 @@ impl<
         TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
     > MyHttpClientWrapperInner<TStream, TConnector>
 {
+    /// An H2 client over `connector`, with PINGs when `keep_alive` is set.
+    pub fn new_h2(connector: TConnector, keep_alive: Option<H2KeepAlive>) -> Self {
+        let peer_settings = H2PeerSettings::default();
+        let connector = PeerSettingsConnector::new(connector, peer_settings.clone());
+
+        match keep_alive {
+            Some(keep_alive) => Self::H2KeepAlive(
+                super::H2KeepAliveClient::new(connector, keep_alive),
+                peer_settings,
+            ),
+            None => Self::H2(
+                my_http_client::http2::MyHttp2Client::new(connector),
+                peer_settings,
+            ),
+        }
+    }
+
     pub fn is_h2(&self) -> bool {
-        matches!(self, Self::H2(_))
+        matches!(self, Self::H2(..) | Self::H2KeepAlive(..))
+    }
+
+    /// Only the keep-alive H2 client learns about a dead peer on its own; the
+    /// other clients find out when a request fails.
+    pub fn is_dead(&self) -> bool {
+        match self {
+            Self::H2KeepAlive(client, _) => client.is_dead(),
+            _ => false,
+        }
+    }
+
+    /// What the server allows on an H2 connection, once it has said so.
+    pub fn get_h2_max_concurrent_streams(&self) -> Option<usize> {
+        match self {
+            Self::H2(_, peer_settings) | Self::H2KeepAlive(_, peer_settings) => {
+                peer_settings.get_max_concurrent_streams()
+            }
+            _ => None,
+        }
     }
 
     pub async fn do_request(
@@ -61,12 +109,19 @@ impl<
                 }
             }
 
-            Self::H2(my_http_client) => {
+            Self::H2(my_http_client, _) => {
                 let result = my_http_client
                     .do_request(request.as_hyper(), request_timeout)
                     .await?;
                 Ok(MyHttpResponse::Response(result))
             }
+
+            Self::H2KeepAlive(client, _) => {
+                let result = client
+                    .do_request(request.as_hyper(), request_timeout)
+                    .await?;
+                Ok(MyHttpResponse::Response(result))
+            }
         }
     }
 
@@ -93,7 +148,7 @@ impl<
                     }
                 }
             }
-            Self::MyHttpClient(_) | Self::H2(_) => {
+            Self::MyHttpClient(_) | Self::H2(..) | Self::H2KeepAlive(..) => {
                 Err(MyHttpClientError::CanNotExecuteRequest(
                     "A streamed request body requires FlUrlMode::Http1Hyper".to_string(),
                 ))
@@ -123,14 +178,3 @@ impl<
         MyHttpClientWrapperInner::Hyper(client)
     }
 }
-
-impl<
-        TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + 'static,
-        TConnector: MyHttpClientConnector<TStream> + Send + Sync + 'static,
-    > From<my_http_client::http2::MyHttp2Client<TStream, TConnector>>
-    for MyHttpClientWrapperInner<TStream, TConnector>
-{
-    fn from(client: my_http_client::http2::MyHttp2Client<TStream, TConnector>) -> Self {
-        MyHttpClientWrapperInner::H2(client)
-    }
-}
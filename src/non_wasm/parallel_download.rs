@@ -0,0 +1,229 @@
+use std::{
+    io::SeekFrom,
+    path::{Path, PathBuf},
+};
+
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::{
+    non_wasm::file_download::{get_content_range, get_validator, hash_file, FileSink},
+    FlUrl, FlUrlError, SaveToFileOptions, SavedFile,
+};
+
+/// One `Range` request of a parallel download: `start..=end` of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    start: u64,
+    end: u64,
+}
+
+impl Segment {
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+}
+
+/// Cuts `total` bytes into ranges of `segment_size`, the last one shorter.
+fn split_into_segments(total: u64, segment_size: u64) -> Vec<Segment> {
+    let segment_size = segment_size.max(1);
+
+    (0..total)
+        .step_by(segment_size as usize)
+        .map(|start| Segment {
+            start,
+            end: (start + segment_size).min(total) - 1,
+        })
+        .collect()
+}
+
+pub(crate) async fn download_parallel(
+    fl_url: FlUrl,
+    path: &Path,
+    segments: usize,
+    segment_size: u64,
+    options: SaveToFileOptions,
+) -> Result<SavedFile, FlUrlError> {
+    let probe = fl_url.fork_for_download().head().await?;
+
+    let total = probe
+        .get_header_case_insensitive("content-length")
+        .ok()
+        .flatten()
+        .and_then(|value| value.trim().parse::<u64>().ok());
+
+    let accepts_ranges = probe
+        .get_header_case_insensitive("accept-ranges")
+        .ok()
+        .flatten()
+        .map(|value| value.trim().eq_ignore_ascii_case("bytes"))
+        .unwrap_or(false);
+
+    let validator = get_validator(&probe);
+    let probe_status = probe.get_status_code();
+    drop(probe);
+
+    let total = match total {
+        Some(total) if probe_status == 200 && accepts_ranges && total > segment_size.max(1) => {
+            total
+        }
+        // Nothing to split — or a HEAD the server does not answer (405, 403, …),
+        // which says nothing about the GET: one GET, streamed to the file like
+        // `save_to_file`.
+        _ => {
+            return fl_url
+                .fork_for_download()
+                .get()
+                .await?
+                .save_to_file_with(path, options)
+                .await;
+        }
+    };
+
+    // The bytes land out of order, so the hash can only be taken at the end.
+    let mut sink = FileSink::create(path, &options.set_sha256(false)).await?;
+    let temp_path = sink.preallocate(total).await?;
+
+    let mut pending = split_into_segments(total, segment_size).into_iter();
+    let mut running = tokio::task::JoinSet::new();
+    let mut received = 0;
+
+    loop {
+        while running.len() < segments.max(1) {
+            let Some(segment) = pending.next() else {
+                break;
+            };
+
+            running.spawn(download_segment(
+                fl_url.fork_for_download(),
+                temp_path.clone(),
+                segment,
+                validator.clone(),
+            ));
+        }
+
+        let Some(finished) = running.join_next().await else {
+            break;
+        };
+
+        // An error drops `running`, which aborts the other segments, and the
+        // sink, which removes the temp file.
+        received += match finished {
+            Ok(result) => result?,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => return Err(FlUrlError::DownloadMismatch(err.to_string())),
+        };
+    }
+
+    if received != total {
+        return Err(FlUrlError::DownloadMismatch(format!(
+            "Received {} bytes of the {} announced",
+            received, total
+        )));
+    }
+
+    let mut saved = sink.commit().await?;
+
+    if options.sha256 {
+        let hasher = hash_file(&saved.path, total).await?;
+        saved.sha256 = Some(sha2::Digest::finalize(hasher).into());
+    }
+
+    Ok(saved)
+}
+
+/// GETs one range and writes it at its offset, on its own file handle.
+async fn download_segment(
+    fl_url: FlUrl,
+    temp_path: PathBuf,
+    segment: Segment,
+    validator: Option<String>,
+) -> Result<u64, FlUrlError> {
+    let mut request =
+        fl_url.with_header("Range", format!("bytes={}-{}", segment.start, segment.end));
+
+    // A file that changes on the server mid-way comes back whole instead of as
+    // a range, rather than as bytes of another version.
+    if let Some(validator) = validator.as_deref() {
+        request = request.with_header("If-Range", validator);
+    }
+
+    let response = request.get().await?;
+
+    match response.get_status_code() {
+        206 => {}
+        200 => {
+            return Err(FlUrlError::DownloadMismatch(
+                "The server answered a range with the whole file: it changed mid-way, or does not serve ranges"
+                    .to_string(),
+            ))
+        }
+        status_code => return Err(FlUrlError::UnexpectedStatusCode(status_code)),
+    }
+
+    let start = get_content_range(&response).and_then(|range| range.start);
+    if start != Some(segment.start) {
+        return Err(FlUrlError::DownloadMismatch(format!(
+            "Asked for bytes {}-{}, got a range starting at {:?}",
+            segment.start, segment.end, start
+        )));
+    }
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .open(&temp_path)
+        .await?;
+    file.seek(SeekFrom::Start(segment.start)).await?;
+
+    let mut stream = response.get_body_as_stream();
+    let mut written = 0;
+
+    while let Some(chunk) = stream.get_next_bytes().await? {
+        if written + chunk.len() as u64 > segment.len() {
+            return Err(FlUrlError::DownloadMismatch(format!(
+                "Bytes {}-{} came back longer than asked for",
+                segment.start, segment.end
+            )));
+        }
+
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+
+    file.flush().await?;
+
+    if written != segment.len() {
+        return Err(FlUrlError::DownloadMismatch(format!(
+            "Bytes {}-{} came back {} bytes long",
+            segment.start, segment.end, written
+        )));
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_file_is_cut_into_ranges_the_last_one_shorter() {
+        assert_eq!(
+            split_into_segments(10, 4),
+            vec![
+                Segment { start: 0, end: 3 },
+                Segment { start: 4, end: 7 },
+                Segment { start: 8, end: 9 },
+            ]
+        );
+
+        assert_eq!(
+            split_into_segments(8, 4),
+            vec![Segment { start: 0, end: 3 }, Segment { start: 4, end: 7 }]
+        );
+
+        assert_eq!(
+            split_into_segments(1, 4),
+            vec![Segment { start: 0, end: 0 }]
+        );
+    }
+}
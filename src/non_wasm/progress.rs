@@ -0,0 +1,127 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use bytes::Bytes;
+use hyper::body::{Body, Frame, SizeHint};
+
+/// What `on_upload_progress` / `on_download_progress` hand their callback: the
+/// bytes transferred so far, and the total when it is known up front.
+pub(crate) type ProgressCallback = Arc<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
+/// Counts the bytes of one transfer and reports every step to the callback.
+pub(crate) struct ProgressCounter {
+    callback: ProgressCallback,
+    transferred: u64,
+    total: Option<u64>,
+}
+
+impl ProgressCounter {
+    pub fn new(callback: ProgressCallback, total: Option<u64>) -> Self {
+        Self {
+            callback,
+            transferred: 0,
+            total,
+        }
+    }
+
+    pub fn advance(&mut self, len: usize) {
+        if len == 0 {
+            return;
+        }
+
+        self.transferred += len as u64;
+        (self.callback)(self.transferred, self.total);
+    }
+}
+
+/// A body that reports its data frames to a [`ProgressCounter`] as they pass
+/// through, unchanged — for the streamed request body on its way to the socket
+/// and the response body on its way into memory.
+pub(crate) struct ProgressBody<TBody> {
+    inner: Pin<Box<TBody>>,
+    counter: ProgressCounter,
+}
+
+impl<TBody> ProgressBody<TBody> {
+    pub fn new(inner: TBody, counter: ProgressCounter) -> Self {
+        Self {
+            inner: Box::pin(inner),
+            counter,
+        }
+    }
+}
+
+impl<TBody> Body for ProgressBody<TBody>
+where
+    TBody: Body<Data = Bytes>,
+{
+    type Data = Bytes;
+    type Error = TBody::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.get_mut();
+
+        let frame = match this.inner.as_mut().poll_frame(cx) {
+            Poll::Ready(frame) => frame,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        if let Some(Ok(frame)) = frame.as_ref() {
+            if let Some(data) = frame.data_ref() {
+                this.counter.advance(data.len());
+            }
+        }
+
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// The `Content-Length` of a response, as the total of its download.
+pub(crate) fn get_content_length(headers: &hyper::HeaderMap) -> Option<u64> {
+    headers
+        .get(hyper::header::CONTENT_LENGTH)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[tokio::test]
+    async fn every_data_frame_is_reported_with_the_total() {
+        use http_body_util::BodyExt;
+
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let callback: ProgressCallback = Arc::new(move |transferred, total| {
+            sink.lock().unwrap().push((transferred, total));
+        });
+
+        let inner = http_body_util::Full::new(Bytes::from_static(b"0123456789"));
+        let body = ProgressBody::new(inner, ProgressCounter::new(callback, Some(10)));
+
+        assert_eq!(body.size_hint().exact(), Some(10));
+
+        let collected = body.collect().await.unwrap().to_bytes();
+
+        assert_eq!(&collected[..], b"0123456789");
+        assert_eq!(*reported.lock().unwrap(), vec![(10, Some(10))]);
+    }
+}
@@ -1,10 +1,15 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 use http_body_util::BodyExt;
 use hyper::HeaderMap;
 use my_http_client::HyperResponse;
 
+use crate::non_wasm::bandwidth_limit::{BandwidthLimit, Direction, Throttle, ThrottledBody};
+use crate::non_wasm::progress::{
+    get_content_length, ProgressBody, ProgressCallback, ProgressCounter,
+};
 use crate::{FlUrlError, FlUrlReadingHeaderError};
 
 pub enum ResponseBody {
@@ -136,6 +141,8 @@ impl ResponseBody {
     pub(crate) async fn convert_to_slice_if_needed(
         &mut self,
         body_read_timeout: Option<Duration>,
+        download_progress: Option<ProgressCallback>,
+        bandwidth_limit: Option<Arc<BandwidthLimit>>,
     ) -> Result<(), FlUrlError> {
         match self {
             Self::Hyper(response) => {
@@ -146,6 +153,24 @@ impl ResponseBody {
 
                 let (parts, incoming) = response.into_parts();
 
+                let incoming = match bandwidth_limit {
+                    Some(limit) => {
+                        let throttle = Throttle::new(limit, Direction::Download);
+                        ThrottledBody::new(incoming, Some(throttle)).boxed()
+                    }
+                    None => incoming,
+                };
+
+                // Counted off the wire: with `accept_compression` these are the
+                // encoded bytes, which is what `Content-Length` announces.
+                let incoming = match download_progress {
+                    Some(callback) => {
+                        let total = get_content_length(&parts.headers);
+                        ProgressBody::new(incoming, ProgressCounter::new(callback, total)).boxed()
+                    }
+                    None => incoming,
+                };
+
                 // Written BEFORE the await: if the read future is dropped mid-way
                 // (cancellation) or fails, the enum stays in a valid state —
                 // headers remain reachable and body reads return an error
@@ -170,9 +195,10 @@ impl ResponseBody {
                 };
 
                 // A read error/timeout leaves body: None -> the caller disposes
-                // the connection. A successful read stores the raw body; gzip
-                // decoding is a SEPARATE, body-level step (decode_gzip_if_needed)
-                // so a decode failure never disposes an already-drained socket.
+                // the connection. A successful read stores the raw body;
+                // decompression is a SEPARATE, body-level step
+                // (decode_content_encoding_if_needed) so a decode failure never
+                // disposes an already-drained socket.
                 let body = body_result?;
 
                 if let Self::Body { body: dest, .. } = self {
@@ -185,25 +211,26 @@ impl ResponseBody {
         }
     }
 
-    /// Decodes a gzip-encoded loaded body in place and updates the
-    /// `Content-Encoding` / `Content-Length` headers to match. A pure data-level
-    /// transform: it runs only after the body has been fully read off the wire
-    /// and the connection settled, so a decode error does not affect connection
-    /// reuse.
-    pub(crate) fn decode_gzip_if_needed(&mut self) -> Result<(), FlUrlError> {
+    /// Decodes a loaded body encoded with any of the [`crate::Encoding`]s in
+    /// place — stacked codings (`Content-Encoding: gzip, br`) are undone last to
+    /// first — and updates the `Content-Encoding` / `Content-Length` headers to
+    /// match. A body with a coding FlUrl does not know is left as it came. A pure
+    /// data-level transform: it runs only after the body has been fully read off
+    /// the wire and the connection settled, so a decode error does not affect
+    /// connection reuse.
+    pub(crate) fn decode_content_encoding_if_needed(&mut self) -> Result<(), FlUrlError> {
         let Self::Body { headers, body, .. } = self else {
             return Ok(());
         };
 
-        let is_gzip = headers
+        let encodings = headers
             .get(hyper::header::CONTENT_ENCODING)
             .and_then(|value| value.to_str().ok())
-            .map(|value| value.eq_ignore_ascii_case("gzip"))
-            .unwrap_or(false);
+            .and_then(crate::compression::parse_content_encoding);
 
-        if !is_gzip {
+        let Some(encodings) = encodings else {
             return Ok(());
-        }
+        };
 
         let Some(compressed) = body.as_ref() else {
             return Ok(());
@@ -213,7 +240,16 @@ impl ResponseBody {
             return Ok(());
         }
 
-        let decoded = decompress_gzip_body(compressed.as_slice())?;
+        let mut decoded = None;
+
+        for encoding in encodings.iter().rev() {
+            let input = decoded.as_deref().unwrap_or(compressed.as_slice());
+            decoded = Some(crate::compression::decode(*encoding, input)?);
+        }
+
+        let Some(decoded) = decoded else {
+            return Ok(());
+        };
 
         // The stored headers must describe the body we actually hold, not the
         // compressed wire form.
@@ -229,6 +265,29 @@ impl ResponseBody {
         Ok(())
     }
 
+    /// The streaming counterpart of [`Self::decode_content_encoding_if_needed`]
+    /// for a body that is handed out as it arrives: a decoder for the codings in
+    /// `headers` — `None` when there are none, or one FlUrl does not know — with
+    /// `Content-Encoding` removed. `Content-Length` goes too: the decoded length
+    /// is not known until the body has been read.
+    pub(crate) fn create_stream_decoder(
+        headers: &mut HeaderMap,
+    ) -> Option<crate::compression::StreamDecoder> {
+        let encodings = headers
+            .get(hyper::header::CONTENT_ENCODING)
+            .and_then(|value| value.to_str().ok())
+            .and_then(crate::compression::parse_content_encoding)?;
+
+        if encodings.is_empty() {
+            return None;
+        }
+
+        headers.remove(hyper::header::CONTENT_ENCODING);
+        headers.remove(hyper::header::CONTENT_LENGTH);
+
+        Some(crate::compression::StreamDecoder::new(&encodings))
+    }
+
     /// `true` only when the body has actually been read into memory. The
     /// materialized variant with `body: None` (left behind by a cancelled or
     /// failed read) does NOT count — the socket still carries unread bytes.
@@ -267,12 +326,12 @@ impl ResponseBody {
     }
 
     pub async fn convert_body_and_get_as_slice(&mut self) -> Result<&[u8], FlUrlError> {
-        self.convert_to_slice_if_needed(None).await?;
+        self.convert_to_slice_if_needed(None, None, None).await?;
         self.get_loaded_body_as_slice()
     }
 
     pub async fn convert_body_and_receive_it(&mut self) -> Result<Vec<u8>, FlUrlError> {
-        self.convert_to_slice_if_needed(None).await?;
+        self.convert_to_slice_if_needed(None, None, None).await?;
         self.take_loaded_body()
     }
     pub fn into_http_body(self) -> Result<HyperResponse, FlUrlError> {
@@ -349,20 +408,6 @@ impl ResponseBody {
     }
 }
 
-fn decompress_gzip_body(data: &[u8]) -> Result<Vec<u8>, FlUrlError> {
-    use std::io::Read;
-
-    // MultiGzDecoder (not GzDecoder) so concatenated gzip members — a valid,
-    // spec-allowed encoding some servers emit — are all decoded, not just the
-    // first.
-    let mut decoder = flate2::read::MultiGzDecoder::new(data);
-    let mut result = Vec::new();
-    decoder.read_to_end(&mut result).map_err(|err| {
-        FlUrlError::ReadingHyperBodyError(format!("Failed to decompress gzip body: {}", err))
-    })?;
-    Ok(result)
-}
-
 /*
 fn compile_full_body<TResult>(
     status_code: http::StatusCode,
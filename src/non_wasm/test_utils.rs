@@ -0,0 +1,21 @@
+//! Helpers shared by the unit tests of the native backend.
+
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// A `Stream` over a vec, without pulling in futures-util for `stream::iter`.
+pub(crate) fn stream_of<T: Unpin>(items: Vec<T>) -> impl futures_core::Stream<Item = T> {
+    struct Iter<T>(std::vec::IntoIter<T>);
+
+    impl<T: Unpin> futures_core::Stream for Iter<T> {
+        type Item = T;
+
+        fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<T>> {
+            Poll::Ready(self.get_mut().0.next())
+        }
+    }
+
+    Iter(items.into_iter())
+}
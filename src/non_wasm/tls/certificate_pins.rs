@@ -0,0 +1,266 @@
+use std::hash::{Hash, Hasher};
+
+use my_tls::tokio_rustls::rustls::pki_types::CertificateDer;
+use sha2::{Digest, Sha256};
+
+use crate::FlUrlError;
+
+/// Prefix of the `CanNotConnectToRemoteHost` message a pin mismatch travels in
+/// through my-http-client, so `FlUrl` can report it as
+/// [`FlUrlError::TlsCertificatePinMismatch`] instead of a generic connect error.
+pub(crate) const PIN_MISMATCH_ERROR_PREFIX: &str = "TLS certificate pin mismatch: ";
+
+/// A set of SPKI SHA-256 pins — the hash of the DER `SubjectPublicKeyInfo`, the
+/// same value HPKP and OkHttp use (`sha256/<base64>`). One way to get it:
+///
+/// ```text
+/// openssl x509 -in cert.pem -pubkey -noout | openssl pkey -pubin -outform der \
+///     | openssl dgst -sha256 -binary | base64
+/// ```
+///
+/// Pinning is checked after the handshake and on top of the regular chain
+/// verification, never instead of it: the connection is accepted only when the
+/// leaf or one of the intermediates the server sent matches one of the pins.
+/// Pin the key you rotate to as well as the current one, or a routine
+/// certificate renewal takes the integration down.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct TlsCertificatePins {
+    sha256: Vec<[u8; 32]>,
+}
+
+impl TlsCertificatePins {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_sha256(mut self, spki_sha256: [u8; 32]) -> Self {
+        if let Err(index) = self.sha256.binary_search(&spki_sha256) {
+            self.sha256.insert(index, spki_sha256);
+        }
+        self
+    }
+
+    /// Base64 of the hash, with or without the `sha256/` prefix.
+    pub fn add_base64(self, pin: &str) -> Result<Self, FlUrlError> {
+        let pin = pin.trim();
+        let encoded = pin.strip_prefix("sha256/").unwrap_or(pin);
+
+        match decode_base64(encoded).and_then(|hash| <[u8; 32]>::try_from(hash).ok()) {
+            Some(hash) => Ok(self.add_sha256(hash)),
+            None => Err(FlUrlError::InvalidTlsConfiguration(format!(
+                "'{}' is not a base64 SHA-256 pin",
+                pin
+            ))),
+        }
+    }
+
+    /// Hex of the hash; `:` separators (as printed by `openssl dgst -c`) are ignored.
+    pub fn add_hex(self, pin: &str) -> Result<Self, FlUrlError> {
+        let digits: Vec<u8> = pin.trim().bytes().filter(|b| *b != b':').collect();
+
+        let mut hash = [0u8; 32];
+        let parsed = digits.len() == 64
+            && digits.chunks(2).zip(hash.iter_mut()).all(|(pair, byte)| {
+                match (hex_value(pair[0]), hex_value(pair[1])) {
+                    (Some(high), Some(low)) => {
+                        *byte = high << 4 | low;
+                        true
+                    }
+                    _ => false,
+                }
+            });
+
+        if !parsed {
+            return Err(FlUrlError::InvalidTlsConfiguration(format!(
+                "'{}' is not a hex SHA-256 pin",
+                pin
+            )));
+        }
+
+        Ok(self.add_sha256(hash))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sha256.is_empty()
+    }
+
+    pub(crate) fn get_fingerprint(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// `Err` carries the message for the caller, already prefixed with
+    /// [`PIN_MISMATCH_ERROR_PREFIX`].
+    pub(crate) fn check(
+        &self,
+        server_name: &str,
+        peer_certificates: &[CertificateDer<'_>],
+    ) -> Result<(), String> {
+        let matched = peer_certificates.iter().any(|certificate| {
+            get_spki_sha256(certificate.as_ref())
+                .is_some_and(|hash| self.sha256.binary_search(&hash).is_ok())
+        });
+
+        if matched {
+            return Ok(());
+        }
+
+        Err(format!(
+            "{}none of the {} certificate(s) presented by '{}' matches the configured pins",
+            PIN_MISMATCH_ERROR_PREFIX,
+            peer_certificates.len(),
+            server_name
+        ))
+    }
+}
+
+/// SHA-256 over the DER `SubjectPublicKeyInfo` of an X.509 certificate. Only walks
+/// the TBS fields in front of it; the certificate itself has already passed
+/// chain verification by the time this runs.
+pub(crate) fn get_spki_sha256(certificate: &[u8]) -> Option<[u8; 32]> {
+    let (_, certificate, _) = read_der(certificate)?;
+    let (_, mut tbs, _) = read_der(certificate)?;
+
+    // version [0] EXPLICIT is optional
+    if tbs.first() == Some(&0xa0) {
+        tbs = read_der(tbs)?.2;
+    }
+
+    // serialNumber, signature, issuer, validity, subject
+    for _ in 0..5 {
+        tbs = read_der(tbs)?.2;
+    }
+
+    let (spki, _, _) = read_der(tbs)?;
+    if spki.first() != Some(&0x30) {
+        return None;
+    }
+
+    Some(Sha256::digest(spki).into())
+}
+
+/// Splits one DER TLV off the front: `(whole tlv, value, rest)`.
+fn read_der(input: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let first_length_byte = *input.get(1)?;
+
+    let (header_len, value_len) = if first_length_byte < 0x80 {
+        (2, first_length_byte as usize)
+    } else {
+        let length_bytes = (first_length_byte & 0x7f) as usize;
+        if length_bytes == 0 || length_bytes > 4 {
+            return None;
+        }
+
+        let value_len = input
+            .get(2..2 + length_bytes)?
+            .iter()
+            .fold(0usize, |acc, b| acc << 8 | *b as usize);
+
+        (2 + length_bytes, value_len)
+    };
+
+    let end = header_len.checked_add(value_len)?;
+    if end > input.len() {
+        return None;
+    }
+
+    Some((&input[..end], &input[header_len..end], &input[end..]))
+}
+
+fn hex_value(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|value| value as u8)
+}
+
+fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim_end_matches('=');
+    let mut result = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in input.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+
+        buffer = buffer << 6 | value as u32;
+        bits += 6;
+
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+        }
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use my_tls::tokio_rustls::rustls::pki_types::pem::PemObject;
+
+    use super::*;
+
+    // `openssl x509 -pubkey | openssl pkey -pubin -outform der | openssl dgst -sha256`
+    // over `TEST_ROOT_PEM`.
+    const TEST_ROOT_PIN_BASE64: &str = "sha256/4omPh4LpAPeQrU3bV7daFkFZ03Hk6xawOsA7qYwzHu8=";
+    const TEST_ROOT_PIN_HEX: &str =
+        "e2898f8782e900f790ad4ddb57b75a164159d371e4eb16b03ac03ba98c331eef";
+
+    fn test_root() -> CertificateDer<'static> {
+        CertificateDer::from_pem_slice(
+            crate::non_wasm::tls::test_certificates::TEST_ROOT_PEM.as_bytes(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn spki_hash_matches_openssl() {
+        let pins = TlsCertificatePins::new()
+            .add_hex(TEST_ROOT_PIN_HEX)
+            .unwrap();
+
+        assert_eq!(
+            get_spki_sha256(test_root().as_ref()),
+            pins.sha256.first().copied()
+        );
+    }
+
+    #[test]
+    fn base64_and_hex_pins_are_the_same_pin() {
+        let from_base64 = TlsCertificatePins::new()
+            .add_base64(TEST_ROOT_PIN_BASE64)
+            .unwrap();
+        let from_hex = TlsCertificatePins::new()
+            .add_hex(TEST_ROOT_PIN_HEX)
+            .unwrap();
+
+        assert_eq!(from_base64, from_hex);
+        assert_eq!(from_base64.get_fingerprint(), from_hex.get_fingerprint());
+    }
+
+    #[test]
+    fn check_accepts_a_pinned_chain_and_rejects_others() {
+        let chain = vec![test_root()];
+
+        let pinned = TlsCertificatePins::new()
+            .add_base64(TEST_ROOT_PIN_BASE64)
+            .unwrap();
+        assert!(pinned.check("localhost", &chain).is_ok());
+
+        let other = TlsCertificatePins::new().add_sha256([7u8; 32]);
+        let err = other.check("localhost", &chain).unwrap_err();
+        assert!(err.starts_with(PIN_MISMATCH_ERROR_PREFIX));
+    }
+
+    #[test]
+    fn malformed_pins_are_rejected() {
+        assert!(TlsCertificatePins::new().add_base64("sha256/abc").is_err());
+        assert!(TlsCertificatePins::new().add_hex("e2898f").is_err());
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::Arc;
+
+use ahash::AHashMap;
+use my_tls::tokio_rustls::rustls::ClientConfig;
+use parking_lot::Mutex;
+use rust_extensions::date_time::DateTimeAsMicroseconds;
+
+use super::TlsClientSettings;
+
+/// How many TLS identities one cache keeps a `ClientConfig` for. Past that the
+/// least recently used one is dropped — together with its session tickets —
+/// which only matters for setups that mint identities per request (a verifier
+/// `Arc` per request, say).
+pub const MAX_SHARED_TLS_CLIENT_CONFIGS: usize = 64;
+
+/// One rustls `ClientConfig` per TLS identity — see
+/// `TlsClientSettings::get_client_config_key` — shared by every `HttpsConnector`
+/// a `FlUrlHttpConnectionsCache` hands out for it, pooled or opened with
+/// `do_not_reuse_connection`.
+///
+/// Sharing the config is what makes TLS session resumption work: rustls keeps
+/// the session tickets in the config's resumption store, so a reconnect to a host
+/// resumes the session of the previous connection instead of doing a full
+/// handshake. It also saves building the root store on every connect.
+pub struct TlsClientConfigCache {
+    items: Mutex<AHashMap<String, (Arc<ClientConfig>, DateTimeAsMicroseconds)>>,
+}
+
+impl TlsClientConfigCache {
+    pub fn new() -> Self {
+        Self {
+            items: Mutex::new(AHashMap::new()),
+        }
+    }
+
+    /// A build error is not cached: the next connect tries again.
+    pub(crate) fn get_or_create(
+        &self,
+        settings: &TlsClientSettings,
+    ) -> Result<Arc<ClientConfig>, String> {
+        let key = settings.get_client_config_key();
+        let now = DateTimeAsMicroseconds::now();
+
+        if let Some((client_config, last_used)) = self.items.lock().get_mut(&key) {
+            *last_used = now;
+            return Ok(client_config.clone());
+        }
+
+        // Built outside of the lock: a root store is not free to build, and
+        // other identities should not wait for it. Two connects racing for the
+        // same key build twice and keep the first.
+        let client_config = Arc::new(settings.create_client_config()?);
+
+        let mut items = self.items.lock();
+
+        if !items.contains_key(&key) && items.len() >= MAX_SHARED_TLS_CLIENT_CONFIGS {
+            let least_recently_used = items
+                .iter()
+                .min_by_key(|(_, (_, last_used))| last_used.unix_microseconds)
+                .map(|(key, _)| key.clone());
+
+            if let Some(least_recently_used) = least_recently_used {
+                items.remove(&least_recently_used);
+            }
+        }
+
+        let (client_config, _) = items.entry(key).or_insert((client_config, now));
+        Ok(client_config.clone())
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.items.lock().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> TlsClientSettings {
+        TlsClientSettings {
+            client_certificate: None,
+            client_identity: None,
+            accept_invalid_certificate: false,
+            root_certificates: None,
+            certificate_pins: None,
+            server_certificate_verifier: None,
+            tls_policy: None,
+            key_log: None,
+            client_configs: None,
+        }
+    }
+
+    // Building a config needs a provider, which `_tls` alone does not install.
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    #[test]
+    fn one_config_per_tls_identity() {
+        let cache = TlsClientConfigCache::new();
+
+        let first = cache.get_or_create(&settings()).unwrap();
+        let second = cache.get_or_create(&settings()).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let mut pinned = settings();
+        pinned.certificate_pins = Some(Arc::new(crate::TlsCertificatePins::new()));
+        let same_identity = cache.get_or_create(&pinned).unwrap();
+        assert!(Arc::ptr_eq(&first, &same_identity));
+
+        let mut tls13_only = settings();
+        tls13_only.tls_policy = Some(Arc::new(crate::TlsPolicy::tls13_only()));
+        let other_identity = cache.get_or_create(&tls13_only).unwrap();
+        assert!(!Arc::ptr_eq(&first, &other_identity));
+
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    #[tokio::test]
+    async fn second_handshake_to_a_host_resumes_the_session() {
+        use my_tls::tokio_rustls::{
+            rustls::{pki_types::ServerName, HandshakeKind},
+            TlsConnector,
+        };
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let server = crate::non_wasm::tls::test_server::TestTlsServer::start(&[]).await;
+
+        let mut settings = settings();
+        settings.root_certificates = Some(Arc::new(
+            crate::TlsRootCertificates::from_pem(
+                crate::non_wasm::tls::test_certificates::TEST_ROOT_PEM,
+                crate::TlsTrustMode::ReplaceDefaults,
+            )
+            .unwrap(),
+        ));
+        let cache = TlsClientConfigCache::new();
+
+        let mut handshake_kinds = Vec::new();
+        for _ in 0..2 {
+            // Cloned the way `HttpsConnector` clones it to set ALPN.
+            let client_config = ClientConfig::clone(&cache.get_or_create(&settings).unwrap());
+            let tcp_stream = tokio::net::TcpStream::connect(("127.0.0.1", server.port))
+                .await
+                .unwrap();
+            let mut tls_stream = TlsConnector::from(Arc::new(client_config))
+                .connect(ServerName::try_from("localhost").unwrap(), tcp_stream)
+                .await
+                .unwrap();
+            handshake_kinds.push(tls_stream.get_ref().1.handshake_kind());
+
+            // TLS 1.3 tickets arrive after the handshake: reading the response
+            // takes them in.
+            tls_stream
+                .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = Vec::new();
+            let _ = tls_stream.read_to_end(&mut response).await;
+        }
+
+        assert_eq!(
+            handshake_kinds,
+            [Some(HandshakeKind::Full), Some(HandshakeKind::Resumed)]
+        );
+    }
+}
@@ -0,0 +1,448 @@
+use std::{
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
+};
+
+use my_tls::tokio_rustls::rustls::{
+    client::ResolvesClientCert,
+    crypto::CryptoProvider,
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+    sign::CertifiedKey,
+    SignatureScheme,
+};
+use parking_lot::Mutex;
+
+use crate::FlUrlError;
+
+/// How often a [`ReloadableTlsClientIdentity`] looks at its files by default.
+pub const DEFAULT_CLIENT_IDENTITY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A client certificate chain plus its private key, loaded from PEM or PKCS#12.
+///
+/// The files are parsed when the value is built, and — when a rustls
+/// `CryptoProvider` is already installed as the process default — the key is
+/// checked against the leaf certificate, so a wrong pair fails here rather than
+/// in the middle of a handshake.
+#[derive(Debug)]
+pub struct TlsClientIdentity {
+    cert_chain: Vec<CertificateDer<'static>>,
+    private_key: PrivateKeyDer<'static>,
+    fingerprint: u64,
+}
+
+impl TlsClientIdentity {
+    /// `cert_pem` holds the leaf certificate first, followed by any intermediates;
+    /// `key_pem` holds one PKCS#8, PKCS#1 or SEC1 private key.
+    pub fn from_pem(
+        cert_pem: impl AsRef<[u8]>,
+        key_pem: impl AsRef<[u8]>,
+    ) -> Result<Self, FlUrlError> {
+        let cert_chain = CertificateDer::pem_slice_iter(cert_pem.as_ref())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                FlUrlError::InvalidTlsConfiguration(format!(
+                    "Can not read client certificate from PEM: {}",
+                    err
+                ))
+            })?;
+
+        let private_key = PrivateKeyDer::from_pem_slice(key_pem.as_ref()).map_err(|err| {
+            FlUrlError::InvalidTlsConfiguration(format!(
+                "Can not read client private key from PEM: {}",
+                err
+            ))
+        })?;
+
+        Self::new(cert_chain, private_key)
+    }
+
+    pub fn from_pem_files(
+        cert_path: impl AsRef<Path>,
+        key_path: impl AsRef<Path>,
+    ) -> Result<Self, FlUrlError> {
+        let cert_pem = std::fs::read(cert_path.as_ref())?;
+        let key_pem = std::fs::read(key_path.as_ref())?;
+        Self::from_pem(cert_pem, key_pem)
+    }
+
+    /// Takes the first entry of the store that has a private key.
+    pub fn from_pkcs12(der: impl AsRef<[u8]>, password: &str) -> Result<Self, FlUrlError> {
+        let key_store =
+            p12_keystore::KeyStore::from_pkcs12(der.as_ref(), password).map_err(|err| {
+                FlUrlError::InvalidTlsConfiguration(format!("Can not read PKCS#12: {}", err))
+            })?;
+
+        let Some((_, key_chain)) = key_store.private_key_chain() else {
+            return Err(FlUrlError::InvalidTlsConfiguration(
+                "PKCS#12 has no private key entry".to_string(),
+            ));
+        };
+
+        let cert_chain = key_chain
+            .chain()
+            .iter()
+            .map(|certificate| CertificateDer::from(certificate.as_der().to_vec()))
+            .collect();
+
+        let private_key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_chain.key().to_vec()));
+
+        Self::new(cert_chain, private_key)
+    }
+
+    pub fn from_pkcs12_file(path: impl AsRef<Path>, password: &str) -> Result<Self, FlUrlError> {
+        let der = std::fs::read(path.as_ref())?;
+        Self::from_pkcs12(der, password)
+    }
+
+    fn new(
+        cert_chain: Vec<CertificateDer<'static>>,
+        private_key: PrivateKeyDer<'static>,
+    ) -> Result<Self, FlUrlError> {
+        if cert_chain.is_empty() {
+            return Err(FlUrlError::InvalidTlsConfiguration(
+                "No client certificate found".to_string(),
+            ));
+        }
+
+        // Same hashing as for a `my_tls::ClientCertificate` in the https pool key,
+        // so one certificate gives one key whichever way it was loaded.
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for der in &cert_chain {
+            der.as_ref().hash(&mut hasher);
+        }
+
+        let result = Self {
+            cert_chain,
+            private_key,
+            fingerprint: hasher.finish(),
+        };
+
+        if let Some(provider) = CryptoProvider::get_default() {
+            result.to_certified_key(provider)?;
+        }
+
+        Ok(result)
+    }
+
+    pub fn get_cert_chain(&self) -> &[CertificateDer<'static>] {
+        &self.cert_chain
+    }
+
+    pub(crate) fn get_fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub(crate) fn to_certified_key(
+        &self,
+        provider: &CryptoProvider,
+    ) -> Result<CertifiedKey, FlUrlError> {
+        CertifiedKey::from_der(
+            self.cert_chain.clone(),
+            self.private_key.clone_key(),
+            provider,
+        )
+        .map_err(|err| {
+            FlUrlError::InvalidTlsConfiguration(format!("Invalid client certificate: {}", err))
+        })
+    }
+}
+
+enum ClientIdentityFiles {
+    Pem {
+        cert_path: PathBuf,
+        key_path: PathBuf,
+    },
+    Pkcs12 {
+        path: PathBuf,
+        password: String,
+    },
+}
+
+impl ClientIdentityFiles {
+    fn load(&self) -> Result<TlsClientIdentity, FlUrlError> {
+        match self {
+            Self::Pem {
+                cert_path,
+                key_path,
+            } => TlsClientIdentity::from_pem_files(cert_path, key_path),
+            Self::Pkcs12 { path, password } => TlsClientIdentity::from_pkcs12_file(path, password),
+        }
+    }
+
+    /// Modification time and length of every file; a change in any of them means
+    /// the identity is reloaded.
+    fn get_versions(&self) -> Vec<Option<(SystemTime, u64)>> {
+        let paths: Vec<&Path> = match self {
+            Self::Pem {
+                cert_path,
+                key_path,
+            } => vec![cert_path, key_path],
+            Self::Pkcs12 { path, .. } => vec![path],
+        };
+
+        paths
+            .into_iter()
+            .map(|path| {
+                let metadata = std::fs::metadata(path).ok()?;
+                Some((metadata.modified().ok()?, metadata.len()))
+            })
+            .collect()
+    }
+}
+
+struct ReloadState {
+    versions: Vec<Option<(SystemTime, u64)>>,
+    checked_at: Instant,
+    // A check is running on a blocking thread.
+    checking: bool,
+}
+
+struct ReloadableTlsClientIdentityInner {
+    files: ClientIdentityFiles,
+    check_interval: Duration,
+    // Only ever swapped: readers never wait for the files.
+    identity: Mutex<Arc<TlsClientIdentity>>,
+    state: Mutex<ReloadState>,
+}
+
+impl ReloadableTlsClientIdentityInner {
+    /// Claims the next check, if one is due and none is running.
+    fn start_check(&self) -> bool {
+        let mut state = self.state.lock();
+
+        if state.checking || state.checked_at.elapsed() < self.check_interval {
+            return false;
+        }
+
+        state.checking = true;
+        true
+    }
+
+    /// Looks at the files and reloads the identity if they changed. Blocking:
+    /// metadata calls and file reads.
+    fn check(&self) {
+        let versions = self.files.get_versions();
+        let changed = versions != self.state.lock().versions;
+
+        if changed {
+            if let Ok(identity) = self.files.load() {
+                *self.identity.lock() = Arc::new(identity);
+                self.state.lock().versions = versions;
+            }
+        }
+
+        let mut state = self.state.lock();
+        state.checked_at = Instant::now();
+        state.checking = false;
+    }
+}
+
+/// A client identity that follows its files — the way cert-manager and similar
+/// tools rotate certificates in place.
+///
+/// At most once per check interval the files' modification time and size are
+/// compared with the loaded ones; on a change the identity is loaded again. The
+/// check runs on a blocking thread and never holds up a request: the request
+/// that starts it still gets the identity loaded so far. A reload that fails —
+/// a file caught halfway through being rewritten or, with a default
+/// `CryptoProvider` installed, a new certificate next to the old key — keeps the
+/// previous identity and is retried at the next check.
+///
+/// The identity is part of the https pool key, so requests made after a rotation
+/// open new connections with the new certificate, while the connections of the
+/// old one are no longer handed out and expire with the regular reuse timeout.
+///
+/// Cheap to clone; clones share the loaded identity.
+#[derive(Clone)]
+pub struct ReloadableTlsClientIdentity {
+    inner: Arc<ReloadableTlsClientIdentityInner>,
+}
+
+impl ReloadableTlsClientIdentity {
+    pub fn from_pem_files(
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> Result<Self, FlUrlError> {
+        Self::new(ClientIdentityFiles::Pem {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+        })
+    }
+
+    pub fn from_pkcs12_file(
+        path: impl Into<PathBuf>,
+        password: impl Into<String>,
+    ) -> Result<Self, FlUrlError> {
+        Self::new(ClientIdentityFiles::Pkcs12 {
+            path: path.into(),
+            password: password.into(),
+        })
+    }
+
+    fn new(files: ClientIdentityFiles) -> Result<Self, FlUrlError> {
+        let versions = files.get_versions();
+        let identity = files.load()?;
+
+        Ok(Self {
+            inner: Arc::new(ReloadableTlsClientIdentityInner {
+                files,
+                check_interval: DEFAULT_CLIENT_IDENTITY_CHECK_INTERVAL,
+                identity: Mutex::new(Arc::new(identity)),
+                state: Mutex::new(ReloadState {
+                    versions,
+                    checked_at: Instant::now(),
+                    checking: false,
+                }),
+            }),
+        })
+    }
+
+    /// How often the files are looked at. Only settable before the value is
+    /// cloned or handed to a request.
+    pub fn set_check_interval(mut self, check_interval: Duration) -> Self {
+        if let Some(inner) = Arc::get_mut(&mut self.inner) {
+            inner.check_interval = check_interval;
+        }
+        self
+    }
+
+    /// The identity new connections are made with. When a check is due, it is
+    /// started on the runtime's blocking pool — or, called outside of a tokio
+    /// runtime, made right here — and the identity it loads is what later calls
+    /// get.
+    pub fn get_current(&self) -> Arc<TlsClientIdentity> {
+        if self.inner.start_check() {
+            let inner = self.inner.clone();
+            match tokio::runtime::Handle::try_current() {
+                Ok(runtime) => {
+                    runtime.spawn_blocking(move || inner.check());
+                }
+                Err(_) => inner.check(),
+            }
+        }
+
+        self.inner.identity.lock().clone()
+    }
+}
+
+/// Where a request takes its client identity from.
+#[derive(Clone)]
+pub(crate) enum TlsClientIdentitySource {
+    Fixed(Arc<TlsClientIdentity>),
+    Reloadable(ReloadableTlsClientIdentity),
+}
+
+impl TlsClientIdentitySource {
+    pub fn get_current(&self) -> Arc<TlsClientIdentity> {
+        match self {
+            Self::Fixed(identity) => identity.clone(),
+            Self::Reloadable(identity) => identity.get_current(),
+        }
+    }
+}
+
+/// Hands the same certificate to every server that asks for one, like the
+/// resolver rustls builds for `with_client_auth_cert`.
+#[derive(Debug)]
+pub(crate) struct ClientIdentityResolver(pub Arc<CertifiedKey>);
+
+impl ResolvesClientCert for ClientIdentityResolver {
+    fn resolve(
+        &self,
+        _root_hint_subjects: &[&[u8]],
+        _sigschemes: &[SignatureScheme],
+    ) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.clone())
+    }
+
+    fn has_certs(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::non_wasm::tls::test_certificates::*;
+
+    fn write_pair(dir: &Path, cert_pem: &str, key_pem: &str) -> (PathBuf, PathBuf) {
+        let cert_path = dir.join("tls.crt");
+        let key_path = dir.join("tls.key");
+        std::fs::write(&cert_path, cert_pem).unwrap();
+        std::fs::write(&key_path, key_pem).unwrap();
+        (cert_path, key_path)
+    }
+
+    #[test]
+    fn pem_identity_is_loaded() {
+        let identity = TlsClientIdentity::from_pem(TEST_ROOT_PEM, TEST_ROOT_KEY_PEM).unwrap();
+        assert_eq!(identity.get_cert_chain().len(), 1);
+
+        let result = TlsClientIdentity::from_pem("", TEST_ROOT_KEY_PEM);
+        assert!(matches!(
+            result,
+            Err(FlUrlError::InvalidTlsConfiguration(_))
+        ));
+    }
+
+    #[test]
+    fn reloadable_identity_follows_its_files() {
+        let dir =
+            std::env::temp_dir().join(format!("flurl-client-identity-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path) = write_pair(&dir, TEST_ROOT_PEM, TEST_ROOT_KEY_PEM);
+
+        let identity = ReloadableTlsClientIdentity::from_pem_files(&cert_path, &key_path)
+            .unwrap()
+            .set_check_interval(Duration::ZERO);
+        let before = identity.get_current();
+
+        // Rotated in place; the new pair differs in size, so the change is seen
+        // even where the file system's mtime resolution is coarse.
+        write_pair(&dir, SECOND_TEST_ROOT_PEM, SECOND_TEST_ROOT_KEY_PEM);
+        let after = identity.get_current();
+        assert_ne!(before.get_fingerprint(), after.get_fingerprint());
+
+        // A broken rewrite keeps the last good identity.
+        std::fs::write(&cert_path, "not a certificate").unwrap();
+        assert_eq!(
+            identity.get_current().get_fingerprint(),
+            after.get_fingerprint()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reload_runs_off_the_async_caller() {
+        let dir = std::env::temp_dir().join(format!(
+            "flurl-client-identity-async-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path) = write_pair(&dir, TEST_ROOT_PEM, TEST_ROOT_KEY_PEM);
+
+        let identity = ReloadableTlsClientIdentity::from_pem_files(&cert_path, &key_path)
+            .unwrap()
+            .set_check_interval(Duration::ZERO);
+        let before = identity.get_current();
+
+        write_pair(&dir, SECOND_TEST_ROOT_PEM, SECOND_TEST_ROOT_KEY_PEM);
+
+        // The call that starts a check does not wait for it; a later one gets
+        // what it loaded.
+        let mut after = identity.get_current();
+        for _ in 0..100 {
+            if after.get_fingerprint() != before.get_fingerprint() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            after = identity.get_current();
+        }
+        assert_ne!(before.get_fingerprint(), after.get_fingerprint());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
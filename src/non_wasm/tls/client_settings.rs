@@ -0,0 +1,248 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use my_tls::{
+    tokio_rustls::rustls::{
+        client::{danger::ServerCertVerifier, WebPkiServerVerifier},
+        crypto::CryptoProvider,
+        ClientConfig,
+    },
+    ClientCertificate,
+};
+
+use super::{
+    ClientIdentityResolver, HookedServerCertVerifier, TlsCertificatePins, TlsClientConfigCache,
+    TlsClientIdentity, TlsKeyLogFile, TlsPolicy, TlsRootCertificates, TlsServerCertificateVerifier,
+};
+
+/// Starts the `CanNotConnectToRemoteHost` message `HttpsConnector` returns when
+/// the `ClientConfig` can not be built, so it can be told apart from network
+/// errors and surfaced as `FlUrlError::InvalidTlsConfiguration`.
+pub(crate) const INVALID_TLS_CONFIGURATION_ERROR_PREFIX: &str = "Invalid TLS configuration: ";
+
+/// Everything the TLS side of one `HttpsConnector` is built from: the rustls
+/// `ClientConfig` inputs and the checks run after the handshake. All of it is
+/// part of the https pool key (see `get_https_connection_key`), so a pooled
+/// connection is only reused by requests asking for the same TLS setup.
+#[derive(Clone)]
+pub struct TlsClientSettings {
+    pub client_certificate: Option<ClientCertificate>,
+    pub client_identity: Option<Arc<TlsClientIdentity>>,
+    pub accept_invalid_certificate: bool,
+    pub root_certificates: Option<Arc<TlsRootCertificates>>,
+    pub certificate_pins: Option<Arc<TlsCertificatePins>>,
+    pub server_certificate_verifier: Option<Arc<dyn TlsServerCertificateVerifier>>,
+    pub tls_policy: Option<Arc<TlsPolicy>>,
+    pub key_log: Option<Arc<TlsKeyLogFile>>,
+    /// Where the built `ClientConfig` is shared from; `None` builds a fresh one
+    /// per connect, with nothing to resume a session from.
+    pub client_configs: Option<Arc<TlsClientConfigCache>>,
+}
+
+impl TlsClientSettings {
+    /// The config `HttpsConnector` connects with: the shared one for this TLS
+    /// identity when there is a `TlsClientConfigCache`, else a fresh one.
+    pub fn get_client_config(&self) -> Result<Arc<ClientConfig>, String> {
+        match self.client_configs.as_ref() {
+            Some(client_configs) => client_configs.get_or_create(self),
+            None => self.create_client_config().map(Arc::new),
+        }
+    }
+
+    /// Identifies the `ClientConfig` these settings build: everything that goes
+    /// into it, and nothing that is checked outside of it — the pins are checked
+    /// after the handshake, and the server name is the session store's own key.
+    pub fn get_client_config_key(&self) -> String {
+        let cert_tag = match (
+            self.client_identity.as_ref(),
+            self.client_certificate.as_ref(),
+        ) {
+            (Some(identity), _) => format!("{:016x}", identity.get_fingerprint()),
+            (None, Some(cert)) => format!("{:016x}", get_client_certificate_fingerprint(cert)),
+            (None, None) => "nocert".to_string(),
+        };
+
+        let roots_tag = match self.root_certificates.as_ref() {
+            Some(root_certificates) => format!("{:016x}", root_certificates.get_fingerprint()),
+            None => "sysroots".to_string(),
+        };
+
+        let verifier_tag = match self.server_certificate_verifier.as_ref() {
+            Some(verifier) => format!("vfy{:x}", Arc::as_ptr(verifier) as *const () as usize),
+            None => "novfy".to_string(),
+        };
+
+        let policy_tag = match self.tls_policy.as_ref() {
+            Some(tls_policy) => format!("pol{:016x}", tls_policy.get_fingerprint()),
+            None => "nopol".to_string(),
+        };
+
+        let key_log_tag = match self.key_log.as_ref() {
+            Some(key_log) => format!("klog{:016x}", key_log.get_fingerprint()),
+            None => "noklog".to_string(),
+        };
+
+        format!(
+            "{}|{}|{}|{}|{}|{}",
+            cert_tag,
+            roots_tag,
+            verifier_tag,
+            policy_tag,
+            key_log_tag,
+            if self.accept_invalid_certificate {
+                "insecure"
+            } else {
+                "verified"
+            }
+        )
+    }
+
+    pub fn create_client_config(&self) -> Result<ClientConfig, String> {
+        let mut client_config = my_tls::create_tls_client_config_ex(
+            &self.client_certificate,
+            self.accept_invalid_certificate,
+        )
+        .map_err(|err| err.to_string())?;
+
+        let verifier = self.create_server_verifier(client_config.crypto_provider())?;
+
+        client_config = match self.tls_policy.as_ref() {
+            Some(tls_policy) => tls_policy.apply(client_config, verifier)?,
+            None => {
+                client_config.dangerous().set_certificate_verifier(verifier);
+                client_config
+            }
+        };
+
+        if let Some(key_log) = self.key_log.as_ref() {
+            client_config.key_log = key_log.clone();
+        }
+
+        if let Some(client_identity) = self.client_identity.as_ref() {
+            let certified_key = client_identity
+                .to_certified_key(client_config.crypto_provider())
+                .map_err(|err| err.to_string())?;
+
+            client_config.client_auth_cert_resolver =
+                Arc::new(ClientIdentityResolver(Arc::new(certified_key)));
+        }
+
+        Ok(client_config)
+    }
+
+    /// The server certificate verifier of every config these settings build, with
+    /// a policy or without, so a policy never changes what is trusted: the roots
+    /// from `with_root_certificates`, else the `webpki-roots` Mozilla set, with the
+    /// verifier hook on top.
+    fn create_server_verifier(
+        &self,
+        provider: &Arc<CryptoProvider>,
+    ) -> Result<Arc<dyn ServerCertVerifier>, String> {
+        // `accept_invalid_certificate` only switches verification off under
+        // `dangerous-tls`; then there is nothing left for custom roots or the
+        // verifier hook to do.
+        #[cfg(feature = "dangerous-tls")]
+        if self.accept_invalid_certificate {
+            return Ok(no_verification::create(provider.clone()));
+        }
+
+        let root_cert_store = match self.root_certificates.as_ref() {
+            Some(root_certificates) => root_certificates
+                .to_root_cert_store()
+                .map_err(|err| err.to_string())?,
+            None => super::get_default_root_cert_store(),
+        };
+
+        let standard: Arc<dyn ServerCertVerifier> = WebPkiServerVerifier::builder_with_provider(
+            Arc::new(root_cert_store),
+            provider.clone(),
+        )
+        .build()
+        .map_err(|err| err.to_string())?;
+
+        match self.server_certificate_verifier.as_ref() {
+            Some(hook) => Ok(Arc::new(HookedServerCertVerifier {
+                standard,
+                hook: hook.clone(),
+            })),
+            None => Ok(standard),
+        }
+    }
+}
+
+/// A `my_tls::ClientCertificate` carries no fingerprint of its own; its chain
+/// stands in for one.
+pub fn get_client_certificate_fingerprint(client_certificate: &ClientCertificate) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for der in &client_certificate.cert_chain {
+        der.as_ref().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// What `accept_invalid_certificate` verifies with under `dangerous-tls`.
+#[cfg(feature = "dangerous-tls")]
+mod no_verification {
+    use std::sync::Arc;
+
+    use my_tls::tokio_rustls::rustls::{
+        client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+        crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider},
+        pki_types::{CertificateDer, ServerName, UnixTime},
+        DigitallySignedStruct, Error, SignatureScheme,
+    };
+
+    pub fn create(provider: Arc<CryptoProvider>) -> Arc<dyn ServerCertVerifier> {
+        Arc::new(NoServerVerification(provider))
+    }
+
+    #[derive(Debug)]
+    struct NoServerVerification(Arc<CryptoProvider>);
+
+    impl ServerCertVerifier for NoServerVerification {
+        fn verify_server_cert(
+            &self,
+            _end_entity: &CertificateDer<'_>,
+            _intermediates: &[CertificateDer<'_>],
+            _server_name: &ServerName<'_>,
+            _ocsp_response: &[u8],
+            _now: UnixTime,
+        ) -> Result<ServerCertVerified, Error> {
+            Ok(ServerCertVerified::assertion())
+        }
+
+        fn verify_tls12_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, Error> {
+            verify_tls12_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn verify_tls13_signature(
+            &self,
+            message: &[u8],
+            cert: &CertificateDer<'_>,
+            dss: &DigitallySignedStruct,
+        ) -> Result<HandshakeSignatureValid, Error> {
+            verify_tls13_signature(
+                message,
+                cert,
+                dss,
+                &self.0.signature_verification_algorithms,
+            )
+        }
+
+        fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+            self.0.signature_verification_algorithms.supported_schemes()
+        }
+    }
+}
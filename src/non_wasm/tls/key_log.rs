@@ -0,0 +1,139 @@
+use std::{
+    fs::{File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use my_tls::tokio_rustls::rustls::KeyLog;
+use parking_lot::Mutex;
+
+/// The variable Wireshark, curl, browsers and rustls' own `KeyLogFile` read the
+/// key-log path from.
+pub const SSLKEYLOGFILE_ENV_VARIABLE: &str = "SSLKEYLOGFILE";
+
+/// Writes the TLS session secrets of the connections it is set on to a file in
+/// the NSS key-log format, so a packet capture of them can be decrypted in
+/// Wireshark (*Preferences → Protocols → TLS → (Pre)-Master-Secret log filename*).
+///
+/// Strictly a debugging aid: whoever can read the file can read the traffic. It
+/// is never switched on by the environment alone — `SSLKEYLOGFILE` is only
+/// honored through [`Self::from_env`], by code that asked for it. The file is
+/// opened for appending on the first handshake; a file that can not be opened
+/// or written is skipped without failing the connection.
+pub struct TlsKeyLogFile {
+    path: PathBuf,
+    file: Mutex<Option<File>>,
+}
+
+impl TlsKeyLogFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// The path in `SSLKEYLOGFILE`, or `None` if the variable is unset or empty.
+    pub fn from_env() -> Option<Self> {
+        let path = std::env::var_os(SSLKEYLOGFILE_ENV_VARIABLE)?;
+
+        if path.is_empty() {
+            return None;
+        }
+
+        Some(Self::new(path))
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn get_fingerprint(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.path.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+impl std::fmt::Debug for TlsKeyLogFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsKeyLogFile")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyLog for TlsKeyLogFile {
+    fn log(&self, label: &str, client_random: &[u8], secret: &[u8]) {
+        let mut line =
+            String::with_capacity(label.len() + 2 * (client_random.len() + secret.len()) + 3);
+        line.push_str(label);
+        line.push(' ');
+        push_hex(&mut line, client_random);
+        line.push(' ');
+        push_hex(&mut line, secret);
+        line.push('\n');
+
+        let mut file = self.file.lock();
+
+        if file.is_none() {
+            *file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .ok();
+        }
+
+        if let Some(opened) = file.as_mut() {
+            // One write per line keeps lines whole when several connections —
+            // or several `TlsKeyLogFile`s — append to the same file.
+            if opened.write_all(line.as_bytes()).is_err() {
+                *file = None;
+            }
+        }
+    }
+}
+
+fn push_hex(out: &mut String, bytes: &[u8]) {
+    use std::fmt::Write;
+
+    for byte in bytes {
+        let _ = write!(out, "{:02x}", byte);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_appended_in_nss_format() {
+        let path = std::env::temp_dir().join(format!("flurl-key-log-{}.txt", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let key_log = TlsKeyLogFile::new(&path);
+        key_log.log("CLIENT_RANDOM", &[0x01, 0xab], &[0xff, 0x00, 0x10]);
+        key_log.log("CLIENT_TRAFFIC_SECRET_0", &[0x02], &[0x03]);
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            content,
+            "CLIENT_RANDOM 01ab ff0010\nCLIENT_TRAFFIC_SECRET_0 02 03\n"
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn key_log_is_keyed_by_its_path() {
+        assert_eq!(
+            TlsKeyLogFile::new("/tmp/keys.log").get_fingerprint(),
+            TlsKeyLogFile::new("/tmp/keys.log").get_fingerprint()
+        );
+        assert_ne!(
+            TlsKeyLogFile::new("/tmp/keys.log").get_fingerprint(),
+            TlsKeyLogFile::new("/tmp/other.log").get_fingerprint()
+        );
+    }
+}
@@ -0,0 +1,28 @@
+//! TLS settings FlUrl applies on top of the rustls `ClientConfig` that `my_tls`
+//! builds: client identities loaded from files, custom trust anchors, a server
+//! certificate verifier hook, certificate pins, version and cipher-suite
+//! policies, the key log and everything else `HttpsConnector` needs to know around the
+//! handshake. Only compiled with a TLS provider feature.
+
+mod certificate_pins;
+mod client_config_cache;
+mod client_identity;
+mod client_settings;
+mod key_log;
+mod root_certificates;
+mod server_certificate_verifier;
+#[cfg(test)]
+pub(crate) mod test_certificates;
+// Serving needs a provider, which `_tls` alone does not install.
+#[cfg(all(test, any(feature = "with-ring-tls", feature = "with-rust-tls")))]
+pub(crate) mod test_server;
+mod tls_policy;
+
+pub use certificate_pins::*;
+pub(crate) use client_config_cache::*;
+pub use client_identity::*;
+pub(crate) use client_settings::*;
+pub use key_log::*;
+pub use root_certificates::*;
+pub use server_certificate_verifier::*;
+pub use tls_policy::*;
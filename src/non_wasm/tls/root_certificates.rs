@@ -0,0 +1,183 @@
+use std::{
+    hash::{Hash, Hasher},
+    path::Path,
+};
+
+use my_tls::tokio_rustls::rustls::{
+    pki_types::{pem::PemObject, CertificateDer},
+    RootCertStore,
+};
+
+use crate::FlUrlError;
+
+/// How custom trust anchors relate to the built-in root set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TlsTrustMode {
+    /// Only the given certificates are trusted. The usual choice for an internal
+    /// service behind a private CA: a certificate issued by a public CA for the
+    /// same name is rejected.
+    ReplaceDefaults,
+    /// The given certificates are trusted in addition to the Mozilla root set
+    /// shipped with `webpki-roots`, so the same client can still talk to public
+    /// hosts.
+    AddToDefaults,
+}
+
+/// Trust anchors for verifying the server certificate of an `https://` request —
+/// the safe answer to a private CA, where the only workaround used to be
+/// `accept_invalid_certificate`.
+///
+/// Every certificate is validated when the value is built, so a bad bundle fails
+/// here and not on the first handshake. The value is cheap to clone and is meant
+/// to be built once and reused: the bundle is part of the https pool key, and
+/// requests carrying the same bundle share connections.
+#[derive(Debug, Clone)]
+pub struct TlsRootCertificates {
+    mode: TlsTrustMode,
+    certificates: Vec<CertificateDer<'static>>,
+    fingerprint: u64,
+}
+
+impl TlsRootCertificates {
+    /// Reads every `CERTIFICATE` block of a PEM bundle; other blocks (keys, CRLs)
+    /// are skipped. A bundle without a single certificate is an error.
+    pub fn from_pem(pem: impl AsRef<[u8]>, mode: TlsTrustMode) -> Result<Self, FlUrlError> {
+        let certificates = CertificateDer::pem_slice_iter(pem.as_ref())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| {
+                FlUrlError::InvalidTlsConfiguration(format!(
+                    "Can not read root certificates from PEM: {}",
+                    err
+                ))
+            })?;
+
+        Self::new(certificates, mode)
+    }
+
+    pub fn from_pem_file(path: impl AsRef<Path>, mode: TlsTrustMode) -> Result<Self, FlUrlError> {
+        let pem = std::fs::read(path.as_ref())?;
+        Self::from_pem(pem, mode)
+    }
+
+    /// One DER-encoded certificate per item.
+    pub fn from_der(
+        certificates: impl IntoIterator<Item = Vec<u8>>,
+        mode: TlsTrustMode,
+    ) -> Result<Self, FlUrlError> {
+        let certificates = certificates.into_iter().map(CertificateDer::from).collect();
+        Self::new(certificates, mode)
+    }
+
+    fn new(
+        certificates: Vec<CertificateDer<'static>>,
+        mode: TlsTrustMode,
+    ) -> Result<Self, FlUrlError> {
+        if certificates.is_empty() {
+            return Err(FlUrlError::InvalidTlsConfiguration(
+                "No root certificates found".to_string(),
+            ));
+        }
+
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        mode.hash(&mut hasher);
+        for der in &certificates {
+            der.as_ref().hash(&mut hasher);
+        }
+
+        let result = Self {
+            mode,
+            certificates,
+            fingerprint: hasher.finish(),
+        };
+
+        result.to_root_cert_store()?;
+
+        Ok(result)
+    }
+
+    pub fn get_mode(&self) -> TlsTrustMode {
+        self.mode
+    }
+
+    pub fn len(&self) -> usize {
+        self.certificates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.certificates.is_empty()
+    }
+
+    /// Identifies the bundle together with its mode inside pool keys.
+    pub(crate) fn get_fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    pub(crate) fn to_root_cert_store(&self) -> Result<RootCertStore, FlUrlError> {
+        let mut result = match self.mode {
+            TlsTrustMode::ReplaceDefaults => RootCertStore::empty(),
+            TlsTrustMode::AddToDefaults => get_default_root_cert_store(),
+        };
+
+        for certificate in &self.certificates {
+            result.add(certificate.clone())?;
+        }
+
+        Ok(result)
+    }
+}
+
+/// The Mozilla root set shipped with `webpki-roots`.
+pub(crate) fn get_default_root_cert_store() -> RootCertStore {
+    RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::non_wasm::tls::test_certificates::TEST_ROOT_PEM;
+
+    #[test]
+    fn replace_mode_trusts_only_the_bundle() {
+        let roots =
+            TlsRootCertificates::from_pem(TEST_ROOT_PEM, TlsTrustMode::ReplaceDefaults).unwrap();
+
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots.to_root_cert_store().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn add_mode_keeps_the_default_roots() {
+        let roots =
+            TlsRootCertificates::from_pem(TEST_ROOT_PEM, TlsTrustMode::AddToDefaults).unwrap();
+
+        assert_eq!(
+            roots.to_root_cert_store().unwrap().len(),
+            webpki_roots::TLS_SERVER_ROOTS.len() + 1
+        );
+    }
+
+    #[test]
+    fn mode_is_part_of_the_fingerprint() {
+        let replace =
+            TlsRootCertificates::from_pem(TEST_ROOT_PEM, TlsTrustMode::ReplaceDefaults).unwrap();
+        let add =
+            TlsRootCertificates::from_pem(TEST_ROOT_PEM, TlsTrustMode::AddToDefaults).unwrap();
+
+        assert_ne!(replace.get_fingerprint(), add.get_fingerprint());
+    }
+
+    #[test]
+    fn bundle_without_certificates_is_rejected() {
+        let result = TlsRootCertificates::from_pem("not a pem", TlsTrustMode::ReplaceDefaults);
+        assert!(matches!(
+            result,
+            Err(FlUrlError::InvalidTlsConfiguration(_))
+        ));
+
+        let result =
+            TlsRootCertificates::from_der(vec![vec![1, 2, 3]], TlsTrustMode::AddToDefaults);
+        assert!(result.is_err());
+    }
+}
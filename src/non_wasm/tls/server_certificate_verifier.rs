@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use my_tls::tokio_rustls::rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    pki_types::{CertificateDer, ServerName, UnixTime},
+    CertificateError, DigitallySignedStruct, Error, SignatureScheme,
+};
+
+/// An extension point for deciding whether to trust the server certificate,
+/// for the cases the standard verification does not cover — checking a SPIFFE
+/// ID in a URI SAN, or accepting a certificate issued for another hostname than
+/// the one connected to.
+///
+/// Unlike `accept_invalid_certificate` it does not switch verification off: the
+/// hook runs after the standard chain and hostname verification, sees its
+/// outcome in [`TlsServerCertificateContext::default_verification`], and makes
+/// the final call. Returning `Err` aborts the handshake with the given reason.
+///
+/// ```ignore
+/// struct SpiffeId(&'static str);
+///
+/// impl TlsServerCertificateVerifier for SpiffeId {
+///     fn verify(&self, ctx: &TlsServerCertificateContext<'_>) -> Result<(), String> {
+///         if !ctx.chain_is_trusted() {
+///             return Err("untrusted chain".to_string());
+///         }
+///         // `uri_sans` stands for parsing the leaf with an X.509 crate
+///         if uri_sans(ctx.end_entity).iter().any(|san| san == self.0) {
+///             Ok(())
+///         } else {
+///             Err(format!("{} is not among the URI SANs", self.0))
+///         }
+///     }
+/// }
+/// ```
+pub trait TlsServerCertificateVerifier: Send + Sync + 'static {
+    fn verify(&self, ctx: &TlsServerCertificateContext<'_>) -> Result<(), String>;
+}
+
+/// What a [`TlsServerCertificateVerifier`] gets to look at.
+pub struct TlsServerCertificateContext<'a> {
+    /// The SNI name the connection was opened with.
+    pub server_name: &'a str,
+    /// DER of the server's leaf certificate.
+    pub end_entity: &'a CertificateDer<'a>,
+    /// DER of the intermediates the server sent, in the order it sent them.
+    pub intermediates: &'a [CertificateDer<'a>],
+    /// Outcome of the standard verification of the chain and of `server_name` —
+    /// against the custom roots when set with `with_root_certificates`, the
+    /// `webpki-roots` Mozilla set otherwise.
+    pub default_verification: &'a Result<(), Error>,
+
+    standard: &'a dyn ServerCertVerifier,
+    ocsp_response: &'a [u8],
+    now: UnixTime,
+}
+
+impl TlsServerCertificateContext<'_> {
+    /// `true` when the chain leads to a trusted root — the standard verification
+    /// either passed, or failed on the hostname only. The chain is checked before
+    /// the name, so a name error means the chain itself was fine.
+    pub fn chain_is_trusted(&self) -> bool {
+        match self.default_verification {
+            Ok(()) => true,
+            Err(Error::InvalidCertificate(CertificateError::NotValidForName)) => true,
+            Err(Error::InvalidCertificate(CertificateError::NotValidForNameContext { .. })) => true,
+            Err(_) => false,
+        }
+    }
+
+    /// Runs the standard verification again as if the connection had been
+    /// opened for `name` — accepting a certificate issued for another hostname
+    /// without giving up on the chain check.
+    pub fn verify_for_name(&self, name: &str) -> Result<(), Error> {
+        let name = ServerName::try_from(name.to_string())
+            .map_err(|err| Error::General(format!("Invalid server name '{}': {}", name, err)))?;
+
+        self.standard
+            .verify_server_cert(
+                self.end_entity,
+                self.intermediates,
+                &name,
+                self.ocsp_response,
+                self.now,
+            )
+            .map(|_| ())
+    }
+}
+
+/// The rustls verifier installed when a [`TlsServerCertificateVerifier`] is set:
+/// the standard one underneath, the hook on top. Handshake signatures are
+/// always checked by the standard verifier.
+pub(crate) struct HookedServerCertVerifier {
+    pub standard: Arc<dyn ServerCertVerifier>,
+    pub hook: Arc<dyn TlsServerCertificateVerifier>,
+}
+
+impl std::fmt::Debug for HookedServerCertVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HookedServerCertVerifier")
+            .field("standard", &self.standard)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ServerCertVerifier for HookedServerCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, Error> {
+        let default_verification = self
+            .standard
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+            .map(|_| ());
+
+        let server_name = server_name.to_str();
+
+        let ctx = TlsServerCertificateContext {
+            server_name: server_name.as_ref(),
+            end_entity,
+            intermediates,
+            default_verification: &default_verification,
+            standard: self.standard.as_ref(),
+            ocsp_response,
+            now,
+        };
+
+        match self.hook.verify(&ctx) {
+            Ok(()) => Ok(ServerCertVerified::assertion()),
+            Err(reason) => Err(Error::General(format!(
+                "Server certificate rejected: {}",
+                reason
+            ))),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.standard.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, Error> {
+        self.standard.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.standard.supported_verify_schemes()
+    }
+}
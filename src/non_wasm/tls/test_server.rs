@@ -0,0 +1,104 @@
+//! A local rustls server for the TLS tests: hyper behind a `TlsAcceptor` with the
+//! `TEST_LOCALHOST_PEM` leaf, speaking h2 or HTTP/1.1 as the handshake
+//! negotiated. Every response body is the HTTP version the request came in with.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use my_tls::tokio_rustls::{
+    rustls::{
+        pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+        ServerConfig,
+    },
+    TlsAcceptor,
+};
+
+use super::test_certificates::{TEST_LOCALHOST_KEY_PEM, TEST_LOCALHOST_PEM};
+
+pub(crate) struct TestTlsServer {
+    pub port: u16,
+    connections: Arc<AtomicUsize>,
+}
+
+impl TestTlsServer {
+    /// `alpn_protocols` is what the server accepts, in its order of preference;
+    /// empty for a server that knows nothing of ALPN.
+    pub async fn start(alpn_protocols: &[&[u8]]) -> Self {
+        // The provider the client side uses: `_tls` alone does not install one.
+        let provider = my_tls::create_tls_client_config_ex(&None, false)
+            .unwrap()
+            .crypto_provider()
+            .clone();
+
+        let mut server_config = ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CertificateDer::from_pem_slice(TEST_LOCALHOST_PEM.as_bytes()).unwrap()],
+                PrivateKeyDer::from_pem_slice(TEST_LOCALHOST_KEY_PEM.as_bytes()).unwrap(),
+            )
+            .unwrap();
+        server_config.alpn_protocols = alpn_protocols.iter().map(|p| p.to_vec()).collect();
+
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let accepted = connections.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((stream, _)) = listener.accept().await else {
+                    return;
+                };
+                accepted.fetch_add(1, Ordering::Relaxed);
+
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let Ok(tls_stream) = acceptor.accept(stream).await else {
+                        return;
+                    };
+
+                    let (_, session) = tls_stream.get_ref();
+                    let h2 = session.alpn_protocol() == Some(b"h2".as_slice());
+
+                    serve(tls_stream, h2).await;
+                });
+            }
+        });
+
+        Self { port, connections }
+    }
+
+    /// The TCP connections accepted so far.
+    pub fn get_connections(&self) -> usize {
+        self.connections.load(Ordering::Relaxed)
+    }
+}
+
+async fn serve<TStream: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static>(
+    stream: TStream,
+    h2: bool,
+) {
+    let service =
+        hyper::service::service_fn(|req: hyper::Request<hyper::body::Incoming>| async move {
+            let version = format!("{:?}", req.version());
+            Ok::<_, std::convert::Infallible>(hyper::Response::new(http_body_util::Full::new(
+                bytes::Bytes::from(version),
+            )))
+        });
+
+    let io = hyper_util::rt::TokioIo::new(stream);
+    if h2 {
+        let _ = hyper::server::conn::http2::Builder::new(hyper_util::rt::TokioExecutor::new())
+            .serve_connection(io, service)
+            .await;
+    } else {
+        let _ = hyper::server::conn::http1::Builder::new()
+            .serve_connection(io, service)
+            .await;
+    }
+}
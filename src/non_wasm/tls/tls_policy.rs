@@ -0,0 +1,249 @@
+use std::{
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
+use my_tls::tokio_rustls::rustls::{
+    client::danger::ServerCertVerifier, crypto::CryptoProvider, ClientConfig, ProtocolVersion,
+    SupportedProtocolVersion,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13,
+}
+
+impl TlsVersion {
+    fn as_protocol_version(&self) -> ProtocolVersion {
+        match self {
+            Self::Tls12 => ProtocolVersion::TLSv1_2,
+            Self::Tls13 => ProtocolVersion::TLSv1_3,
+        }
+    }
+}
+
+/// Which TLS versions and cipher suites a connection may negotiate, applied on
+/// top of the TLS provider's defaults (TLS 1.2 and 1.3, the provider's suites).
+///
+/// Cipher suites are given by their IANA names, as rustls prints them:
+/// `TLS13_AES_256_GCM_SHA384`, `TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256`, … The list
+/// is checked against the provider when the first connection is made: a name the
+/// provider does not implement, an empty version range, or a list without a
+/// single suite for the allowed versions fails the connection instead of being
+/// silently ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TlsPolicy {
+    min_version: TlsVersion,
+    max_version: TlsVersion,
+    cipher_suites: Option<Vec<String>>,
+}
+
+impl Default for TlsPolicy {
+    fn default() -> Self {
+        Self {
+            min_version: TlsVersion::Tls12,
+            max_version: TlsVersion::Tls13,
+            cipher_suites: None,
+        }
+    }
+}
+
+impl TlsPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tls13_only() -> Self {
+        Self::new().set_min_version(TlsVersion::Tls13)
+    }
+
+    pub fn set_min_version(mut self, version: TlsVersion) -> Self {
+        self.min_version = version;
+        self
+    }
+
+    pub fn set_max_version(mut self, version: TlsVersion) -> Self {
+        self.max_version = version;
+        self
+    }
+
+    /// Restricts the cipher suites to this list; the order of preference stays
+    /// the provider's.
+    pub fn set_cipher_suites<S: Into<String>>(
+        mut self,
+        cipher_suites: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.cipher_suites = Some(cipher_suites.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub(crate) fn get_fingerprint(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    fn allows(&self, version: ProtocolVersion) -> bool {
+        [TlsVersion::Tls12, TlsVersion::Tls13]
+            .into_iter()
+            .filter(|allowed| *allowed >= self.min_version && *allowed <= self.max_version)
+            .any(|allowed| allowed.as_protocol_version() == version)
+    }
+
+    /// `provider` with only the suites the policy allows.
+    fn filter_provider(&self, provider: &CryptoProvider) -> Result<CryptoProvider, String> {
+        if let Some(cipher_suites) = self.cipher_suites.as_ref() {
+            for name in cipher_suites {
+                let known = provider
+                    .cipher_suites
+                    .iter()
+                    .any(|suite| format!("{:?}", suite.suite()) == *name);
+
+                if !known {
+                    return Err(format!(
+                        "Cipher suite '{}' is not supported by the TLS provider",
+                        name
+                    ));
+                }
+            }
+        }
+
+        let mut result = provider.clone();
+        result.cipher_suites.retain(|suite| {
+            let name = format!("{:?}", suite.suite());
+            self.allows(suite.version().version)
+                && match self.cipher_suites.as_ref() {
+                    Some(cipher_suites) => cipher_suites.contains(&name),
+                    None => true,
+                }
+        });
+
+        if result.cipher_suites.is_empty() {
+            return Err(format!(
+                "No cipher suite left for TLS {:?}..={:?}",
+                self.min_version, self.max_version
+            ));
+        }
+
+        Ok(result)
+    }
+
+    /// A `ClientConfig` has no setters for versions or suites, so the policy
+    /// rebuilds `base` — the one `my_tls` made — with `verifier`, the one every
+    /// config of these settings gets, policy or not. What `base` was set up with
+    /// is carried over.
+    pub(crate) fn apply(
+        &self,
+        base: ClientConfig,
+        verifier: Arc<dyn ServerCertVerifier>,
+    ) -> Result<ClientConfig, String> {
+        let provider = Arc::new(self.filter_provider(base.crypto_provider())?);
+
+        let versions: Vec<&'static SupportedProtocolVersion> =
+            my_tls::tokio_rustls::rustls::ALL_VERSIONS
+                .iter()
+                .copied()
+                .filter(|version| self.allows(version.version))
+                .collect();
+
+        let mut result = ClientConfig::builder_with_provider(provider)
+            .with_protocol_versions(&versions)
+            .map_err(|err| err.to_string())?
+            .dangerous()
+            .with_custom_certificate_verifier(verifier)
+            .with_no_client_auth();
+
+        result.alpn_protocols = base.alpn_protocols;
+        result.resumption = base.resumption;
+        result.max_fragment_size = base.max_fragment_size;
+        result.client_auth_cert_resolver = base.client_auth_cert_resolver;
+        result.enable_sni = base.enable_sni;
+        result.key_log = base.key_log;
+        result.enable_secret_extraction = base.enable_secret_extraction;
+        result.enable_early_data = base.enable_early_data;
+        result.time_provider = base.time_provider;
+        result.cert_decompressors = base.cert_decompressors;
+        result.cert_compressors = base.cert_compressors;
+        result.cert_compression_cache = base.cert_compression_cache;
+
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_range_is_inclusive() {
+        let policy = TlsPolicy::new();
+        assert!(policy.allows(ProtocolVersion::TLSv1_2));
+        assert!(policy.allows(ProtocolVersion::TLSv1_3));
+
+        let policy = TlsPolicy::tls13_only();
+        assert!(!policy.allows(ProtocolVersion::TLSv1_2));
+        assert!(policy.allows(ProtocolVersion::TLSv1_3));
+
+        let policy = TlsPolicy::new().set_max_version(TlsVersion::Tls12);
+        assert!(policy.allows(ProtocolVersion::TLSv1_2));
+        assert!(!policy.allows(ProtocolVersion::TLSv1_3));
+
+        let empty = TlsPolicy::tls13_only().set_max_version(TlsVersion::Tls12);
+        assert!(!empty.allows(ProtocolVersion::TLSv1_2));
+        assert!(!empty.allows(ProtocolVersion::TLSv1_3));
+    }
+
+    // rustls has no getter for the verifier of a config; its `Debug` output is
+    // the one view of it.
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    fn get_verifier_debug(client_config: &ClientConfig) -> String {
+        let debug = format!("{:?}", client_config);
+        let start = debug.find("verifier: ").unwrap();
+        let end = start + debug[start..].find(", cert_decompressors").unwrap();
+        debug[start..end].to_string()
+    }
+
+    // Building a config needs a provider, which `_tls` alone does not install.
+    #[cfg(any(feature = "with-ring-tls", feature = "with-rust-tls"))]
+    #[test]
+    fn a_policy_trusts_what_the_config_without_one_trusts() {
+        let settings = crate::non_wasm::tls::TlsClientSettings {
+            client_certificate: None,
+            client_identity: None,
+            accept_invalid_certificate: false,
+            root_certificates: None,
+            certificate_pins: None,
+            server_certificate_verifier: None,
+            tls_policy: None,
+            key_log: None,
+            client_configs: None,
+        };
+
+        let mut with_policy = settings.clone();
+        with_policy.tls_policy = Some(Arc::new(TlsPolicy::tls13_only()));
+
+        let without_policy = settings.create_client_config().unwrap();
+        let with_policy = with_policy.create_client_config().unwrap();
+
+        assert!(get_verifier_debug(&without_policy).contains("WebPkiServerVerifier"));
+        assert_eq!(
+            get_verifier_debug(&with_policy),
+            get_verifier_debug(&without_policy)
+        );
+    }
+
+    #[test]
+    fn cipher_suites_are_part_of_the_fingerprint() {
+        let all = TlsPolicy::new();
+        let restricted = TlsPolicy::new().set_cipher_suites(["TLS13_AES_256_GCM_SHA384"]);
+
+        assert_ne!(all.get_fingerprint(), restricted.get_fingerprint());
+        assert_eq!(
+            restricted.get_fingerprint(),
+            TlsPolicy::new()
+                .set_cipher_suites(vec!["TLS13_AES_256_GCM_SHA384".to_string()])
+                .get_fingerprint()
+        );
+    }
+}
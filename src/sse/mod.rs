@@ -0,0 +1,475 @@
+//! Server-Sent Events: the `text/event-stream` parser and the reconnection
+//! policy behind `FlUrl::get_event_stream`. Target-agnostic — each backend reads
+//! the body its own way (hyper on native, a `ReadableStream` under wasm) and
+//! feeds the chunks through here.
+
+use std::time::Duration;
+
+const DEFAULT_RECONNECT_DELAY: Duration = Duration::from_secs(3);
+const DEFAULT_MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+// A line longer than this is taken for a body that is not an event stream, not
+// buffered on: a server that never sends a line break would otherwise grow it
+// without end.
+const MAX_EVENT_STREAM_LINE_SIZE: usize = 64 * 1024;
+
+/// One event of an event stream, as dispatched by a blank line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The last event ID of the stream when this event was dispatched: set by
+    /// an `id:` field of this event or of one before it, and sent back as
+    /// `Last-Event-ID` on a reconnect. `None` once the server has reset it with
+    /// an empty `id:`.
+    pub id: Option<String>,
+    /// The `event:` field, `message` when the event had none.
+    pub event: String,
+    /// The `data:` lines of the event, joined by `\n`.
+    pub data: String,
+    /// The `retry:` field of this event, in milliseconds on the wire. Also
+    /// applied to the reconnects that follow.
+    pub retry: Option<Duration>,
+}
+
+impl SseEvent {
+    /// The `data` of the event as JSON.
+    pub fn get_json<T: serde::de::DeserializeOwned>(&self) -> Result<T, crate::FlUrlError> {
+        Ok(serde_json::from_str(&self.data)?)
+    }
+}
+
+/// How `FlUrl::get_event_stream_with` reconnects.
+///
+/// After a connection that delivered events ends, the next one is made after
+/// the reconnect delay — 3 s unless set here, and replaced by whatever the
+/// server sends in a `retry:` field. A connection that fails — a transport
+/// error, a `502` / `503` / `504` / `408` / `429` — doubles the delay for the
+/// next attempt, up to `max_reconnect_delay`; `max_reconnect_attempts` gives up
+/// after that many failures in a row. `last_event_id` resumes a stream a
+/// previous process was reading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventStreamOptions {
+    pub(crate) reconnect_delay: Duration,
+    pub(crate) max_reconnect_delay: Duration,
+    pub(crate) max_reconnect_attempts: Option<usize>,
+    pub(crate) last_event_id: Option<String>,
+}
+
+impl EventStreamOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    pub fn set_max_reconnect_delay(mut self, max_reconnect_delay: Duration) -> Self {
+        self.max_reconnect_delay = max_reconnect_delay;
+        self
+    }
+
+    pub fn set_max_reconnect_attempts(mut self, max_reconnect_attempts: usize) -> Self {
+        self.max_reconnect_attempts = Some(max_reconnect_attempts);
+        self
+    }
+
+    pub fn set_last_event_id(mut self, last_event_id: impl Into<String>) -> Self {
+        self.last_event_id = Some(last_event_id.into());
+        self
+    }
+}
+
+impl Default for EventStreamOptions {
+    fn default() -> Self {
+        Self {
+            reconnect_delay: DEFAULT_RECONNECT_DELAY,
+            max_reconnect_delay: DEFAULT_MAX_RECONNECT_DELAY,
+            max_reconnect_attempts: None,
+            last_event_id: None,
+        }
+    }
+}
+
+/// What one connection attempt came to, for [`Reconnect::on_response`].
+pub(crate) enum ResponseVerdict {
+    /// `200` with a `text/event-stream` body: read it.
+    Stream,
+    /// `204`: the server asks the client to stop.
+    End,
+    /// A status worth another attempt after a back-off.
+    Retry,
+}
+
+/// The reconnection state of one event stream, shared by both backends.
+pub(crate) struct Reconnect {
+    options: EventStreamOptions,
+    // The delay after a connection that ended normally; `retry:` replaces it.
+    reconnect_delay: Duration,
+    // Failures since the last connection that delivered anything.
+    failed_attempts: usize,
+    connected_before: bool,
+}
+
+impl Reconnect {
+    pub fn new(options: EventStreamOptions) -> Self {
+        Self {
+            reconnect_delay: options.reconnect_delay,
+            options,
+            failed_attempts: 0,
+            connected_before: false,
+        }
+    }
+
+    /// How long to wait before the next connection: nothing for the first,
+    /// the reconnect delay after a connection that ended, doubled for every
+    /// failure in a row since.
+    pub fn get_delay(&self) -> Duration {
+        if !self.connected_before && self.failed_attempts == 0 {
+            return Duration::ZERO;
+        }
+
+        let factor = 1u32 << self.failed_attempts.saturating_sub(1).min(16);
+        self.reconnect_delay
+            .saturating_mul(factor)
+            .min(self.options.max_reconnect_delay.max(self.reconnect_delay))
+    }
+
+    /// Counts a failed attempt; `false` once `max_reconnect_attempts` is used up.
+    pub fn on_failure(&mut self) -> bool {
+        self.failed_attempts += 1;
+
+        match self.options.max_reconnect_attempts {
+            Some(max) => self.failed_attempts <= max,
+            None => true,
+        }
+    }
+
+    /// A connection was answered with an event stream: the next one waits at
+    /// least the reconnect delay, even if this one closes without a byte.
+    pub fn on_connected(&mut self) {
+        self.connected_before = true;
+    }
+
+    /// A connection has delivered bytes: the failures before it are forgiven.
+    pub fn on_data(&mut self) {
+        self.failed_attempts = 0;
+        self.connected_before = true;
+    }
+
+    pub fn on_retry(&mut self, retry: Duration) {
+        self.reconnect_delay = retry;
+    }
+
+    /// Sorts the status and `Content-Type` of a response.
+    pub fn on_response(
+        &self,
+        status_code: u16,
+        content_type: Option<&str>,
+    ) -> Result<ResponseVerdict, crate::FlUrlError> {
+        match status_code {
+            200 => {}
+            204 => return Ok(ResponseVerdict::End),
+            408 | 429 | 502 | 503 | 504 => return Ok(ResponseVerdict::Retry),
+            _ => return Err(crate::FlUrlError::UnexpectedStatusCode(status_code)),
+        }
+
+        let media_type = content_type
+            .and_then(|content_type| content_type.split(';').next())
+            .map(|media_type| media_type.trim());
+
+        match media_type {
+            Some(media_type) if media_type.eq_ignore_ascii_case("text/event-stream") => {
+                Ok(ResponseVerdict::Stream)
+            }
+            _ => Err(crate::FlUrlError::InvalidEventStream(format!(
+                "Content-Type is {:?}, not text/event-stream",
+                content_type
+            ))),
+        }
+    }
+}
+
+/// The `text/event-stream` parser of the HTML standard, fed chunk by chunk: a
+/// chunk may end anywhere, in a line, in a UTF-8 sequence or between the CR and
+/// LF of a line break.
+pub(crate) struct SseParser {
+    line: Vec<u8>,
+    // The chunk before ended on a CR: a LF that starts this one belongs to it.
+    after_cr: bool,
+    // A BOM is only skipped at the very start of the stream.
+    at_stream_start: bool,
+    data: String,
+    has_data: bool,
+    event: Option<String>,
+    retry: Option<Duration>,
+    last_event_id: Option<String>,
+}
+
+impl SseParser {
+    pub fn new(last_event_id: Option<String>) -> Self {
+        Self {
+            line: Vec::new(),
+            after_cr: false,
+            at_stream_start: true,
+            data: String::new(),
+            has_data: false,
+            event: None,
+            retry: None,
+            last_event_id,
+        }
+    }
+
+    /// The ID a reconnect sends as `Last-Event-ID`.
+    pub fn get_last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// For a new connection: what was left of an event the one before did not
+    /// finish is dropped, the last event ID is kept.
+    pub fn reset(&mut self) {
+        self.line.clear();
+        self.after_cr = false;
+        self.at_stream_start = true;
+        self.data.clear();
+        self.has_data = false;
+        self.event = None;
+        self.retry = None;
+    }
+
+    /// Parses `chunk` and pushes the events it completes to `events`. Returns
+    /// the reconnection time of a `retry:` field, if the chunk had one, and
+    /// fails on a line longer than `MAX_EVENT_STREAM_LINE_SIZE`.
+    pub fn feed(
+        &mut self,
+        mut chunk: &[u8],
+        events: &mut Vec<SseEvent>,
+    ) -> Result<Option<Duration>, crate::FlUrlError> {
+        let mut retry = None;
+
+        if self.after_cr {
+            self.after_cr = false;
+            if let Some(rest) = chunk.strip_prefix(b"\n") {
+                chunk = rest;
+            }
+        }
+
+        while let Some(pos) = chunk.iter().position(|b| *b == b'\r' || *b == b'\n') {
+            self.line.extend_from_slice(&chunk[..pos]);
+            self.check_line_size()?;
+
+            if chunk[pos] == b'\r' {
+                match chunk.get(pos + 1) {
+                    Some(b'\n') => chunk = &chunk[pos + 2..],
+                    Some(_) => chunk = &chunk[pos + 1..],
+                    None => {
+                        self.after_cr = true;
+                        chunk = &[];
+                    }
+                }
+            } else {
+                chunk = &chunk[pos + 1..];
+            }
+
+            let line = std::mem::take(&mut self.line);
+            if let Some(line_retry) = self.process_line(&line, events) {
+                retry = Some(line_retry);
+            }
+        }
+
+        self.line.extend_from_slice(chunk);
+        self.check_line_size()?;
+
+        Ok(retry)
+    }
+
+    fn check_line_size(&self) -> Result<(), crate::FlUrlError> {
+        if self.line.len() > MAX_EVENT_STREAM_LINE_SIZE {
+            return Err(crate::FlUrlError::InvalidEventStream(format!(
+                "A line runs over {} bytes",
+                MAX_EVENT_STREAM_LINE_SIZE
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn process_line(&mut self, line: &[u8], events: &mut Vec<SseEvent>) -> Option<Duration> {
+        let mut line = String::from_utf8_lossy(line);
+
+        if self.at_stream_start {
+            self.at_stream_start = false;
+            if let Some(rest) = line.strip_prefix('\u{feff}') {
+                line = rest.to_string().into();
+            }
+        }
+
+        if line.is_empty() {
+            self.dispatch(events);
+            return None;
+        }
+
+        if line.starts_with(':') {
+            return None;
+        }
+
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (&*line, ""),
+        };
+
+        match field {
+            "event" => self.event = Some(value.to_string()),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+                self.has_data = true;
+            }
+            // An ID with a NUL in it is ignored, an empty one resets it.
+            "id" if !value.contains('\0') => {
+                self.last_event_id = if value.is_empty() {
+                    None
+                } else {
+                    Some(value.to_string())
+                };
+            }
+            "retry" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+                if let Ok(millis) = value.parse::<u64>() {
+                    let retry = Duration::from_millis(millis);
+                    self.retry = Some(retry);
+                    return Some(retry);
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    fn dispatch(&mut self, events: &mut Vec<SseEvent>) {
+        let event = self.event.take();
+        let retry = self.retry.take();
+
+        if !self.has_data {
+            return;
+        }
+
+        let mut data = std::mem::take(&mut self.data);
+        self.has_data = false;
+        data.pop(); // the `\n` after the last line
+
+        events.push(SseEvent {
+            id: self.last_event_id.clone(),
+            event: event.unwrap_or_else(|| "message".to_string()),
+            data,
+            retry,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(chunks: &[&[u8]]) -> Vec<SseEvent> {
+        let mut parser = SseParser::new(None);
+        let mut events = Vec::new();
+        for chunk in chunks {
+            parser.feed(chunk, &mut events).unwrap();
+        }
+        events
+    }
+
+    #[test]
+    fn events_are_parsed_across_chunk_borders_and_line_endings() {
+        let events = parse(&[
+            b"\xEF\xBB\xBF: a comment\r",
+            b"\nid: 1\revent: update\ndata: first",
+            b" line\r\ndata:second line\r",
+            b"\r",
+            b"data: {\"n\":2}\nretry: 1500\n\n",
+        ]);
+
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    id: Some("1".to_string()),
+                    event: "update".to_string(),
+                    data: "first line\nsecond line".to_string(),
+                    retry: None,
+                },
+                SseEvent {
+                    id: Some("1".to_string()),
+                    event: "message".to_string(),
+                    data: "{\"n\":2}".to_string(),
+                    retry: Some(Duration::from_millis(1500)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn a_block_without_data_is_not_dispatched_but_its_id_sticks() {
+        let mut parser = SseParser::new(Some("7".to_string()));
+        let mut events = Vec::new();
+
+        parser
+            .feed(b"id: 8\nevent: ping\n\nretry: soon\ndata\n\n", &mut events)
+            .unwrap();
+
+        assert_eq!(parser.get_last_event_id(), Some("8"));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event, "message");
+        assert_eq!(events[0].data, "");
+        assert_eq!(events[0].retry, None);
+
+        // An event cut off by the connection is dropped with it.
+        parser.feed(b"data: half", &mut events).unwrap();
+        parser.reset();
+        parser.feed(b"id\ndata: whole\n\n", &mut events).unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].data, "whole");
+        assert_eq!(events[1].id, None);
+    }
+
+    #[test]
+    fn a_line_that_never_ends_fails_the_stream() {
+        let mut parser = SseParser::new(None);
+        let mut events = Vec::new();
+
+        let chunk = vec![b'x'; MAX_EVENT_STREAM_LINE_SIZE / 2];
+        parser.feed(b"data: ", &mut events).unwrap();
+        parser.feed(&chunk, &mut events).unwrap();
+
+        let result = parser.feed(&chunk, &mut events);
+        assert!(matches!(
+            result,
+            Err(crate::FlUrlError::InvalidEventStream(_))
+        ));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn the_delay_doubles_with_every_failure_up_to_the_cap() {
+        let mut reconnect = Reconnect::new(
+            EventStreamOptions::new()
+                .set_reconnect_delay(Duration::from_secs(1))
+                .set_max_reconnect_delay(Duration::from_secs(5))
+                .set_max_reconnect_attempts(4),
+        );
+
+        assert_eq!(reconnect.get_delay(), Duration::ZERO);
+
+        let mut delays = Vec::new();
+        while reconnect.on_failure() {
+            delays.push(reconnect.get_delay().as_secs());
+        }
+        assert_eq!(delays, vec![1, 2, 4, 5]);
+
+        reconnect.on_data();
+        reconnect.on_retry(Duration::from_millis(250));
+        assert_eq!(reconnect.get_delay(), Duration::from_millis(250));
+    }
+}
@@ -20,6 +20,23 @@ impl FlUrlHttpConnectionsCache {
     /// No-op under wasm: `fetch` owns the connection pool.
     pub fn clear(&self) {}
 
+    /// No-op under wasm: the browser decides how many H2 connections it opens.
+    pub fn set_h2_max_concurrent_streams(&self, _value: usize) {}
+
+    /// No-op under wasm: the browser decides how many H2 connections it opens.
+    pub fn set_h2_max_connections_per_key(&self, _value: usize) {}
+
+    /// No-op under wasm: the browser decides how many H2 connections it opens.
+    pub fn set_h2_idle_scale_down_timeout(&self, _timeout: std::time::Duration) {}
+
+    /// No-op under wasm: the browser owns its connections and their liveness checks.
+    pub fn set_h2_keep_alive(
+        &self,
+        _interval: std::time::Duration,
+        _ack_timeout: std::time::Duration,
+    ) {
+    }
+
     /// No-op under wasm: `fetch` owns the connection pool.
     pub fn gc(&self, _reuse_connection_timeout_seconds: i64) {}
 }
@@ -3,6 +3,7 @@
 
 use std::cell::Cell;
 use std::rc::Rc;
+use std::time::Duration;
 
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
@@ -143,6 +144,128 @@ pub(crate) async fn read_response_body(
     }
 }
 
+/// A response body read chunk by chunk off its `ReadableStream`, for bodies that
+/// are not meant to end — an event stream. Dropping it before the end cancels
+/// the stream, which closes the connection.
+///
+/// When `body_timeout_millis` is set, every read is bounded by it on the
+/// request's `AbortController`, as the native `response_body_timeout` bounds
+/// every chunk; the abort is surfaced as [`FlUrlError::Timeout`].
+pub(crate) struct BodyReader {
+    // The `ReadableStreamDefaultReader`, driven through `Reflect`: its typed
+    // web-sys binding has changed shape between versions.
+    reader: JsValue,
+    controller: Option<AbortController>,
+    body_timeout_millis: Option<i32>,
+    done: bool,
+}
+
+impl BodyReader {
+    pub(crate) fn new(
+        response: &Response,
+        controller: Option<AbortController>,
+        body_timeout_millis: Option<i32>,
+    ) -> Result<Self, FlUrlError> {
+        let body = response
+            .body()
+            .ok_or_else(|| FlUrlError::FetchError("response has no body".to_string()))?;
+
+        Ok(Self {
+            reader: call_method(body.as_ref(), "getReader")?,
+            controller,
+            body_timeout_millis,
+            done: false,
+        })
+    }
+
+    /// The next chunk as the browser hands it over, or `None` at the end.
+    pub(crate) async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, FlUrlError> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let promise = call_method(&self.reader, "read")?
+            .dyn_into::<js_sys::Promise>()
+            .map_err(|_| FlUrlError::FetchError("read() did not return a Promise".to_string()))?;
+
+        let timed_out = Rc::new(Cell::new(false));
+        let timer_handle = match (self.controller.as_ref(), self.body_timeout_millis) {
+            (Some(controller), Some(millis)) => {
+                set_abort_timer(controller, millis, timed_out.clone())
+            }
+            _ => None,
+        };
+
+        let result = JsFuture::from(promise).await;
+
+        if let Some(handle) = timer_handle {
+            clear_timer(handle);
+        }
+
+        let result = match result {
+            Ok(result) => result,
+            Err(err) => {
+                if timed_out.get() || is_abort_error(&err) {
+                    return Err(FlUrlError::Timeout);
+                }
+                return Err(js_to_err(err));
+            }
+        };
+
+        let done = js_sys::Reflect::get(&result, &JsValue::from_str("done"))
+            .map_err(js_to_err)?
+            .as_bool()
+            .unwrap_or(false);
+        if done {
+            self.done = true;
+            return Ok(None);
+        }
+
+        let value =
+            js_sys::Reflect::get(&result, &JsValue::from_str("value")).map_err(js_to_err)?;
+        let chunk = value.dyn_into::<js_sys::Uint8Array>().map_err(|_| {
+            FlUrlError::FetchError("read() did not return a Uint8Array".to_string())
+        })?;
+
+        Ok(Some(chunk.to_vec()))
+    }
+}
+
+impl Drop for BodyReader {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = call_method(&self.reader, "cancel");
+        }
+    }
+}
+
+/// Resolves after `duration` on the `setTimeout` of the page or worker — at
+/// once where there is none.
+pub(crate) async fn sleep(duration: Duration) {
+    let millis = duration.as_millis().min(i32::MAX as u128) as i32;
+
+    let promise = js_sys::Promise::new(&mut |resolve, _reject| {
+        let global = js_sys::global();
+        let scheduled = if let Some(window) = global.dyn_ref::<web_sys::Window>() {
+            window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis)
+                .is_ok()
+        } else if let Some(scope) = global.dyn_ref::<web_sys::WorkerGlobalScope>() {
+            scope
+                .set_timeout_with_callback_and_timeout_and_arguments_0(&resolve, millis)
+                .is_ok()
+        } else {
+            false
+        };
+
+        if !scheduled {
+            let _ = resolve.call0(&JsValue::UNDEFINED);
+        }
+    });
+
+    let _ = JsFuture::from(promise).await;
+}
+
 /// Reads all response headers into a `(name, value)` list. The browser lower-cases
 /// header names, so callers should look them up case-insensitively.
 pub(crate) fn collect_headers(headers: &Headers) -> Vec<(String, String)> {
@@ -237,6 +360,15 @@ fn clear_timer(handle: i32) {
     }
 }
 
+fn call_method(target: &JsValue, name: &str) -> Result<JsValue, FlUrlError> {
+    let method = js_sys::Reflect::get(target, &JsValue::from_str(name))
+        .map_err(js_to_err)?
+        .dyn_into::<js_sys::Function>()
+        .map_err(|_| FlUrlError::FetchError(format!("{} is not a function", name)))?;
+
+    method.call0(target).map_err(js_to_err)
+}
+
 fn is_abort_error(err: &JsValue) -> bool {
     err.dyn_ref::<web_sys::DomException>()
         .map(|exception| exception.name() == "AbortError")
@@ -4,7 +4,7 @@ use std::fmt::Debug;
 use serde::de::DeserializeOwned;
 use my_http_utils::UrlBuilder;
 
-use crate::wasm::fetch::{collect_headers, read_response_body};
+use crate::wasm::fetch::{collect_headers, read_response_body, BodyReader};
 use crate::{FlUrlError, FlUrlReadingHeaderError};
 
 /// wasm counterpart of the native `FlUrlResponse`.
@@ -132,6 +132,16 @@ impl FlUrlResponse {
         self.get_body_as_str().await
     }
 
+    /// The body as it arrives, for `get_event_stream`. Has to come before any
+    /// `get_body_*` call, which reads the body whole.
+    pub(crate) fn into_body_reader(mut self) -> Result<BodyReader, FlUrlError> {
+        let response = self.response.take().ok_or_else(|| {
+            FlUrlError::FetchError("response body has already been consumed".to_string())
+        })?;
+
+        BodyReader::new(&response, self.controller.take(), self.body_timeout_millis)
+    }
+
     /// Always `false` under wasm — kept for API parity. The browser owns the
     /// connection, so FlUrl never decides to drop it.
     pub fn drop_connection(&self) -> bool {
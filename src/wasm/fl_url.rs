@@ -17,6 +17,7 @@ pub enum FlUrlMode {
     H2,
     Http1NoHyper,
     Http1Hyper,
+    Auto,
 }
 
 impl FlUrlMode {
@@ -56,6 +57,7 @@ pub struct FlUrl {
     pub do_not_reuse_connection: bool,
     pub connections_cache: Option<Arc<FlUrlHttpConnectionsCache>>,
     pub compress_body: bool,
+    compression: crate::CompressionOptions,
     pub decompress_gzip_response: bool,
     pub print_input_request: bool,
     pub reuse_connection_timeout_sec: i64,
@@ -107,6 +109,7 @@ impl FlUrl {
             do_not_reuse_connection: false,
             connections_cache: None,
             compress_body: false,
+            compression: Default::default(),
             decompress_gzip_response: false,
             print_input_request: false,
             reuse_connection_timeout_sec: 120,
@@ -115,8 +118,15 @@ impl FlUrl {
         })
     }
 
-    pub fn compress(mut self) -> Self {
+    pub fn compress(self) -> Self {
+        self.compress_with(crate::CompressionOptions::default())
+    }
+
+    /// Compresses the request body as `options` says, like the native backend:
+    /// the codecs are pure Rust, so they run in the browser too.
+    pub fn compress_with(mut self, options: crate::CompressionOptions) -> Self {
         self.compress_body = true;
+        self.compression = options;
         self
     }
 
@@ -127,6 +137,13 @@ impl FlUrl {
         self
     }
 
+    /// No-op under wasm, like [`Self::accept_gzip`]: `Accept-Encoding` is a
+    /// header the browser owns, and it decodes whatever it negotiated. Kept for
+    /// API parity.
+    pub fn accept_compression(self, _encodings: &[crate::Encoding]) -> Self {
+        self
+    }
+
     pub fn set_not_used_connection_timeout(mut self, timeout: Duration) -> Self {
         self.not_used_connection_timeout = timeout;
         self.reuse_connection_timeout_sec = (timeout.as_secs_f64().ceil() as i64).max(1);
@@ -143,6 +160,12 @@ impl FlUrl {
         self
     }
 
+    /// No-op under wasm: the browser owns its connections and their liveness
+    /// checks. Kept for API parity.
+    pub fn set_h2_keep_alive(self, _interval: Duration, _ack_timeout: Duration) -> Self {
+        self
+    }
+
     /// Retries the request up to `max_retries` extra times on failure. Only
     /// idempotent methods are replayed (a POST/PATCH that may have reached the
     /// server is never re-sent).
@@ -161,8 +184,8 @@ impl FlUrl {
         self
     }
 
-    /// Stored for API parity. Under wasm the body is buffered by `fetch` in one
-    /// `arrayBuffer()` call, so there is no separate body-read timeout to enforce.
+    /// Bounds the `arrayBuffer()` read of the body, or each read of an event
+    /// stream, on the request's `AbortController`; unbounded by default.
     pub fn set_response_body_timeout(mut self, timeout: Duration) -> Self {
         self.response_body_timeout = Some(timeout);
         self
@@ -313,6 +336,52 @@ impl FlUrl {
             .await
     }
 
+    /// Reads the url as a Server-Sent Events stream with the default
+    /// reconnection. See [`Self::get_event_stream_with`].
+    pub fn get_event_stream(self) -> crate::FlUrlEventStream {
+        self.get_event_stream_with(crate::EventStreamOptions::new())
+    }
+
+    /// Reads the url as a Server-Sent Events stream, off the `fetch` body as it
+    /// streams in. Same reconnection as the native backend; the browser's own
+    /// `EventSource` is not used, so custom headers go out with every connection.
+    pub fn get_event_stream_with(
+        self,
+        options: crate::EventStreamOptions,
+    ) -> crate::FlUrlEventStream {
+        crate::FlUrlEventStream::new(self, options)
+    }
+
+    /// The same GET once more, for the next connection of `get_event_stream`:
+    /// every setting and header of this request but `Last-Event-ID`, which the
+    /// stream sets itself.
+    pub(crate) fn fork_for_event_stream(&self) -> Self {
+        let mut headers = FlUrlHeaders::new();
+        for (name, value) in self.headers.iter() {
+            if !name.eq_ignore_ascii_case("Last-Event-ID") {
+                headers.add(name, value);
+            }
+        }
+
+        Self {
+            url_builder: self.url_builder.clone(),
+            headers,
+            accept_invalid_certificate: self.accept_invalid_certificate,
+            not_used_connection_timeout: self.not_used_connection_timeout,
+            request_timeout: self.request_timeout,
+            response_body_timeout: self.response_body_timeout,
+            do_not_reuse_connection: self.do_not_reuse_connection,
+            connections_cache: self.connections_cache.clone(),
+            compress_body: false,
+            compression: self.compression,
+            decompress_gzip_response: self.decompress_gzip_response,
+            print_input_request: self.print_input_request,
+            reuse_connection_timeout_sec: self.reuse_connection_timeout_sec,
+            mode: self.mode,
+            max_retries: self.max_retries,
+        }
+    }
+
     pub async fn head(self) -> Result<FlUrlResponse, FlUrlError> {
         self.run("HEAD", true, HttpRequestBody::Empty, None).await
     }
@@ -448,7 +517,7 @@ impl FlUrl {
             _ => {}
         }
 
-        let body_bytes = self.compile(method, body, debug);
+        let body_bytes = self.compile(method, body, debug)?;
         let url = self.url_builder.to_string();
         let header_list: Vec<(String, String)> = self
             .headers
@@ -498,14 +567,14 @@ impl FlUrl {
     }
 
     /// Fills in `Content-Type` (from the body) if absent, materializes the body,
-    /// optionally writes the debug string and gzip-compresses the body — mirroring
+    /// optionally writes the debug string and compresses the body — mirroring
     /// the native `compile_request`.
     fn compile(
         &mut self,
         method: &str,
         body: HttpRequestBody,
         debug: Option<&mut String>,
-    ) -> Vec<u8> {
+    ) -> Result<Vec<u8>, FlUrlError> {
         if let Some(content_type) = body.get_content_type() {
             if !self.headers.has_header("Content-Type") {
                 self.headers.add("Content-Type", content_type.as_str());
@@ -519,27 +588,25 @@ impl FlUrl {
         }
 
         if self.compress_body {
-            bytes = self.compress_body(bytes);
+            bytes = self.compress_body(bytes)?;
         }
 
-        bytes
+        Ok(bytes)
     }
 
-    fn compress_body(&mut self, body: Vec<u8>) -> Vec<u8> {
-        use flate2::{write::GzEncoder, Compression};
-        use std::io::Write;
-
-        if body.len() < 64 {
-            return body;
+    fn compress_body(&mut self, body: Vec<u8>) -> Result<Vec<u8>, FlUrlError> {
+        if body.len() < self.compression.min_size {
+            return Ok(body);
         }
 
+        let result = self.compression.compress(body.as_slice())?;
+
         if !self.headers.has_header("Content-Encoding") {
-            self.headers.add("Content-Encoding", "gzip");
+            self.headers
+                .add("Content-Encoding", self.compression.algorithm.as_str());
         }
 
-        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-        encoder.write_all(body.as_slice()).unwrap();
-        encoder.finish().unwrap()
+        Ok(result)
     }
 
     fn fill_debug(&self, out: &mut String, method: &str, body: &[u8]) {
@@ -0,0 +1,146 @@
+use std::collections::VecDeque;
+
+use crate::sse::{Reconnect, ResponseVerdict, SseParser};
+use crate::wasm::fetch::{sleep, BodyReader};
+use crate::{EventStreamOptions, FlUrl, FlUrlError, SseEvent};
+
+/// wasm counterpart of the native `FlUrlEventStream`: the same events and the
+/// same reconnection, read off the `ReadableStream` of the `fetch` body.
+///
+/// A failed `fetch`, a body that breaks off or a read that runs past
+/// `response_body_timeout` is reconnected on.
+pub struct FlUrlEventStream {
+    fl_url: FlUrl,
+    reconnect: Reconnect,
+    parser: SseParser,
+    // The connection being read; `None` between connections.
+    reader: Option<BodyReader>,
+    // Parsed from a chunk, not handed out yet.
+    events: VecDeque<SseEvent>,
+    ended: bool,
+}
+
+impl FlUrlEventStream {
+    pub(crate) fn new(fl_url: FlUrl, options: EventStreamOptions) -> Self {
+        let last_event_id = options.last_event_id.clone().or_else(|| {
+            fl_url
+                .headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("Last-Event-ID"))
+                .map(|(_, value)| value.to_string())
+        });
+
+        Self {
+            fl_url,
+            reconnect: Reconnect::new(options),
+            parser: SseParser::new(last_event_id),
+            reader: None,
+            events: VecDeque::new(),
+            ended: false,
+        }
+    }
+
+    /// The ID the next reconnect sends as `Last-Event-ID`.
+    pub fn get_last_event_id(&self) -> Option<&str> {
+        self.parser.get_last_event_id()
+    }
+
+    /// The next event, connecting or reconnecting as needed; `None` once the
+    /// server has answered `204`.
+    pub async fn next_event(&mut self) -> Result<Option<SseEvent>, FlUrlError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Ok(Some(event));
+            }
+
+            if self.ended {
+                return Ok(None);
+            }
+
+            let mut reader = match self.reader.take() {
+                Some(reader) => reader,
+                None => match self.connect().await? {
+                    Some(reader) => reader,
+                    None => {
+                        self.ended = true;
+                        return Ok(None);
+                    }
+                },
+            };
+
+            match reader.next_chunk().await {
+                Ok(Some(chunk)) => {
+                    self.reconnect.on_data();
+
+                    let mut events = Vec::new();
+                    if let Some(retry) = self.parser.feed(&chunk, &mut events)? {
+                        self.reconnect.on_retry(retry);
+                    }
+                    self.events.extend(events);
+                    self.reader = Some(reader);
+                }
+                Ok(None) => {}
+                Err(err) => {
+                    if !self.reconnect.on_failure() {
+                        return Err(err);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Makes connections until one answers with an event stream (`Some`) or
+    /// with `204` (`None`).
+    async fn connect(&mut self) -> Result<Option<BodyReader>, FlUrlError> {
+        self.parser.reset();
+
+        loop {
+            let delay = self.reconnect.get_delay();
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+
+            let mut request = self.fl_url.fork_for_event_stream();
+            if !request.headers.has_header("Accept") {
+                request.headers.add("Accept", "text/event-stream");
+            }
+            if !request.headers.has_header("Cache-Control") {
+                request.headers.add("Cache-Control", "no-cache");
+            }
+            if let Some(last_event_id) = self.parser.get_last_event_id() {
+                request.headers.add("Last-Event-ID", last_event_id);
+            }
+
+            let response = match request.get().await {
+                Ok(response) => response,
+                Err(err) => {
+                    let transport_error =
+                        matches!(err, FlUrlError::Timeout | FlUrlError::FetchError(_));
+                    if transport_error && self.reconnect.on_failure() {
+                        continue;
+                    }
+                    return Err(err);
+                }
+            };
+
+            let status_code = response.get_status_code();
+            let content_type = response
+                .get_header_case_insensitive("content-type")
+                .ok()
+                .flatten();
+
+            match self.reconnect.on_response(status_code, content_type)? {
+                ResponseVerdict::Stream => {
+                    self.reconnect.on_connected();
+                    return Ok(Some(response.into_body_reader()?));
+                }
+                ResponseVerdict::End => return Ok(None),
+                ResponseVerdict::Retry => {
+                    if !self.reconnect.on_failure() {
+                        return Err(FlUrlError::UnexpectedStatusCode(status_code));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -15,11 +15,13 @@ mod connections_cache;
 mod fetch;
 mod fl_response;
 mod fl_url;
+mod fl_url_event_stream;
 mod fl_url_headers;
 mod into_fl_url;
 
 pub use connections_cache::*;
 pub use fl_response::*;
 pub use fl_url::*;
+pub use fl_url_event_stream::*;
 pub use fl_url_headers::*;
 pub use into_fl_url::*;
@@ -0,0 +1,64 @@
+//! Setup shared by the download tests: a scratch directory, and a local
+//! HTTP/1.1 server whose responses the test writes out by hand.
+
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::net::{tcp::OwnedWriteHalf, TcpListener};
+
+/// A fresh, empty directory under the system temp dir, for `name` in this test
+/// process.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("flurl-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Serves each connection on a task of its own: reads one request head and
+/// hands it, lowercased, to `respond` with the write half of the connection,
+/// which is closed once `respond` drops it. Returns the url of the server and
+/// every request head it got, in order.
+pub async fn start_server<TRespond, TFuture>(respond: TRespond) -> (String, Arc<Mutex<Vec<String>>>)
+where
+    TRespond: Fn(String, OwnedWriteHalf) -> TFuture + Send + Sync + 'static,
+    TFuture: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let heads = Arc::new(Mutex::new(Vec::new()));
+    let respond = Arc::new(respond);
+
+    let recorded = heads.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((socket, _)) = listener.accept().await else {
+                return;
+            };
+            let respond = respond.clone();
+            let recorded = recorded.clone();
+
+            tokio::spawn(async move {
+                let (read_half, write_half) = socket.into_split();
+                let mut reader = BufReader::new(read_half);
+
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    let read = reader.read_line(&mut line).await.unwrap();
+                    if read == 0 || line == "\r\n" {
+                        break;
+                    }
+                    head.push_str(&line.to_lowercase());
+                }
+                recorded.lock().unwrap().push(head.clone());
+
+                respond(head, write_half).await;
+            });
+        }
+    });
+
+    (format!("http://{}", addr), heads)
+}
@@ -0,0 +1,186 @@
+//! `download_parallel` against a real socket: the file comes down as several
+//! ranges at once and is stitched together at the right offsets.
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+
+use common::temp_dir;
+use flurl::{FlUrl, FlUrlError, SaveToFileOptions};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+const ETAG: &str = "\"v1\"";
+
+/// Serves `body` with `Accept-Ranges: bytes`, one connection per request. The
+/// HEAD is answered with `head_status_line` and announces `head_etag`; a ranged
+/// GET is answered with its range as long as its `If-Range` names `etag`, with
+/// the whole body otherwise. Every request head is recorded, lowercased.
+async fn start_server(
+    body: Vec<u8>,
+    head_status_line: &'static str,
+    head_etag: &'static str,
+    etag: &'static str,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let body = Arc::new(body);
+
+    common::start_server(move |head, mut write_half| {
+        let body = body.clone();
+
+        async move {
+            let range = head
+                .lines()
+                .find_map(|line| line.strip_prefix("range: bytes="))
+                .filter(|_| head.contains(&format!("if-range: {}", etag)))
+                .map(|range| {
+                    let (start, end) = range.trim().split_once('-').unwrap();
+                    (
+                        start.parse::<usize>().unwrap(),
+                        end.parse::<usize>().unwrap(),
+                    )
+                });
+
+            let (status_line, content_range, sent) = match range {
+                Some((start, end)) => (
+                    "206 Partial Content",
+                    format!("content-range: bytes {}-{}/{}\r\n", start, end, body.len()),
+                    &body[start..=end],
+                ),
+                None => ("200 OK", String::new(), &body[..]),
+            };
+
+            let is_head = head.starts_with("head ");
+            let status_line = if is_head {
+                head_status_line
+            } else {
+                status_line
+            };
+            let response_head = format!(
+                "HTTP/1.1 {}\r\netag: {}\r\naccept-ranges: bytes\r\n{}content-length: {}\r\nconnection: close\r\n\r\n",
+                status_line,
+                if is_head { head_etag } else { etag },
+                content_range,
+                sent.len()
+            );
+            write_half
+                .write_all(response_head.as_bytes())
+                .await
+                .unwrap();
+
+            if !is_head {
+                let _ = write_half.write_all(sent).await;
+            }
+            let _ = write_half.flush().await;
+        }
+    })
+    .await
+}
+
+#[tokio::test]
+async fn the_file_comes_down_in_ranges_stitched_at_their_offsets() {
+    let body: Vec<u8> = (0..1200 * 1024 + 17).map(|i| (i % 251) as u8).collect();
+    let (url, heads) = start_server(body.clone(), "200 OK", ETAG, ETAG).await;
+
+    let dir = temp_dir("parallel");
+    let path = dir.join("image.iso");
+
+    let saved = FlUrl::new(url.as_str())
+        .append_path_segment("image.iso")
+        .download_parallel_with(
+            &path,
+            4,
+            256 * 1024,
+            SaveToFileOptions::new().set_sha256(true),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    assert_eq!(saved.bytes_written, body.len() as u64);
+    assert_eq!(
+        saved.sha256.unwrap(),
+        <[u8; 32]>::from(Sha256::digest(&body))
+    );
+
+    let heads = heads.lock().unwrap();
+    assert!(heads[0].starts_with("head "), "{}", heads[0]);
+
+    let mut ranges: Vec<_> = heads[1..]
+        .iter()
+        .map(|head| {
+            assert!(head.contains(&format!("if-range: {}", ETAG)), "{}", head);
+            head.lines()
+                .find_map(|line| line.strip_prefix("range: bytes="))
+                .unwrap()
+                .trim()
+                .to_string()
+        })
+        .collect();
+    ranges.sort();
+    assert_eq!(
+        ranges,
+        vec![
+            "0-262143",
+            "1048576-1228816",
+            "262144-524287",
+            "524288-786431",
+            "786432-1048575",
+        ]
+    );
+
+    // The temp file is gone with the rename.
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_file_that_changed_is_not_stitched_together() {
+    let body: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    // The ranges carry `If-Range` on the ETag of the HEAD, which the file no
+    // longer has by the time they are sent: they come back whole.
+    let (url, _heads) = start_server(body, "200 OK", ETAG, "\"v2\"").await;
+
+    let dir = temp_dir("parallel-changed");
+    let path = dir.join("image.iso");
+    std::fs::write(&path, b"previous version").unwrap();
+
+    let result = FlUrl::new(url.as_str())
+        .download_parallel(&path, 2, 16 * 1024)
+        .await;
+
+    assert!(
+        matches!(result, Err(FlUrlError::DownloadMismatch(_))),
+        "{:?}",
+        result
+    );
+    assert_eq!(std::fs::read(&path).unwrap(), b"previous version");
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_head_the_server_refuses_falls_back_to_one_get() {
+    let body: Vec<u8> = (0..64 * 1024).map(|i| (i % 251) as u8).collect();
+    let (url, heads) = start_server(body.clone(), "405 Method Not Allowed", ETAG, ETAG).await;
+
+    let dir = temp_dir("parallel-no-head");
+    let path = dir.join("image.iso");
+
+    let saved = FlUrl::new(url.as_str())
+        .download_parallel(&path, 2, 16 * 1024)
+        .await
+        .unwrap();
+
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    assert_eq!(saved.bytes_written, body.len() as u64);
+
+    let heads = heads.lock().unwrap();
+    assert_eq!(heads.len(), 2);
+    assert!(heads[1].starts_with("get "), "{}", heads[1]);
+    assert!(!heads[1].contains("range:"), "{}", heads[1]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,135 @@
+//! `download_resumable` against a real socket: a body that breaks off mid-way is
+//! picked up with a range request instead of being downloaded again.
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use common::temp_dir;
+use flurl::{FlUrl, FlUrlError, ResumableDownloadOptions};
+use tokio::io::AsyncWriteExt;
+
+const ETAG: &str = "\"v1\"";
+
+/// Serves `body` with ranges. The n-th response is cut off after
+/// `break_after[n]` bytes when there is such an entry; every request head is
+/// recorded, lowercased.
+async fn start_server(
+    body: Vec<u8>,
+    status: u16,
+    break_after: Vec<usize>,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let body = Arc::new(body);
+    let break_after = Arc::new(Mutex::new(break_after.into_iter()));
+
+    common::start_server(move |head, mut write_half| {
+        let body = body.clone();
+        let break_after = break_after.clone();
+
+        async move {
+            let start = head
+                .lines()
+                .find_map(|line| line.strip_prefix("range: bytes="))
+                .filter(|_| head.contains(&format!("if-range: {}", ETAG)))
+                .map(|range| range.trim_end_matches('-').parse::<usize>().unwrap());
+
+            let (status_line, content_range, sent) = match (status, start) {
+                (200, Some(start)) => (
+                    "206 Partial Content",
+                    format!(
+                        "content-range: bytes {}-{}/{}\r\n",
+                        start,
+                        body.len() - 1,
+                        body.len()
+                    ),
+                    &body[start..],
+                ),
+                (200, None) => ("200 OK", String::new(), &body[..]),
+                _ => ("404 Not Found", String::new(), &b"not here"[..]),
+            };
+
+            let response_head = format!(
+                "HTTP/1.1 {}\r\netag: {}\r\n{}content-length: {}\r\nconnection: close\r\n\r\n",
+                status_line,
+                ETAG,
+                content_range,
+                sent.len()
+            );
+            write_half
+                .write_all(response_head.as_bytes())
+                .await
+                .unwrap();
+
+            let cut_off = break_after.lock().unwrap().next();
+            let sent = match cut_off {
+                Some(len) => &sent[..len],
+                None => sent,
+            };
+            let _ = write_half.write_all(sent).await;
+            let _ = write_half.flush().await;
+            // Dropping the write half closes the connection, short of the
+            // announced length when the body was cut off.
+        }
+    })
+    .await
+}
+
+fn options() -> ResumableDownloadOptions {
+    ResumableDownloadOptions::new()
+        .set_backoff(Duration::from_millis(10), Duration::from_millis(50))
+        .set_sha256(true)
+}
+
+#[tokio::test]
+async fn a_body_cut_off_mid_way_is_resumed_with_a_range() {
+    let body: Vec<u8> = (0..256 * 1024).map(|i| (i % 251) as u8).collect();
+    let (url, heads) = start_server(body.clone(), 200, vec![100 * 1024, 50 * 1024]).await;
+
+    let dir = temp_dir("resume");
+    let path = dir.join("backup.tar");
+
+    let saved = FlUrl::new(url.as_str())
+        .append_path_segment("backup.tar")
+        .download_resumable_with(&path, options())
+        .await
+        .unwrap();
+
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    assert_eq!(saved.bytes_written, body.len() as u64);
+    assert!(saved.sha256.is_some());
+
+    let heads = heads.lock().unwrap();
+    assert_eq!(heads.len(), 3);
+    assert!(!heads[0].contains("range:"), "{}", heads[0]);
+    assert!(heads[1].contains("range: bytes=102400-"), "{}", heads[1]);
+    assert!(
+        heads[1].contains(&format!("if-range: {}", ETAG)),
+        "{}",
+        heads[1]
+    );
+    assert!(heads[2].contains("range: bytes=153600-"), "{}", heads[2]);
+
+    // The `.part` and its validator are gone with the rename.
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn an_error_status_is_not_written_to_the_file() {
+    let (url, _heads) = start_server(Vec::new(), 404, Vec::new()).await;
+
+    let dir = temp_dir("resume-404");
+    let path = dir.join("missing.bin");
+
+    let result = FlUrl::new(url.as_str())
+        .download_resumable_with(&path, options())
+        .await;
+
+    assert!(matches!(result, Err(FlUrlError::UnexpectedStatusCode(404))));
+    assert!(!path.exists());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
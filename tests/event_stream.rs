@@ -0,0 +1,173 @@
+//! `get_event_stream` against a real socket: events parsed across writes, a
+//! reconnect that carries `Last-Event-ID` after the server-sent `retry:` delay, a
+//! `204` that ends the stream, a retried `503` before a body that is not an event
+//! stream, and a `Cache-Control` of the caller's that is sent as the only one.
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::time::{Duration, Instant};
+
+use flurl::{EventStreamOptions, FlUrl, FlUrlError};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{tcp::OwnedWriteHalf, TcpListener};
+
+/// Accepts the next connection and reads the request head off it.
+async fn accept_request(listener: &TcpListener) -> (String, OwnedWriteHalf) {
+    let (socket, _) = listener.accept().await.unwrap();
+    let (read_half, write_half) = socket.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).await.unwrap();
+        if read == 0 || line == "\r\n" {
+            break;
+        }
+        head.push_str(&line);
+    }
+
+    (head, write_half)
+}
+
+fn header<'s>(head: &'s str, name: &str) -> Option<&'s str> {
+    head.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        if key.trim().eq_ignore_ascii_case(name) {
+            Some(value.trim())
+        } else {
+            None
+        }
+    })
+}
+
+/// A chunked `text/event-stream` body, one chunk per piece, then the end of it.
+async fn send_event_stream(mut write_half: OwnedWriteHalf, pieces: &[&str]) {
+    write_half
+        .write_all(
+            b"HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\n\
+              transfer-encoding: chunked\r\nconnection: close\r\n\r\n",
+        )
+        .await
+        .unwrap();
+
+    for piece in pieces {
+        let chunk = format!("{:x}\r\n{}\r\n", piece.len(), piece);
+        write_half.write_all(chunk.as_bytes()).await.unwrap();
+        write_half.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    write_half.write_all(b"0\r\n\r\n").await.unwrap();
+}
+
+async fn send_empty(mut write_half: OwnedWriteHalf, status_line: &str, content_type: &str) {
+    let head = format!(
+        "HTTP/1.1 {}\r\ncontent-type: {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+        status_line, content_type
+    );
+    write_half.write_all(head.as_bytes()).await.unwrap();
+}
+
+#[tokio::test]
+async fn the_stream_reconnects_with_the_last_event_id_until_a_204() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = tokio::spawn(async move {
+        let (head, write_half) = accept_request(&listener).await;
+        assert_eq!(header(&head, "accept"), Some("text/event-stream"));
+        assert_eq!(header(&head, "last-event-id"), None);
+        send_event_stream(
+            write_half,
+            &[
+                ": keep-alive\n\nretry: 100\nid: 1\nevent: greet",
+                "ing\ndata: hello\n\nid: 2\r\ndata: {\"n\":2}\r\n\r",
+                "\ndata: cut off with the connection",
+            ],
+        )
+        .await;
+        let first_closed_at = Instant::now();
+
+        let (head, write_half) = accept_request(&listener).await;
+        assert!(first_closed_at.elapsed() >= Duration::from_millis(90));
+        assert_eq!(header(&head, "last-event-id"), Some("2"));
+        send_event_stream(write_half, &["data: after the reconnect\n\n"]).await;
+
+        let (_, write_half) = accept_request(&listener).await;
+        send_empty(write_half, "204 No Content", "text/plain").await;
+    });
+
+    let mut events = FlUrl::new(format!("http://127.0.0.1:{}", port)).get_event_stream();
+
+    let event = events.next_event().await.unwrap().unwrap();
+    assert_eq!(event.id.as_deref(), Some("1"));
+    assert_eq!(event.event, "greeting");
+    assert_eq!(event.data, "hello");
+    assert_eq!(event.retry, Some(Duration::from_millis(100)));
+
+    let event = events.next_event().await.unwrap().unwrap();
+    assert_eq!(event.id.as_deref(), Some("2"));
+    assert_eq!(event.event, "message");
+    assert_eq!(event.get_json::<serde_json::Value>().unwrap()["n"], 2);
+
+    let event = events.next_event().await.unwrap().unwrap();
+    assert_eq!(event.id.as_deref(), Some("2"));
+    assert_eq!(event.data, "after the reconnect");
+
+    assert!(events.next_event().await.unwrap().is_none());
+    assert!(events.next_event().await.unwrap().is_none());
+    assert_eq!(events.get_last_event_id(), Some("2"));
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn a_503_is_retried_and_a_body_that_is_not_an_event_stream_fails() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = tokio::spawn(async move {
+        let (_, write_half) = accept_request(&listener).await;
+        send_empty(write_half, "503 Service Unavailable", "text/plain").await;
+
+        let (head, write_half) = accept_request(&listener).await;
+        assert_eq!(header(&head, "last-event-id"), Some("41"));
+        send_empty(write_half, "200 OK", "application/json").await;
+    });
+
+    let mut events = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .with_header("Last-Event-ID", "41")
+        .get_event_stream_with(
+            EventStreamOptions::new().set_reconnect_delay(Duration::from_millis(10)),
+        );
+
+    let result = events.next_event().await;
+    assert!(matches!(result, Err(FlUrlError::InvalidEventStream(_))));
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn headers_set_on_the_request_are_not_doubled() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = tokio::spawn(async move {
+        let (head, write_half) = accept_request(&listener).await;
+        send_empty(write_half, "204 No Content", "text/plain").await;
+        head
+    });
+
+    let mut events = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .with_header("Cache-Control", "no-store")
+        .get_event_stream();
+    assert!(events.next_event().await.unwrap().is_none());
+
+    let head = server.await.unwrap();
+    let cache_control: Vec<_> = head
+        .lines()
+        .filter(|line| line.to_lowercase().starts_with("cache-control:"))
+        .collect();
+    assert_eq!(cache_control.len(), 1, "{}", head);
+    assert_eq!(header(&head, "cache-control"), Some("no-store"));
+}
@@ -0,0 +1,113 @@
+//! `into_multipart` against a real socket: a `multipart/byteranges` answer to a
+//! multi-range request, read part by part, and a response that is not multipart
+//! turned away up front.
+#![cfg(not(target_arch = "wasm32"))]
+
+use flurl::{FlUrl, FlUrlError};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+/// Answers one request with `content_type` and `body`, written in `pieces`
+/// writes so that the parts arrive cut at arbitrary places.
+async fn serve_one_response(
+    listener: TcpListener,
+    content_type: &str,
+    body: Vec<u8>,
+    pieces: usize,
+) {
+    let (socket, _) = listener.accept().await.unwrap();
+    let (read_half, mut write_half) = socket.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    loop {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).await.unwrap();
+        if read == 0 || line == "\r\n" {
+            break;
+        }
+    }
+
+    let head = format!(
+        "HTTP/1.1 206 Partial Content\r\ncontent-type: {}\r\ncontent-length: {}\r\n\r\n",
+        content_type,
+        body.len()
+    );
+    write_half.write_all(head.as_bytes()).await.unwrap();
+
+    for piece in body.chunks(body.len() / pieces + 1) {
+        write_half.write_all(piece).await.unwrap();
+        write_half.flush().await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    }
+}
+
+#[tokio::test]
+async fn byteranges_are_read_part_by_part() {
+    let first: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+    let second = b"the tail of the file".to_vec();
+
+    let mut body = Vec::new();
+    body.extend_from_slice(b"--3d6b6a416f9b5\r\nContent-Type: application/octet-stream\r\n");
+    body.extend_from_slice(b"Content-Range: bytes 0-99999/200000\r\n\r\n");
+    body.extend_from_slice(&first);
+    body.extend_from_slice(b"\r\n--3d6b6a416f9b5\r\nContent-Type: application/octet-stream\r\n");
+    body.extend_from_slice(b"Content-Range: bytes 199980-199999/200000\r\n\r\n");
+    body.extend_from_slice(&second);
+    body.extend_from_slice(b"\r\n--3d6b6a416f9b5--\r\n");
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(serve_one_response(
+        listener,
+        "multipart/byteranges; boundary=3d6b6a416f9b5",
+        body,
+        7,
+    ));
+
+    let mut multipart = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .with_header("Range", "bytes=0-99999,199980-199999")
+        .get()
+        .await
+        .unwrap()
+        .into_multipart()
+        .unwrap();
+
+    let mut part = multipart.next_part().await.unwrap().unwrap();
+    assert_eq!(part.get_content_range(), Some(0..=99_999));
+
+    let mut received = Vec::new();
+    while let Some(chunk) = part.get_next_chunk().await.unwrap() {
+        received.extend_from_slice(&chunk);
+    }
+    assert_eq!(received, first);
+
+    let part = multipart.next_part().await.unwrap().unwrap();
+    assert_eq!(part.get_content_range(), Some(199_980..=199_999));
+    assert_eq!(part.receive_body().await.unwrap(), second);
+
+    assert!(multipart.next_part().await.unwrap().is_none());
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn a_response_that_is_not_multipart_is_turned_away() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(serve_one_response(
+        listener,
+        "application/json",
+        b"{}".to_vec(),
+        1,
+    ));
+
+    let result = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .get()
+        .await
+        .unwrap()
+        .into_multipart();
+
+    assert!(matches!(result, Err(FlUrlError::InvalidMultipart(_))));
+
+    server.await.unwrap();
+}
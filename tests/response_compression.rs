@@ -0,0 +1,279 @@
+//! Compressed responses end-to-end: the server encodes the body with whatever the
+//! `enc` query parameter names, and `accept_compression` has to hand back the
+//! original bytes with headers that describe them.
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::io::Write;
+
+use bytes::Bytes;
+use flurl::{Encoding, FlUrl};
+use http_body_util::Full;
+use hyper::service::service_fn;
+use hyper_util::rt::TokioIo;
+use tokio::net::TcpListener;
+
+const PAYLOAD: &str = "{\"items\":[\"alpha\",\"beta\",\"gamma\",\"alpha\",\"beta\",\"gamma\"]}";
+
+fn encode(encoding: &str, data: &[u8]) -> Vec<u8> {
+    match encoding {
+        "gzip" => {
+            let mut encoder =
+                flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        }
+        "deflate" => {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            encoder.write_all(data).unwrap();
+            encoder.finish().unwrap()
+        }
+        "br" => {
+            let mut result = Vec::new();
+            {
+                let mut writer = brotli::CompressorWriter::new(&mut result, 4096, 5, 22);
+                writer.write_all(data).unwrap();
+            }
+            result
+        }
+        "zstd" => {
+            ruzstd::encoding::compress_to_vec(data, ruzstd::encoding::CompressionLevel::Fastest)
+        }
+        // Not something FlUrl decodes: the body has to come through untouched.
+        "x-custom" => data.to_vec(),
+        other => panic!("unexpected encoding {}", other),
+    }
+}
+
+async fn handle(
+    req: hyper::Request<hyper::body::Incoming>,
+) -> Result<hyper::Response<Full<Bytes>>, std::convert::Infallible> {
+    let accept_encoding = req
+        .headers()
+        .get(hyper::header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("-")
+        .to_string();
+
+    // `enc=gzip.br` means gzip first, then br — the order of `Content-Encoding`.
+    let codings: Vec<String> = req
+        .uri()
+        .query()
+        .and_then(|query| query.strip_prefix("enc="))
+        .map(|value| value.split('.').map(|coding| coding.to_string()).collect())
+        .unwrap_or_default();
+
+    let mut body = PAYLOAD.as_bytes().to_vec();
+    for coding in &codings {
+        body = encode(coding, &body);
+    }
+
+    let response = hyper::Response::builder()
+        .header("x-accept-encoding", accept_encoding)
+        .header(hyper::header::CONTENT_ENCODING, codings.join(", "))
+        .header(hyper::header::CONTENT_LENGTH, body.len())
+        .body(Full::new(Bytes::from(body)))
+        .unwrap();
+
+    Ok(response)
+}
+
+async fn start_server() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        loop {
+            let Ok((stream, _)) = listener.accept().await else {
+                return;
+            };
+
+            tokio::spawn(async move {
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(TokioIo::new(stream), service_fn(handle))
+                    .await;
+            });
+        }
+    });
+
+    format!("http://{}", addr)
+}
+
+#[tokio::test]
+async fn every_encoding_is_decoded_and_the_headers_follow() {
+    let url = start_server().await;
+
+    for encoding in ["gzip", "deflate", "br", "zstd"] {
+        let mut response = FlUrl::new(url.as_str())
+            .append_query_param("enc", Some(encoding))
+            .accept_compression(&[
+                Encoding::Brotli,
+                Encoding::Zstd,
+                Encoding::Gzip,
+                Encoding::Deflate,
+            ])
+            .get()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.get_header("x-accept-encoding").unwrap(),
+            Some("br, zstd, gzip, deflate")
+        );
+
+        let body = response.get_body_as_str().await.unwrap().to_string();
+        assert_eq!(body, PAYLOAD, "{}", encoding);
+
+        assert_eq!(response.get_header("content-encoding").unwrap(), None);
+        assert_eq!(
+            response.get_header("content-length").unwrap(),
+            Some(PAYLOAD.len().to_string().as_str())
+        );
+    }
+}
+
+#[tokio::test]
+async fn stacked_codings_are_undone_last_to_first() {
+    let url = start_server().await;
+
+    let mut response = FlUrl::new(url.as_str())
+        .append_query_param("enc", Some("gzip.br"))
+        .accept_compression(&[Encoding::Gzip, Encoding::Brotli])
+        .get()
+        .await
+        .unwrap();
+
+    assert_eq!(response.get_body_as_str().await.unwrap(), PAYLOAD);
+}
+
+#[tokio::test]
+async fn an_unknown_coding_leaves_the_body_as_it_came() {
+    let url = start_server().await;
+
+    let mut response = FlUrl::new(url.as_str())
+        .append_query_param("enc", Some("x-custom"))
+        .accept_compression(&[Encoding::Gzip])
+        .get()
+        .await
+        .unwrap();
+
+    assert_eq!(response.get_body_as_str().await.unwrap(), PAYLOAD);
+    assert_eq!(
+        response.get_header("content-encoding").unwrap(),
+        Some("x-custom")
+    );
+}
+
+#[tokio::test]
+async fn accept_gzip_still_advertises_gzip_only() {
+    let url = start_server().await;
+
+    let mut response = FlUrl::new(url.as_str())
+        .append_query_param("enc", Some("gzip"))
+        .accept_gzip()
+        .get()
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.get_header("x-accept-encoding").unwrap(),
+        Some("gzip")
+    );
+    assert_eq!(response.get_body_as_str().await.unwrap(), PAYLOAD);
+}
+
+#[tokio::test]
+async fn streamed_bodies_are_decoded_chunk_by_chunk() {
+    let url = start_server().await;
+
+    for encoding in ["gzip", "deflate", "br", "zstd", "gzip.br"] {
+        let response = FlUrl::new(url.as_str())
+            .append_query_param("enc", Some(encoding))
+            .accept_compression(&[Encoding::Gzip, Encoding::Brotli, Encoding::Zstd])
+            .get()
+            .await
+            .unwrap();
+
+        let mut stream = response.get_body_as_stream();
+
+        let headers = &stream.get_parts().headers;
+        assert!(headers.get(hyper::header::CONTENT_ENCODING).is_none());
+        assert!(headers.get(hyper::header::CONTENT_LENGTH).is_none());
+
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.get_next_chunk().await.unwrap() {
+            assert!(!chunk.is_empty());
+            body.extend_from_slice(&chunk);
+        }
+
+        assert_eq!(body, PAYLOAD.as_bytes(), "{}", encoding);
+    }
+}
+
+#[tokio::test]
+async fn into_hyper_response_body_is_decoded_as_it_is_polled() {
+    use http_body_util::BodyExt;
+
+    let url = start_server().await;
+
+    let response = FlUrl::new(url.as_str())
+        .append_query_param("enc", Some("br"))
+        .accept_compression(&[Encoding::Brotli])
+        .get()
+        .await
+        .unwrap()
+        .into_hyper_response();
+
+    assert!(response
+        .headers()
+        .get(hyper::header::CONTENT_ENCODING)
+        .is_none());
+
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    assert_eq!(body.as_ref(), PAYLOAD.as_bytes());
+}
+
+#[tokio::test]
+async fn streamed_bodies_stay_raw_without_accept_compression() {
+    let url = start_server().await;
+
+    let response = FlUrl::new(url.as_str())
+        .append_query_param("enc", Some("gzip"))
+        .get()
+        .await
+        .unwrap();
+
+    let mut stream = response.get_body_as_stream();
+    assert_eq!(
+        stream
+            .get_parts()
+            .headers
+            .get(hyper::header::CONTENT_ENCODING),
+        Some(&hyper::header::HeaderValue::from_static("gzip"))
+    );
+
+    let mut body = Vec::new();
+    while let Some(chunk) = stream.get_next_chunk().await.unwrap() {
+        body.extend_from_slice(&chunk);
+    }
+
+    assert_eq!(body, encode("gzip", PAYLOAD.as_bytes()));
+}
+
+#[tokio::test]
+async fn into_async_read_pipes_the_decoded_body_through_tokio_io() {
+    let url = start_server().await;
+
+    let response = FlUrl::new(url.as_str())
+        .append_query_param("enc", Some("br"))
+        .accept_compression(&[Encoding::Brotli])
+        .get()
+        .await
+        .unwrap();
+
+    let mut reader = response.get_body_as_stream().into_async_read();
+    let mut body = Vec::new();
+    tokio::io::copy(&mut reader, &mut body).await.unwrap();
+
+    assert_eq!(body, PAYLOAD.as_bytes());
+}
@@ -0,0 +1,191 @@
+//! `save_to_file` against a real socket: the body lands in the file whole, with
+//! the checksum of what was sent, a download that breaks off leaves the target
+//! as it was, and a bandwidth limit slows the read down.
+#![cfg(not(target_arch = "wasm32"))]
+
+mod common;
+
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::temp_dir;
+use flurl::{FlUrl, SaveToFileOptions};
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncWriteExt;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn payload(chunks: usize) -> Vec<u8> {
+    (0..chunks * CHUNK_SIZE).map(|i| (i % 251) as u8).collect()
+}
+
+/// Answers every request with `body`, announced in full by `Content-Length`.
+/// With `stall_after` the server stops writing after that many bytes and holds
+/// the connection open.
+async fn start_server(body: Vec<u8>, stall_after: Option<usize>) -> String {
+    let body = Arc::new(body);
+
+    let (url, _heads) = common::start_server(move |_head, mut write_half| {
+        let body = body.clone();
+
+        async move {
+            let head = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", body.len());
+            write_half.write_all(head.as_bytes()).await.unwrap();
+
+            let sent = stall_after.unwrap_or(body.len());
+            for chunk in body[..sent].chunks(CHUNK_SIZE) {
+                write_half.write_all(chunk).await.unwrap();
+            }
+            write_half.flush().await.unwrap();
+
+            if stall_after.is_some() {
+                tokio::time::sleep(Duration::from_secs(30)).await;
+            }
+        }
+    })
+    .await;
+
+    url
+}
+
+fn dir_entries(dir: &Path) -> Vec<String> {
+    std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn the_body_is_saved_whole_with_its_checksum() {
+    let body = payload(64);
+    let url = start_server(body.clone(), None).await;
+
+    let dir = temp_dir("save-whole");
+    let path = dir.join("artifact.bin");
+
+    let saved = FlUrl::new(url.as_str())
+        .append_path_segment("artifact.bin")
+        .get()
+        .await
+        .unwrap()
+        .save_to_file_with(
+            &path,
+            SaveToFileOptions::new().set_sha256(true).set_fsync(true),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(saved.path, path);
+    assert_eq!(saved.bytes_written, body.len() as u64);
+    assert_eq!(
+        saved.sha256.unwrap(),
+        <[u8; 32]>::from(Sha256::digest(&body))
+    );
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    assert_eq!(dir_entries(&dir), vec!["artifact.bin".to_string()]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn download_progress_follows_the_body_to_its_content_length() {
+    let body = payload(16);
+    let url = start_server(body.clone(), None).await;
+
+    let dir = temp_dir("save-progress");
+    let path = dir.join("artifact.bin");
+
+    let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = reported.clone();
+
+    FlUrl::new(url.as_str())
+        .get()
+        .await
+        .unwrap()
+        .on_download_progress(move |received, total| sink.lock().unwrap().push((received, total)))
+        .save_to_file(&path)
+        .await
+        .unwrap();
+
+    let reported = reported.lock().unwrap();
+    assert!(reported.len() > 1, "{:?}", reported);
+    assert!(reported.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(reported
+        .iter()
+        .all(|(_, total)| *total == Some(body.len() as u64)));
+    assert_eq!(reported.last().unwrap().0, body.len() as u64);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_bandwidth_limit_holds_the_download_back() {
+    // 128 KB/s, and 256 KB to read: the first 128 KB go on the full bucket, the
+    // rest takes a second.
+    let body = payload(4);
+
+    let url = start_server(body.clone(), None).await;
+
+    let dir = temp_dir("save-throttled");
+    let path = dir.join("artifact.bin");
+
+    let started = std::time::Instant::now();
+    FlUrl::new(url.as_str())
+        .set_bandwidth_limit(128 * 1024)
+        .get()
+        .await
+        .unwrap()
+        .save_to_file(&path)
+        .await
+        .unwrap();
+    let elapsed = started.elapsed();
+
+    assert_eq!(std::fs::read(&path).unwrap(), body);
+    assert!(elapsed >= Duration::from_millis(800), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+
+    // The buffered read is held to the same limit.
+    let url = start_server(body.clone(), None).await;
+
+    let started = std::time::Instant::now();
+    let received = FlUrl::new(url.as_str())
+        .set_bandwidth_limit(128 * 1024)
+        .get()
+        .await
+        .unwrap()
+        .receive_body()
+        .await
+        .unwrap();
+    let elapsed = started.elapsed();
+
+    assert_eq!(received, body);
+    assert!(elapsed >= Duration::from_millis(800), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn a_stalled_body_times_out_and_leaves_the_target_untouched() {
+    let body = payload(16);
+    let url = start_server(body, Some(4 * CHUNK_SIZE)).await;
+
+    let dir = temp_dir("save-stalled");
+    let path = dir.join("artifact.bin");
+    std::fs::write(&path, b"previous version").unwrap();
+
+    let result = FlUrl::new(url.as_str())
+        .set_response_body_timeout(Duration::from_millis(300))
+        .get()
+        .await
+        .unwrap()
+        .save_to_file(&path)
+        .await;
+
+    assert!(result.unwrap_err().is_timeout());
+    assert_eq!(std::fs::read(&path).unwrap(), b"previous version");
+    assert_eq!(dir_entries(&dir), vec!["artifact.bin".to_string()]);
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -312,21 +312,139 @@ async fn content_length_keeps_memory_flat_regardless_of_the_body_size() {
     assert_eq!(total, (4096 * CHUNK_SIZE) as u64);
 }
 
+/// Accepts one connection and reads a chunked request body into memory — for
+/// payloads small enough to keep, like a compressed one.
+async fn serve_one_chunked_request_kept(listener: TcpListener) -> (String, Vec<u8>) {
+    let (socket, _) = listener.accept().await.unwrap();
+    let (read_half, mut write_half) = socket.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    let head = read_head(&mut reader).await;
+    let mut body = Vec::new();
+
+    loop {
+        let mut size_line = String::new();
+        reader.read_line(&mut size_line).await.unwrap();
+
+        let size = usize::from_str_radix(size_line.trim().split(';').next().unwrap(), 16).unwrap();
+
+        if size == 0 {
+            let mut trailer = String::new();
+            let _ = reader.read_line(&mut trailer).await;
+            break;
+        }
+
+        let mut chunk = vec![0u8; size + 2];
+        reader.read_exact(&mut chunk).await.unwrap();
+        body.extend_from_slice(&chunk[..size]);
+    }
+
+    write_half
+        .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok")
+        .await
+        .unwrap();
+    write_half.flush().await.unwrap();
+
+    (head, body)
+}
+
 #[tokio::test]
-async fn compress_is_refused_for_a_streamed_body() {
-    let (publisher, body) = RequestBodyStream::<Vec<u8>>::new(1);
-    drop(publisher);
+async fn compress_gzips_a_streamed_body_on_the_fly() {
+    use std::io::Read;
 
-    let result = FlUrl::new("http://127.0.0.1:1")
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = tokio::spawn(serve_one_chunked_request_kept(listener));
+
+    const CHUNKS: usize = 16;
+
+    let (publisher, body) = RequestBodyStream::new(4);
+    publish_x_chunks(publisher, CHUNKS);
+
+    let mut response = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .append_path_segment("upload")
         .compress()
+        .set_timeout(Duration::from_secs(30))
+        // The compressed length is unknown, so the length given here can not be
+        // the framing: the body goes out chunked regardless.
+        .post_request_streamed(body, Some(CHUNKS * CHUNK_SIZE))
+        .await
+        .unwrap();
+
+    assert_eq!(response.get_status_code(), 200);
+    assert_eq!(response.get_body_as_str().await.unwrap(), "ok");
+
+    let (head, compressed) = server.await.unwrap();
+    let head = head.to_lowercase();
+    assert!(head.contains("content-encoding: gzip"), "{}", head);
+    assert!(head.contains("transfer-encoding: chunked"), "{}", head);
+    assert!(!head.contains("content-length"), "{}", head);
+
+    assert!(compressed.len() < CHUNK_SIZE);
+
+    let mut decoded = Vec::new();
+    flate2::read::MultiGzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded.len(), CHUNKS * CHUNK_SIZE);
+    assert!(decoded.iter().all(|byte| *byte == b'x'));
+}
+
+#[tokio::test]
+async fn compress_with_picks_the_algorithm_and_level_of_a_streamed_body() {
+    use std::io::Read;
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = tokio::spawn(serve_one_chunked_request_kept(listener));
+
+    const CHUNKS: usize = 4;
+
+    let (publisher, body) = RequestBodyStream::new(4);
+    publish_x_chunks(publisher, CHUNKS);
+
+    let mut options = flurl::CompressionOptions::new(flurl::Encoding::Brotli);
+    options.level = Some(9);
+
+    let mut response = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .append_path_segment("upload")
+        .compress_with(options)
+        .set_timeout(Duration::from_secs(30))
         .post_request_streamed(body, None)
+        .await
+        .unwrap();
+
+    assert_eq!(response.get_status_code(), 200);
+
+    let (head, compressed) = server.await.unwrap();
+    let head = head.to_lowercase();
+    assert!(head.contains("content-encoding: br"), "{}", head);
+
+    let mut decoded = Vec::new();
+    brotli::Decompressor::new(compressed.as_slice(), 4096)
+        .read_to_end(&mut decoded)
+        .unwrap();
+    assert_eq!(decoded.len(), CHUNKS * CHUNK_SIZE);
+    assert!(decoded.iter().all(|byte| *byte == b'x'));
+}
+
+#[tokio::test]
+async fn a_level_out_of_range_fails_the_request_before_it_is_sent() {
+    let result = FlUrl::new("http://127.0.0.1:1")
+        .compress_with(flurl::CompressionOptions {
+            algorithm: flurl::Encoding::Gzip,
+            level: Some(42),
+            min_size: 0,
+        })
+        .post(flurl::body::HttpRequestBody::from_raw_data(
+            vec![b'x'; 1024],
+            None,
+        ))
         .await;
 
-    match result {
-        Err(flurl::FlUrlError::StreamedBodyCanNotBeCompressed) => {}
-        Err(err) => panic!("expected StreamedBodyCanNotBeCompressed, got {:?}", err),
-        Ok(_) => panic!("compress() over a streamed body must not be silently ignored"),
-    }
+    assert!(matches!(result, Err(flurl::FlUrlError::CompressionError(_))));
 }
 
 #[tokio::test]
@@ -524,6 +642,205 @@ async fn streamed_put_with_a_length_uses_content_length_framing() {
     assert!(received.body_is_all_x);
 }
 
+#[tokio::test]
+async fn put_file_sends_the_file_with_its_length_and_content_type() {
+    const TOTAL: usize = 8 * CHUNK_SIZE + 17;
+
+    let path = std::env::temp_dir().join(format!("flurl-put-file-{}.txt", std::process::id()));
+    std::fs::write(&path, vec![b'x'; TOTAL]).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(serve_one_request(listener, true));
+
+    let mut response = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .append_path_segment("files")
+        .append_path_segment("notes.txt")
+        .do_not_reuse_connection()
+        .set_timeout(Duration::from_secs(30))
+        .put_file(&path)
+        .await
+        .unwrap();
+
+    assert_eq!(response.get_status_code(), 200);
+    let _ = response.get_body_as_slice().await.unwrap();
+
+    let received = server.await.unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert!(
+        received.head.starts_with("PUT /files/notes.txt HTTP/1.1"),
+        "unexpected request line: {}",
+        received.head
+    );
+    assert_eq!(
+        received.header("content-length"),
+        Some(TOTAL.to_string().as_str())
+    );
+    assert_eq!(received.header("content-type"), Some("text/plain"));
+    assert!(!received.has_header("transfer-encoding"));
+    assert_eq!(received.body_len, TOTAL);
+    assert!(received.body_is_all_x);
+}
+
+#[tokio::test]
+async fn upload_progress_reports_every_chunk_up_to_the_file_length() {
+    const TOTAL: usize = 4 * CHUNK_SIZE + 17;
+
+    let path = std::env::temp_dir().join(format!("flurl-progress-{}.bin", std::process::id()));
+    std::fs::write(&path, vec![b'x'; TOTAL]).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(serve_one_request(listener, true));
+
+    let reported = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = reported.clone();
+
+    let mut response = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .do_not_reuse_connection()
+        .on_upload_progress(move |sent, total| sink.lock().unwrap().push((sent, total)))
+        .put_file(&path)
+        .await
+        .unwrap();
+
+    let _ = response.get_body_as_slice().await.unwrap();
+    let received = server.await.unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(received.body_len, TOTAL);
+
+    let reported = reported.lock().unwrap();
+    assert!(reported.len() > 1, "{:?}", reported);
+    assert!(reported.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert!(reported.iter().all(|(_, total)| *total == Some(TOTAL as u64)));
+    assert_eq!(reported.last().unwrap().0, TOTAL as u64);
+}
+
+#[tokio::test]
+async fn a_streamed_form_goes_out_with_its_length_or_chunked() {
+    const FILE_LEN: usize = 4 * CHUNK_SIZE + 17;
+
+    let path = std::env::temp_dir().join(format!("flurl-form-{}.bin", std::process::id()));
+    std::fs::write(&path, vec![b'x'; FILE_LEN]).unwrap();
+
+    // Every part of a known size: the whole form is, too.
+    let form = flurl::FormDataStream::new()
+        .append_form_data_field("title", "Nightly backup")
+        .append_part(flurl::FormDataPart::file("archive", &path).await.unwrap());
+    let expected_len = form.get_content_length().unwrap() as usize;
+    let content_type = form.get_content_type();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(serve_one_request(listener, false));
+
+    let mut response = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .do_not_reuse_connection()
+        .set_timeout(Duration::from_secs(30))
+        .post_form_data_streamed(form)
+        .await
+        .unwrap();
+
+    let _ = response.get_body_as_slice().await.unwrap();
+    let received = server.await.unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert!(expected_len > FILE_LEN);
+    assert_eq!(received.header("content-type"), Some(content_type.as_str()));
+    assert_eq!(
+        received.header("content-length"),
+        Some(expected_len.to_string().as_str())
+    );
+    assert!(!received.has_header("transfer-encoding"));
+    assert_eq!(received.body_len, expected_len);
+
+    // A reader of unknown size: chunked.
+    let form = flurl::FormDataStream::new().append_part(flurl::FormDataPart::reader(
+        "log",
+        &b"line 1\nline 2\n"[..],
+        None,
+    ));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(serve_one_request(listener, false));
+
+    let mut response = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .do_not_reuse_connection()
+        .put_form_data_streamed(form)
+        .await
+        .unwrap();
+
+    let _ = response.get_body_as_slice().await.unwrap();
+    let received = server.await.unwrap();
+
+    assert!(received.head.starts_with("PUT / HTTP/1.1"), "{}", received.head);
+    assert_eq!(received.header("transfer-encoding"), Some("chunked"));
+    assert!(!received.has_header("content-length"));
+    assert!(received.body_len > 14);
+}
+
+#[tokio::test]
+async fn a_bandwidth_limit_holds_the_upload_back() {
+    // 128 KB/s, and a bit over 256 KB to send: the first 128 KB go on the full
+    // bucket, the rest takes a second.
+    const TOTAL: usize = 4 * CHUNK_SIZE + 17;
+
+    let path = std::env::temp_dir().join(format!("flurl-throttled-{}.bin", std::process::id()));
+    std::fs::write(&path, vec![b'x'; TOTAL]).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(serve_one_request(listener, true));
+
+    let started = std::time::Instant::now();
+    let mut response = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .do_not_reuse_connection()
+        .set_bandwidth_limit(128 * 1024)
+        .put_file(&path)
+        .await
+        .unwrap();
+
+    let _ = response.get_body_as_slice().await.unwrap();
+    let elapsed = started.elapsed();
+    let received = server.await.unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(received.body_len, TOTAL);
+    assert!(elapsed >= Duration::from_millis(800), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+}
+
+#[tokio::test]
+async fn post_file_keeps_a_content_type_set_by_the_caller() {
+    let path = std::env::temp_dir().join(format!("flurl-post-file-{}.json", std::process::id()));
+    std::fs::write(&path, vec![b'x'; CHUNK_SIZE]).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(serve_one_request(listener, true));
+
+    let mut response = FlUrl::new(format!("http://127.0.0.1:{}", port))
+        .do_not_reuse_connection()
+        .with_header("Content-Type", "application/x-ndjson")
+        .post_file(&path)
+        .await
+        .unwrap();
+
+    let _ = response.get_body_as_slice().await.unwrap();
+
+    let received = server.await.unwrap();
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(received.header_count("content-type"), 1);
+    assert_eq!(
+        received.header("content-type"),
+        Some("application/x-ndjson")
+    );
+    assert_eq!(received.body_len, CHUNK_SIZE);
+}
+
 #[tokio::test]
 async fn the_content_length_argument_wins_over_a_manually_added_header() {
     // Two `content-length` header lines are a protocol violation, not merely a